@@ -0,0 +1,92 @@
+//! Scalar vs vectorized `dot`, squared distance and `zip_map` on embedding
+//! sized vectors, plus the public `math::dot`, `rbf_kernel` and
+//! `apply_kernel2` entry points. The default working set fits in L2 so the
+//! kernels, not memory, are measured. Also reports how far the vectorized
+//! results drift from the scalar ones.
+//!
+//! `cargo bench --bench kernels [-- <dim> <pairs> <rounds>]`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use motion_core::kernel::{apply_kernel2, rbf_kernel};
+use motion_core::math::dot;
+use motion_core::simd;
+
+/// Deterministic vectors in [-1, 1) from a small LCG.
+fn vectors(count: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    };
+    (0..count).map(|_| (0..dim).map(|_| next()).collect()).collect()
+}
+
+/// Runs `f` over every pair `rounds` times; returns the time and a checksum
+/// so the work is not optimized away.
+fn time<F: Fn(&[f32], &[f32]) -> f32>(a: &[Vec<f32>], b: &[Vec<f32>], rounds: usize, f: F) -> (Duration, f32) {
+    let started = Instant::now();
+    let mut checksum = 0.0;
+    for _ in 0..rounds {
+        for (x, y) in a.iter().zip(b) {
+            checksum += f(black_box(x), black_box(y));
+        }
+    }
+    (started.elapsed(), checksum)
+}
+
+fn main() {
+    let args: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter_map(|a| a.parse().ok())
+        .collect();
+    let dim = args.first().copied().unwrap_or(128);
+    let pairs = args.get(1).copied().unwrap_or(1_000);
+    let rounds = args.get(2).copied().unwrap_or(1_000);
+    let calls = (pairs * rounds) as f64;
+
+    let a = vectors(pairs, dim, 1);
+    let b = vectors(pairs, dim, 2);
+    println!("{pairs} pairs of {dim}-dim vectors, {rounds} rounds");
+    let max_error = |fast: fn(&[f32], &[f32]) -> f32, scalar: fn(&[f32], &[f32]) -> f32| {
+        a.iter()
+            .zip(&b)
+            .map(|(x, y)| {
+                let (f, s) = (fast(x, y), scalar(x, y));
+                (f - s).abs() / s.abs().max(1.0)
+            })
+            .fold(0.0f32, f32::max)
+    };
+    println!(
+        "max relative error vs scalar: dot {:.2e}, sq distance {:.2e}",
+        max_error(simd::dot, simd::dot_scalar),
+        max_error(simd::squared_distance, simd::squared_distance_scalar)
+    );
+
+    let report = |label: &str, (elapsed, checksum): (Duration, f32), baseline: Option<Duration>| {
+        let ns = elapsed.as_secs_f64() * 1e9 / calls;
+        let speedup = baseline.map_or(String::new(), |b| format!("  {:>5.2}x", b.as_secs_f64() / elapsed.as_secs_f64()));
+        println!("{label:<28} {ns:>7.2} ns/call{speedup}  (checksum {checksum:.3})");
+        elapsed
+    };
+
+    let base = report("dot scalar", time(&a, &b, rounds, simd::dot_scalar), None);
+    report("dot chunked", time(&a, &b, rounds, simd::dot_chunked), Some(base));
+    report("dot dispatched", time(&a, &b, rounds, simd::dot), Some(base));
+    report("math::dot", time(&a, &b, rounds, |x, y| dot(x, y).unwrap()), Some(base));
+
+    let base = report("sq distance scalar", time(&a, &b, rounds, simd::squared_distance_scalar), None);
+    report("sq distance chunked", time(&a, &b, rounds, simd::squared_distance_chunked), Some(base));
+    report("sq distance dispatched", time(&a, &b, rounds, simd::squared_distance), Some(base));
+    // Small enough that the similarities of random vectors stay above zero.
+    let gamma = 1.0 / dim as f32;
+    let rbf_scalar = |x: &[f32], y: &[f32]| (-gamma * simd::squared_distance_scalar(x, y)).exp();
+    let base = report("rbf scalar", time(&a, &b, rounds, rbf_scalar), None);
+    report("rbf_kernel", time(&a, &b, rounds, |x, y| rbf_kernel(x, y, gamma).unwrap()), Some(base));
+
+    let step = |f: f32, t: f32| f * 0.9 + t * 0.1;
+    let zip_scalar = |x: &[f32], y: &[f32]| x.iter().zip(y).map(|(&f, &t)| step(f, t)).collect::<Vec<_>>()[0];
+    let base = report("zip_map scalar", time(&a, &b, rounds, zip_scalar), None);
+    report("apply_kernel2", time(&a, &b, rounds, |x, y| apply_kernel2(x, y, step).unwrap()[0]), Some(base));
+}
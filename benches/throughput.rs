@@ -0,0 +1,124 @@
+//! Single `core_loop` vs `ShardedSpace` throughput on a synthetic workload.
+//!
+//! `cargo bench --bench throughput [-- <users> <posts> <interactions>]`
+
+use std::time::{Duration, Instant};
+
+use motion_core::shard::ShardedSpace;
+use motion_core::{
+    Interaction, InteractionType, MotionInput, MotionOutput, MotionSpaceBuilder, PostInput,
+    PreparedInput, UserInput,
+};
+use tokio::sync::mpsc;
+
+const DIM: usize = 128;
+
+/// Deterministic workload: users, then posts, then a mix of post and user
+/// interactions. Embeddings are computed up front so only the core is timed.
+fn workload(users: usize, posts: usize, interactions: usize) -> Vec<PreparedInput> {
+    let space = MotionSpaceBuilder::new().dim(DIM).build().expect("valid config");
+    let mut inputs = Vec::with_capacity(users + posts + interactions);
+    for u in 0..users {
+        inputs.push(MotionInput::User(UserInput { id: format!("u{u}") }).into());
+    }
+    for p in 0..posts {
+        let text = format!("topic{} word{} word{}", p % 17, p % 101, p % 7);
+        let input = PostInput::new(format!("p{p}"), format!("u{}", p % users), &text);
+        let embedding = space.embedder.embed(&input.text);
+        inputs.push(PreparedInput {
+            input: MotionInput::Post(input),
+            embedding: Some(embedding),
+        });
+    }
+    // Small LCG so runs are comparable without pulling in a rng crate.
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move |n: usize| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) as usize % n
+    };
+    for _ in 0..interactions {
+        let interaction = if next(4) == 0 {
+            Interaction::new(
+                InteractionType::Follow,
+                format!("u{}", next(users)),
+                format!("u{}", next(users)),
+            )
+        } else {
+            Interaction::new(
+                InteractionType::Like,
+                format!("p{}", next(posts)),
+                format!("u{}", next(users)),
+            )
+        };
+        inputs.push(MotionInput::Interaction(interaction).into());
+    }
+    inputs
+}
+
+async fn drain(mut rx: mpsc::Receiver<MotionOutput>) -> usize {
+    let mut n = 0;
+    while rx.recv().await.is_some() {
+        n += 1;
+    }
+    n
+}
+
+async fn single(inputs: Vec<PreparedInput>) -> Duration {
+    let mut space = MotionSpaceBuilder::new().dim(DIM).build().expect("valid config");
+    let (in_tx, in_rx) = mpsc::channel::<PreparedInput>(1024);
+    let (out_tx, out_rx) = mpsc::channel(1024);
+    let sink = tokio::spawn(drain(out_rx));
+
+    let started = Instant::now();
+    let core = tokio::spawn(async move {
+        let _ = space.core_loop(in_rx, out_tx).await;
+    });
+    for input in inputs {
+        in_tx.send(input).await.expect("core alive");
+    }
+    drop(in_tx);
+    core.await.expect("core task");
+    sink.await.expect("sink task");
+    started.elapsed()
+}
+
+async fn sharded(inputs: Vec<PreparedInput>, shards: usize) -> Duration {
+    let builder = MotionSpaceBuilder::new().dim(DIM);
+    let (out_tx, out_rx) = mpsc::channel(1024);
+    let sink = tokio::spawn(drain(out_rx));
+
+    let started = Instant::now();
+    let mut space = ShardedSpace::from_builder(builder, shards, out_tx).expect("valid config");
+    for input in inputs {
+        space.apply(input).await.expect("shards alive");
+    }
+    space.shutdown().await.expect("shards join");
+    sink.await.expect("sink task");
+    started.elapsed()
+}
+
+fn main() {
+    let args: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter_map(|a| a.parse().ok())
+        .collect();
+    let users = args.first().copied().unwrap_or(2_000);
+    let posts = args.get(1).copied().unwrap_or(5_000);
+    let interactions = args.get(2).copied().unwrap_or(50_000);
+    let total = users + posts + interactions;
+
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    println!("{users} users, {posts} posts, {interactions} interactions, {cores} cores");
+
+    let report = |label: &str, elapsed: Duration| {
+        let rate = total as f64 / elapsed.as_secs_f64();
+        println!("{label:<12} {:>9.1} ms  {rate:>10.0} inputs/s", elapsed.as_secs_f64() * 1e3);
+    };
+
+    report("single", runtime.block_on(single(workload(users, posts, interactions))));
+    for shards in [2, 4, cores].into_iter().filter(|&n| n > 1) {
+        let elapsed = runtime.block_on(sharded(workload(users, posts, interactions), shards));
+        report(&format!("{shards} shards"), elapsed);
+    }
+}
@@ -0,0 +1,396 @@
+//! Bulk loading of users, posts and interactions from CSV or JSONL files.
+//!
+//! Files are picked by extension: `.csv` has a header row naming the fields,
+//! anything else is read as one JSON object per line. Fields:
+//!
+//! - users: `id`
+//! - posts: `id`, `user_id`, `text`, `timestamp` (unix millis)
+//! - interactions: `kind`, `src_id`, `dst_id`, `alpha` (optional), `timestamp`
+//!
+//! Posts are embedded on worker threads up front. Posts and interactions are
+//! then applied in timestamp order; at equal timestamps a post goes first so
+//! that interactions with it find it.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::math::VecN;
+use crate::motion_core::{MotionEntry, MotionSpace, MotionUser};
+use crate::motion_input::{Interaction, InteractionType, PostInput};
+
+/// One row of a users file.
+#[derive(Debug, Deserialize, Clone)]
+pub struct UserRecord {
+    /// User id.
+    pub id: String,
+}
+
+/// One row of a posts file.
+#[derive(Debug, Deserialize, Clone)]
+pub struct PostRecord {
+    /// Post id.
+    pub id: String,
+    /// Author's user id.
+    pub user_id: String,
+    /// Post text, embedded on load.
+    pub text: String,
+    /// Unix millis the post was created at.
+    pub timestamp: i64,
+}
+
+/// One row of an interactions file.
+#[derive(Debug, Deserialize, Clone)]
+pub struct InteractionRecord {
+    /// Interaction kind, by its snake_case name.
+    pub kind: InteractionType,
+    /// Source id; the post for post kinds.
+    pub src_id: String,
+    /// Destination id; the engaging user for post kinds.
+    pub dst_id: String,
+    /// Overrides the kind's default alpha.
+    #[serde(default)]
+    pub alpha: Option<f32>,
+    /// Unix millis the interaction happened at.
+    pub timestamp: i64,
+}
+
+/// A row that was skipped, either because it did not parse or because the
+/// space rejected it.
+#[derive(Debug, Serialize, Clone)]
+pub struct BadRow {
+    /// Path of the file the row came from.
+    pub file: String,
+    /// 1-based line number in the source file.
+    pub line: usize,
+    /// Why the row was skipped.
+    pub reason: String,
+}
+
+/// What one [`BatchLoader::load`] did.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct LoadReport {
+    /// Users entered.
+    pub users: usize,
+    /// Posts entered.
+    pub posts: usize,
+    /// Interactions applied.
+    pub interactions: usize,
+    /// Rows skipped, sorted by file and line.
+    pub bad_rows: Vec<BadRow>,
+    /// Wall time of the whole load.
+    pub elapsed_ms: u128,
+}
+
+/// Passed to the progress callback while records are applied.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadProgress {
+    /// Posts and interactions processed so far, applied or not.
+    pub applied: usize,
+    /// Posts and interactions to process.
+    pub total: usize,
+}
+
+/// Loads users, posts and interactions from files into a space.
+#[derive(Debug, Clone)]
+pub struct BatchLoader {
+    users: Vec<PathBuf>,
+    posts: Vec<PathBuf>,
+    interactions: Vec<PathBuf>,
+    workers: usize,
+    progress_every: usize,
+}
+
+impl Default for BatchLoader {
+    fn default() -> Self {
+        Self {
+            users: Vec::new(),
+            posts: Vec::new(),
+            interactions: Vec::new(),
+            workers: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            progress_every: 10_000,
+        }
+    }
+}
+
+struct Sourced<T> {
+    file: String,
+    line: usize,
+    record: T,
+}
+
+enum Event {
+    Post(Sourced<PostRecord>, VecN),
+    Interaction(Sourced<InteractionRecord>),
+}
+
+impl Event {
+    fn sort_key(&self) -> (i64, u8) {
+        match self {
+            Event::Post(p, _) => (p.record.timestamp, 0),
+            Event::Interaction(i) => (i.record.timestamp, 1),
+        }
+    }
+}
+
+impl BatchLoader {
+    /// A loader with no files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a users file.
+    pub fn users(mut self, path: impl Into<PathBuf>) -> Self {
+        self.users.push(path.into());
+        self
+    }
+
+    /// Adds a posts file.
+    pub fn posts(mut self, path: impl Into<PathBuf>) -> Self {
+        self.posts.push(path.into());
+        self
+    }
+
+    /// Adds an interactions file.
+    pub fn interactions(mut self, path: impl Into<PathBuf>) -> Self {
+        self.interactions.push(path.into());
+        self
+    }
+
+    /// Number of threads used to embed posts. Zero is treated as one.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// How many applied records between progress callbacks.
+    pub fn progress_every(mut self, every: usize) -> Self {
+        self.progress_every = every.max(1);
+        self
+    }
+
+    /// Reads every file and applies its records to `space`. Bad rows are
+    /// skipped and reported rather than failing the load.
+    pub fn load(&self, space: &mut MotionSpace) -> LoadReport {
+        self.load_with_progress(space, |_| {})
+    }
+
+    /// [`BatchLoader::load`], calling `progress` every
+    /// [`BatchLoader::progress_every`] records.
+    pub fn load_with_progress<F>(&self, space: &mut MotionSpace, mut progress: F) -> LoadReport
+    where
+        F: FnMut(LoadProgress),
+    {
+        let started = Instant::now();
+        let mut report = LoadReport::default();
+
+        let users: Vec<Sourced<UserRecord>> = read_all(&self.users, &mut report.bad_rows);
+        let posts: Vec<Sourced<PostRecord>> = read_all(&self.posts, &mut report.bad_rows);
+        let interactions: Vec<Sourced<InteractionRecord>> =
+            read_all(&self.interactions, &mut report.bad_rows);
+
+        let mut known_users: HashSet<String> = space
+            .entries
+            .iter()
+            .filter_map(|e| match e {
+                MotionEntry::User(u) => Some(u.id.clone()),
+                _ => None,
+            })
+            .collect();
+        for user in users {
+            if known_users.insert(user.record.id.clone()) {
+                space.enter(MotionEntry::User(MotionUser::new(&user.record.id, space.dim)));
+                report.users += 1;
+            }
+        }
+
+        let texts: Vec<&str> = posts.iter().map(|p| p.record.text.as_str()).collect();
+        let embeddings = space.embedder.embed_batch(&texts, self.workers);
+        let mut events: Vec<Event> = posts
+            .into_iter()
+            .zip(embeddings)
+            .map(|(post, embedding)| Event::Post(post, embedding))
+            .chain(interactions.into_iter().map(Event::Interaction))
+            .collect();
+        events.sort_by_key(Event::sort_key);
+
+        let mut known_posts: HashSet<String> = space
+            .entries
+            .iter()
+            .filter_map(|e| match e {
+                MotionEntry::Post(p) => Some(p.id.clone()),
+                _ => None,
+            })
+            .collect();
+        let total = events.len();
+        for (i, event) in events.into_iter().enumerate() {
+            match event {
+                Event::Post(post, embedding) => {
+                    if !known_posts.insert(post.record.id.clone()) {
+                        report.bad_rows.push(bad_row(&post, "duplicate post id"));
+                    } else {
+                        let input = PostInput::new(&post.record.id, &post.record.user_id, &post.record.text)
+                            .with_created_at(post.record.timestamp);
+                        match space.enter_post(&input, embedding) {
+                            Ok(_) => report.posts += 1,
+                            Err(e) => report.bad_rows.push(bad_row(&post, e.to_string())),
+                        }
+                    }
+                }
+                Event::Interaction(interaction) => {
+                    let r = &interaction.record;
+                    let mut input = Interaction::new(r.kind, &r.src_id, &r.dst_id).with_at(r.timestamp);
+                    if let Some(alpha) = r.alpha {
+                        input = input.with_alpha(alpha);
+                    }
+                    match space.apply_interaction(input) {
+                        Ok(_) => report.interactions += 1,
+                        Err(e) => report.bad_rows.push(bad_row(&interaction, e.to_string())),
+                    }
+                }
+            }
+            if (i + 1) % self.progress_every == 0 || i + 1 == total {
+                progress(LoadProgress { applied: i + 1, total });
+            }
+        }
+
+        report
+            .bad_rows
+            .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        report.elapsed_ms = started.elapsed().as_millis();
+        report
+    }
+}
+
+fn bad_row<T>(source: &Sourced<T>, reason: impl Into<String>) -> BadRow {
+    BadRow {
+        file: source.file.clone(),
+        line: source.line,
+        reason: reason.into(),
+    }
+}
+
+fn read_all<T: DeserializeOwned>(paths: &[PathBuf], bad_rows: &mut Vec<BadRow>) -> Vec<Sourced<T>> {
+    let mut records = Vec::new();
+    for path in paths {
+        let file = path.display().to_string();
+        let result = if is_csv(path) {
+            read_csv(path, &file, &mut records, bad_rows)
+        } else {
+            read_jsonl(path, &file, &mut records, bad_rows)
+        };
+        if let Err(reason) = result {
+            bad_rows.push(BadRow { file, line: 0, reason });
+        }
+    }
+    records
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+}
+
+fn read_csv<T: DeserializeOwned>(
+    path: &Path,
+    file: &str,
+    records: &mut Vec<Sourced<T>>,
+    bad_rows: &mut Vec<BadRow>,
+) -> Result<(), String> {
+    let mut reader = csv::Reader::from_path(path).map_err(|e| e.to_string())?;
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    for row in reader.records() {
+        // Quoted fields may span lines, so rows are not one line each.
+        let row = row.and_then(|row| {
+            let line = row.position().map_or(0, |p| p.line() as usize);
+            row.deserialize::<T>(Some(&headers)).map(|record| (line, record))
+        });
+        match row {
+            Ok((line, record)) => records.push(Sourced { file: file.to_string(), line, record }),
+            Err(e) => {
+                let line = e.position().map_or(0, |p| p.line() as usize);
+                bad_rows.push(BadRow { file: file.to_string(), line, reason: e.to_string() });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_jsonl<T: DeserializeOwned>(
+    path: &Path,
+    file: &str,
+    records: &mut Vec<Sourced<T>>,
+    bad_rows: &mut Vec<BadRow>,
+) -> Result<(), String> {
+    let reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    for (i, line) in reader.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(Sourced { file: file.to_string(), line: line_no, record }),
+            Err(e) => bad_rows.push(BadRow { file: file.to_string(), line: line_no, reason: e.to_string() }),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to a file in the temp dir that is removed on drop.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("motion-batch-{}-{name}", std::process::id()));
+            std::fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn csv_bad_rows_report_their_source_line() {
+        let posts = TempFile::new(
+            "posts.csv",
+            "id,user_id,text,timestamp\np1,alice,\"two\nlines\",1\np2,bob,hello,soon\np3,bob,fine,3\n",
+        );
+        let mut space = MotionSpace::new(16);
+        let report = BatchLoader::new().posts(&posts.0).workers(1).load(&mut space);
+        assert_eq!(report.posts, 2);
+        assert_eq!(report.bad_rows.len(), 1);
+        assert_eq!(report.bad_rows[0].line, 4);
+    }
+
+    #[test]
+    fn jsonl_duplicate_posts_are_bad_rows() {
+        let posts = TempFile::new(
+            "posts.jsonl",
+            concat!(
+                r#"{"id":"p1","user_id":"alice","text":"hi","timestamp":1}"#,
+                "\n\n",
+                r#"{"id":"p1","user_id":"bob","text":"again","timestamp":2}"#,
+                "\n",
+            ),
+        );
+        let mut space = MotionSpace::new(16);
+        let report = BatchLoader::new().posts(&posts.0).workers(1).load(&mut space);
+        assert_eq!(report.posts, 1);
+        assert_eq!(report.bad_rows.len(), 1);
+        assert_eq!(report.bad_rows[0].line, 3);
+    }
+}
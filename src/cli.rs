@@ -0,0 +1,356 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use motion_core::queue::ShedPolicy;
+use motion_core::projection::{Method, MIN_SVG_SIZE};
+use motion_core::{ClusterConfig, Exploration, TopicConfig};
+
+#[derive(Debug, Parser)]
+#[command(name = "motion", version, about = "Motion space engine")]
+pub struct Cli {
+    /// JSON config for a fresh motion space (ignored when a snapshot is loaded)
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// How events and reports are written to stdout
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    pub format: OutputFormat,
+
+    /// Only report rejected inputs and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print full coords instead of a short preview; also raises the log
+    /// level to debug (-v) or trace (-vv)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// How diagnostics are written to stderr
+    #[arg(long, value_enum, default_value_t = LogFormat::Pretty, global = true)]
+    pub log_format: LogFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the engine on a live input source (the default)
+    Serve(ServeArgs),
+    /// Apply a recorded input file, then exit
+    Replay(ReplayArgs),
+    /// Bulk-load users, posts and interactions from CSV/JSONL files
+    Load(LoadArgs),
+    /// Write every entry of a snapshot
+    Export(ExportArgs),
+    /// Print summary statistics of a snapshot
+    Stats(StatsArgs),
+    /// Build a diversified feed for one user from a snapshot
+    Feed(FeedArgs),
+    /// Suggest users to follow for one user from a snapshot
+    WhoToFollow(WhoToFollowArgs),
+    /// Summarize a snapshot's interaction graph: degrees, PageRank, components
+    Graph(GraphArgs),
+    /// List the interest clusters of a snapshot with their members
+    Clusters(ClustersArgs),
+    /// Discover post topics in a snapshot and write a JSON topic report
+    Topics(TopicsArgs),
+    /// Project a snapshot's users and posts to 2D/3D for plotting
+    Project(ProjectArgs),
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Command::Serve(ServeArgs::default())
+    }
+}
+
+#[derive(Debug, Args, Default)]
+pub struct SnapshotArgs {
+    /// Start from this snapshot instead of an empty space
+    #[arg(long)]
+    pub load: Option<PathBuf>,
+
+    /// Write a snapshot here once the input is exhausted
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+}
+
+/// Channel capacity used when `--queue-size` is not given.
+const DEFAULT_QUEUE_SIZE: usize = 64;
+
+#[derive(Debug, Args)]
+pub struct PipelineArgs {
+    /// Threads embedding post text ahead of the core (defaults to the number of cores)
+    #[arg(long)]
+    pub embed_workers: Option<usize>,
+
+    /// Partition the space by user id across this many core tasks
+    #[arg(long, default_value_t = 1)]
+    pub shards: usize,
+
+    /// Capacity of each channel between the input, embedding and core stages
+    #[arg(long, default_value_t = DEFAULT_QUEUE_SIZE)]
+    pub queue_size: usize,
+
+    /// What to do with interactions once the input queue is full:
+    /// block, drop_oldest, reject_new or coalesce
+    #[arg(long, default_value_t = ShedPolicy::Block)]
+    pub shed_policy: ShedPolicy,
+
+    /// Serve Prometheus metrics at http://<addr>/metrics, e.g. 127.0.0.1:9464
+    #[arg(long)]
+    pub metrics_addr: Option<String>,
+}
+
+// Kept in line with the clap defaults; used when no subcommand is given.
+impl Default for PipelineArgs {
+    fn default() -> Self {
+        Self {
+            embed_workers: None,
+            shards: 1,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            shed_policy: ShedPolicy::Block,
+            metrics_addr: None,
+        }
+    }
+}
+
+#[derive(Debug, Args, Default)]
+pub struct ServeArgs {
+    /// `stdin`, a file path, or `tcp://<addr>` to accept line connections
+    #[arg(long, default_value = "stdin")]
+    pub input: InputSource,
+
+    #[command(flatten)]
+    pub pipeline: PipelineArgs,
+
+    #[command(flatten)]
+    pub snapshot: SnapshotArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// File of REPL commands or JSON inputs, one per line
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub pipeline: PipelineArgs,
+
+    #[command(flatten)]
+    pub snapshot: SnapshotArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct LoadArgs {
+    /// User files (`id`); repeatable
+    #[arg(long)]
+    pub users: Vec<PathBuf>,
+
+    /// Post files (`id,user_id,text,timestamp`); repeatable
+    #[arg(long)]
+    pub posts: Vec<PathBuf>,
+
+    /// Interaction files (`kind,src_id,dst_id,alpha,timestamp`); repeatable
+    #[arg(long)]
+    pub interactions: Vec<PathBuf>,
+
+    /// Embedding threads (defaults to the number of cores)
+    #[arg(long)]
+    pub workers: Option<usize>,
+
+    #[command(flatten)]
+    pub snapshot: SnapshotArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[arg(long)]
+    pub snapshot: PathBuf,
+
+    /// Write to this file instead of stdout
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    #[arg(long)]
+    pub snapshot: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct FeedArgs {
+    #[arg(long)]
+    pub snapshot: PathBuf,
+
+    #[arg(long)]
+    pub user: String,
+
+    /// Number of posts in the feed
+    #[arg(long, default_value_t = 10)]
+    pub size: usize,
+
+    /// Nearest posts considered before re-ranking
+    #[arg(long, default_value_t = 100)]
+    pub candidates: usize,
+
+    /// Relevance vs diversity trade-off; 1 is plain nearest-neighbour ranking
+    #[arg(long, default_value_t = 0.7)]
+    pub lambda: f32,
+
+    /// At most this many posts per author (0 for no limit)
+    #[arg(long, default_value_t = 2)]
+    pub max_per_author: usize,
+
+    /// Leave out posts older than this many hours
+    #[arg(long)]
+    pub max_age_hours: Option<f64>,
+
+    /// Halve a post's relevance every this many hours of age
+    #[arg(long)]
+    pub half_life_hours: Option<f64>,
+
+    /// greedy, thompson, epsilon:<p> or softmax:<temperature>
+    #[arg(long, default_value = "greedy")]
+    pub explore: Exploration,
+
+    /// Seed for randomized exploration, for reproducible feeds
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct WhoToFollowArgs {
+    #[arg(long)]
+    pub snapshot: PathBuf,
+
+    #[arg(long)]
+    pub user: String,
+
+    /// Number of suggestions
+    #[arg(short, long, default_value_t = 10)]
+    pub k: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct GraphArgs {
+    #[arg(long)]
+    pub snapshot: PathBuf,
+
+    /// Number of top-ranked users and largest components to list
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+
+    /// PageRank damping factor
+    #[arg(long, default_value_t = 0.85)]
+    pub damping: f32,
+
+    /// PageRank power iterations
+    #[arg(long, default_value_t = 50)]
+    pub iterations: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct ClustersArgs {
+    #[arg(long)]
+    pub snapshot: PathBuf,
+
+    /// Re-cluster the snapshot's users into this many clusters instead of
+    /// reporting the clusters it was saved with
+    #[arg(short, long)]
+    pub k: Option<usize>,
+
+    /// Coords per centroid update when re-clustering
+    #[arg(long, default_value_t = ClusterConfig::default().batch_size)]
+    pub batch_size: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct TopicsArgs {
+    #[arg(long)]
+    pub snapshot: PathBuf,
+
+    /// Kernel similarity at which two posts count as neighbours
+    #[arg(long, default_value_t = TopicConfig::default().min_similarity)]
+    pub min_similarity: f32,
+
+    /// Neighbours (the post included) that make a post a topic core
+    #[arg(long, default_value_t = TopicConfig::default().min_posts)]
+    pub min_posts: usize,
+
+    /// Label tokens per topic
+    #[arg(long, default_value_t = TopicConfig::default().labels)]
+    pub labels: usize,
+
+    /// Write the report here instead of stdout
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ProjectArgs {
+    #[arg(long)]
+    pub snapshot: PathBuf,
+
+    /// pca, tsne or tsne:<perplexity>
+    #[arg(long, default_value = "pca")]
+    pub method: Method,
+
+    /// 2 or 3
+    #[arg(long, default_value_t = 2)]
+    pub dims: usize,
+
+    /// t-SNE gradient steps
+    #[arg(long, default_value_t = Method::DEFAULT_ITERATIONS)]
+    pub iterations: usize,
+
+    /// Write the points here instead of stdout: CSV with the text format, a
+    /// JSON array with the json format
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+
+    /// Also draw the x/y plane as an SVG scatter plot
+    #[arg(long)]
+    pub svg: Option<PathBuf>,
+
+    /// Width and height of the SVG in pixels
+    #[arg(long, default_value_t = 800, value_parser = clap::value_parser!(u32).range(MIN_SVG_SIZE as i64..))]
+    pub svg_size: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum InputSource {
+    #[default]
+    Stdin,
+    File(PathBuf),
+    Socket(String),
+}
+
+impl FromStr for InputSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("input source cannot be empty".to_string()),
+            "-" | "stdin" => Ok(InputSource::Stdin),
+            _ => match s.strip_prefix("tcp://") {
+                Some(addr) => Ok(InputSource::Socket(addr.to_string())),
+                None => Ok(InputSource::File(PathBuf::from(s))),
+            },
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Interest communities: online mini-batch k-means over user coords.
+//!
+//! Every time a user moves they are assigned to the nearest centroid right
+//! away, and their new coord is queued. Once `batch_size` coords are queued
+//! each centroid steps toward its queued members with a per-centroid rate of
+//! `1 / members seen so far`, so centroids settle as they absorb more
+//! points. The first coords of the first `k` users seed the centroids, so
+//! one user moving around cannot take more than one seed.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::simd;
+
+/// Parameters of [`Clusters`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct ClusterConfig {
+    /// Number of clusters.
+    pub k: usize,
+    /// Queued coords per centroid update.
+    pub batch_size: usize,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self { k: 8, batch_size: 32 }
+    }
+}
+
+/// A user joined, left or switched clusters. `from` is `None` for a user's
+/// first assignment and `to` is `None` once they are deleted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ClusterChange {
+    /// The user whose membership changed.
+    pub user_id: String,
+    /// The cluster they left.
+    pub from: Option<usize>,
+    /// The cluster they joined.
+    pub to: Option<usize>,
+}
+
+/// One cluster as reported by [`Clusters::summaries`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClusterSummary {
+    /// Cluster id, the index of its centroid.
+    pub id: usize,
+    /// Current centroid coord.
+    pub centroid: Vec<f32>,
+    /// Coords absorbed by the centroid so far.
+    pub absorbed: u64,
+    /// Ids of the users currently assigned to the cluster.
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Centroid {
+    coord: Vec<f32>,
+    absorbed: u64,
+}
+
+/// Online k-means over user coords; see the module docs.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Clusters {
+    /// The parameters the clusters were created with.
+    pub config: ClusterConfig,
+    centroids: Vec<Centroid>,
+    /// User id -> cluster id.
+    assignments: HashMap<String, usize>,
+    /// Coords waiting for the next centroid update, with their cluster.
+    pending: Vec<(usize, Vec<f32>)>,
+}
+
+impl Clusters {
+    /// No centroids yet; the first users to move seed them.
+    pub fn new(config: ClusterConfig) -> Self {
+        Self {
+            config,
+            centroids: Vec::with_capacity(config.k),
+            assignments: HashMap::new(),
+            pending: Vec::with_capacity(config.batch_size),
+        }
+    }
+
+    /// Number of centroids seeded so far.
+    pub fn len(&self) -> usize {
+        self.centroids.len()
+    }
+
+    /// Whether no centroid has been seeded yet.
+    pub fn is_empty(&self) -> bool {
+        self.centroids.is_empty()
+    }
+
+    /// The cluster `user_id` is assigned to.
+    pub fn cluster_of(&self, user_id: &str) -> Option<usize> {
+        self.assignments.get(user_id).copied()
+    }
+
+    /// Coord of the given cluster's centroid.
+    pub fn centroid(&self, cluster: usize) -> Option<&[f32]> {
+        self.centroids.get(cluster).map(|c| c.coord.as_slice())
+    }
+
+    /// Index of the centroid closest to `coord`.
+    pub fn nearest(&self, coord: &[f32]) -> Option<usize> {
+        self.centroids
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, simd::squared_distance(&c.coord, coord)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Records `user_id` at `coord` and returns their membership change, if
+    /// any.
+    pub fn observe(&mut self, user_id: &str, coord: &[f32]) -> Option<ClusterChange> {
+        let seeds = self.centroids.len() < self.config.k
+            && !self.assignments.contains_key(user_id)
+            && !self.centroids.iter().any(|c| c.coord.as_slice() == coord);
+        let cluster = if seeds {
+            self.centroids.push(Centroid { coord: coord.to_vec(), absorbed: 1 });
+            self.centroids.len() - 1
+        } else {
+            let cluster = self.nearest(coord)?;
+            self.pending.push((cluster, coord.to_vec()));
+            if self.pending.len() >= self.config.batch_size.max(1) {
+                self.flush();
+            }
+            cluster
+        };
+        let from = self.assignments.insert(user_id.to_string(), cluster);
+        (from != Some(cluster)).then(|| ClusterChange {
+            user_id: user_id.to_string(),
+            from,
+            to: Some(cluster),
+        })
+    }
+
+    /// Applies the queued coords to their centroids.
+    pub fn flush(&mut self) {
+        for (cluster, coord) in self.pending.drain(..) {
+            let centroid = &mut self.centroids[cluster];
+            centroid.absorbed += 1;
+            let rate = 1.0 / centroid.absorbed as f32;
+            for (c, x) in centroid.coord.iter_mut().zip(&coord) {
+                *c += rate * (x - *c);
+            }
+        }
+    }
+
+    /// Drops `user_id`'s assignment and returns the change, if they had one.
+    pub fn remove(&mut self, user_id: &str) -> Option<ClusterChange> {
+        let from = self.assignments.remove(user_id)?;
+        Some(ClusterChange {
+            user_id: user_id.to_string(),
+            from: Some(from),
+            to: None,
+        })
+    }
+
+    /// Every cluster with its centroid and members, in cluster order.
+    pub fn summaries(&self) -> Vec<ClusterSummary> {
+        let mut summaries: Vec<ClusterSummary> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(id, c)| ClusterSummary {
+                id,
+                centroid: c.coord.clone(),
+                absorbed: c.absorbed,
+                members: Vec::new(),
+            })
+            .collect();
+        for (user_id, &cluster) in &self.assignments {
+            summaries[cluster].members.push(user_id.clone());
+        }
+        for summary in &mut summaries {
+            summary.members.sort();
+        }
+        summaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clusters(k: usize) -> Clusters {
+        Clusters::new(ClusterConfig { k, batch_size: 1 })
+    }
+
+    #[test]
+    fn a_moving_user_seeds_one_centroid() {
+        let mut c = clusters(4);
+        assert_eq!(
+            c.observe("alice", &[1.0, 0.0]),
+            Some(ClusterChange { user_id: "alice".into(), from: None, to: Some(0) })
+        );
+        assert_eq!(c.observe("alice", &[0.0, 1.0]), None);
+        assert_eq!(c.observe("alice", &[-1.0, 0.0]), None);
+        assert_eq!(c.len(), 1);
+
+        c.observe("bob", &[0.0, -1.0]);
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.cluster_of("bob"), Some(1));
+    }
+
+    #[test]
+    fn users_join_the_nearest_centroid_once_seeded() {
+        let mut c = clusters(2);
+        c.observe("a", &[1.0, 0.0]);
+        c.observe("b", &[-1.0, 0.0]);
+        c.observe("c", &[0.9, 0.1]);
+        assert_eq!(c.cluster_of("c"), Some(0));
+        assert_eq!(
+            c.observe("c", &[-0.9, 0.1]),
+            Some(ClusterChange { user_id: "c".into(), from: Some(0), to: Some(1) })
+        );
+        assert_eq!(c.remove("c").and_then(|change| change.from), Some(1));
+        assert_eq!(c.summaries()[1].members, vec!["b".to_string()]);
+    }
+}
@@ -0,0 +1,361 @@
+//! Building a [`MotionSpace`] from code or from a JSON config file.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::cluster::{ClusterConfig, Clusters};
+use crate::embedding::{Embedder, TokenIndex, EMBEDDING_DIM};
+use crate::kernel::Kernel;
+use crate::motion_core::{Dynamics, MotionSpace};
+
+/// Why a config could not be read or a space could not be built from it.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// Reading the config file failed.
+    #[error("failed to read config {path}: {reason}")]
+    Io {
+        /// The config file.
+        path: String,
+        /// The underlying IO error.
+        reason: String,
+    },
+
+    /// The config is not valid JSON for [`MotionConfig`].
+    #[error("failed to parse config: {0}")]
+    Parse(String),
+
+    /// A value is out of range.
+    #[error("invalid config value for {field}: {reason}")]
+    Invalid {
+        /// Dotted path of the offending field, e.g. `kernel.gamma`.
+        field: &'static str,
+        /// What is wrong with its value.
+        reason: String,
+    },
+}
+
+/// Expected number of entries, used to pre-size storage.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct CapacityHints {
+    /// Expected number of users.
+    pub users: usize,
+    /// Expected number of posts.
+    pub posts: usize,
+}
+
+/// Which secondary indexes the space maintains.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Author id -> post ids, used by `posts_by_user` and `is_author`.
+    pub by_author: bool,
+    /// Embedding bucket -> post tokens, used to label discovered topics.
+    pub tokens: bool,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self { by_author: true, tokens: true }
+    }
+}
+
+/// Serializable form of every builder setting. Missing fields take their
+/// defaults, so `{}` is a valid config.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MotionConfig {
+    /// Dimension of every coord.
+    pub dim: usize,
+    /// Similarity kernel interactions are weighted by.
+    pub kernel: Kernel,
+    /// Defaults to the hashed embedder at `dim`.
+    pub embedder: Option<Embedder>,
+    /// How interactions move coords.
+    pub dynamics: Dynamics,
+    /// Storage pre-sizing.
+    pub capacity: CapacityHints,
+    /// Secondary indexes to maintain.
+    pub indexes: IndexConfig,
+    /// Reject interactions of users with themselves and their own posts.
+    pub exclude_self_engagement: bool,
+    /// Online k-means over user coords; off when absent.
+    pub clusters: Option<ClusterConfig>,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self {
+            dim: EMBEDDING_DIM,
+            kernel: Kernel::RBF { gamma: 2.0 },
+            embedder: None,
+            dynamics: Dynamics::default(),
+            capacity: CapacityHints::default(),
+            indexes: IndexConfig::default(),
+            exclude_self_engagement: false,
+            clusters: None,
+        }
+    }
+}
+
+impl MotionConfig {
+    /// Parses a config from JSON.
+    pub fn from_json_str(json: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(json).map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    /// Reads and parses a JSON config file.
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|e| ConfigError::Io {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+        Self::from_json_str(&json)
+    }
+}
+
+/// Builds a validated [`MotionSpace`]; every setting defaults to
+/// [`MotionConfig::default`].
+#[derive(Debug, Clone, Default)]
+pub struct MotionSpaceBuilder {
+    config: MotionConfig,
+}
+
+impl MotionSpaceBuilder {
+    /// A builder with the default config.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder starting from `config`.
+    pub fn from_config(config: MotionConfig) -> Self {
+        Self { config }
+    }
+
+    /// Sets [`MotionConfig::dim`].
+    pub fn dim(mut self, dim: usize) -> Self {
+        self.config.dim = dim;
+        self
+    }
+
+    /// Sets [`MotionConfig::kernel`].
+    pub fn kernel(mut self, kernel: Kernel) -> Self {
+        self.config.kernel = kernel;
+        self
+    }
+
+    /// Sets [`MotionConfig::embedder`].
+    pub fn embedder(mut self, embedder: Embedder) -> Self {
+        self.config.embedder = Some(embedder);
+        self
+    }
+
+    /// Sets [`MotionConfig::dynamics`].
+    pub fn dynamics(mut self, dynamics: Dynamics) -> Self {
+        self.config.dynamics = dynamics;
+        self
+    }
+
+    /// Sets [`MotionConfig::capacity`].
+    pub fn capacity(mut self, users: usize, posts: usize) -> Self {
+        self.config.capacity = CapacityHints { users, posts };
+        self
+    }
+
+    /// Sets [`MotionConfig::indexes`].
+    pub fn indexes(mut self, indexes: IndexConfig) -> Self {
+        self.config.indexes = indexes;
+        self
+    }
+
+    /// Sets [`MotionConfig::exclude_self_engagement`].
+    pub fn exclude_self_engagement(mut self, exclude: bool) -> Self {
+        self.config.exclude_self_engagement = exclude;
+        self
+    }
+
+    /// Turns clustering on with `clusters`.
+    pub fn clusters(mut self, clusters: ClusterConfig) -> Self {
+        self.config.clusters = Some(clusters);
+        self
+    }
+
+    /// The config as set so far.
+    pub fn config(&self) -> &MotionConfig {
+        &self.config
+    }
+
+    /// Checks every value without building anything.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let c = &self.config;
+        if c.dim == 0 {
+            return Err(invalid("dim", "must be greater than zero"));
+        }
+        if let Some(embedder) = &c.embedder {
+            if embedder.dim != c.dim {
+                return Err(invalid(
+                    "embedder.dim",
+                    format!("{} does not match space dim {}", embedder.dim, c.dim),
+                ));
+            }
+            check_finite_non_negative("embedder.token_weight", embedder.token_weight)?;
+            check_finite_non_negative("embedder.trigram_weight", embedder.trigram_weight)?;
+        }
+        match c.kernel {
+            Kernel::RBF { gamma } => {
+                if !gamma.is_finite() || gamma <= 0.0 {
+                    return Err(invalid("kernel.gamma", format!("{} must be positive", gamma)));
+                }
+            }
+        }
+        check_unit_interval("dynamics.decay", c.dynamics.decay)?;
+        check_unit_interval("dynamics.max_repulsion", c.dynamics.max_repulsion)?;
+        check_unit_interval("dynamics.post_drift", c.dynamics.post_drift)?;
+        if let Some(clusters) = &c.clusters {
+            if clusters.k == 0 {
+                return Err(invalid("clusters.k", "must be greater than zero"));
+            }
+            if clusters.batch_size == 0 {
+                return Err(invalid("clusters.batch_size", "must be greater than zero"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the config and builds an empty space from it.
+    pub fn build(self) -> Result<MotionSpace, ConfigError> {
+        self.validate()?;
+        let c = self.config;
+        let mut space = MotionSpace::new(c.dim);
+        space.kernel = c.kernel;
+        if let Some(embedder) = c.embedder {
+            space.embedder = embedder;
+        }
+        space.dynamics = c.dynamics;
+        space.exclude_self_engagement = c.exclude_self_engagement;
+        space.entries.reserve(c.capacity.users + c.capacity.posts);
+        space.user_posts = c
+            .indexes
+            .by_author
+            .then(|| HashMap::with_capacity(c.capacity.users));
+        space.token_index = c.indexes.tokens.then(|| TokenIndex::new(c.dim));
+        space.clusters = c.clusters.map(Clusters::new);
+        Ok(space)
+    }
+}
+
+fn invalid(field: &'static str, reason: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid { field, reason: reason.into() }
+}
+
+fn check_unit_interval(field: &'static str, value: f32) -> Result<(), ConfigError> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(invalid(field, format!("{} must be within [0, 1]", value)));
+    }
+    Ok(())
+}
+
+fn check_finite_non_negative(field: &'static str, value: f32) -> Result<(), ConfigError> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(invalid(field, format!("{} must be finite and non-negative", value)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(json: &str) -> Result<MotionSpace, ConfigError> {
+        MotionSpaceBuilder::from_config(MotionConfig::from_json_str(json)?).build()
+    }
+
+    fn invalid_field(json: &str) -> &'static str {
+        match build(json) {
+            Err(ConfigError::Invalid { field, .. }) => field,
+            Err(e) => panic!("{json}: {e}"),
+            Ok(_) => panic!("{json} was accepted"),
+        }
+    }
+
+    #[test]
+    fn an_empty_config_builds_the_default_space() {
+        let space = build("{}").unwrap();
+        let defaults = Dynamics::default();
+        assert_eq!(space.dim, EMBEDDING_DIM);
+        assert_eq!(space.embedder.dim, EMBEDDING_DIM);
+        assert!(matches!(space.kernel, Kernel::RBF { gamma } if gamma == 2.0));
+        assert_eq!(space.dynamics.decay, defaults.decay);
+        assert_eq!(space.dynamics.max_repulsion, defaults.max_repulsion);
+        assert_eq!(space.dynamics.post_drift, defaults.post_drift);
+        assert!(!space.exclude_self_engagement);
+        assert!(space.user_posts.is_some());
+        assert!(space.token_index.as_ref().is_some_and(|index| index.dim() == EMBEDDING_DIM));
+        assert!(space.clusters.is_none());
+        assert!(space.entries.is_empty());
+    }
+
+    #[test]
+    fn set_fields_override_the_defaults() {
+        let space = build(
+            r#"{"dim": 8, "embedder": {"dim": 8}, "indexes": {"tokens": false},
+                "dynamics": {"decay": 0.5}, "clusters": {"k": 3}}"#,
+        )
+        .unwrap();
+        assert_eq!((space.dim, space.embedder.dim), (8, 8));
+        assert!(space.token_index.is_none() && space.user_posts.is_some());
+        assert_eq!(space.dynamics.decay, 0.5);
+        assert_eq!(space.dynamics.post_drift, Dynamics::default().post_drift);
+        assert!(space.clusters.is_some());
+    }
+
+    #[test]
+    fn each_invalid_value_names_its_field() {
+        let cases = [
+            (r#"{"dim": 0}"#, "dim"),
+            (r#"{"dim": 8, "embedder": {"dim": 16}}"#, "embedder.dim"),
+            (r#"{"embedder": {"token_weight": -1}}"#, "embedder.token_weight"),
+            (r#"{"embedder": {"trigram_weight": -0.5}}"#, "embedder.trigram_weight"),
+            (r#"{"kernel": {"RBF": {"gamma": 0}}}"#, "kernel.gamma"),
+            (r#"{"kernel": {"RBF": {"gamma": -1}}}"#, "kernel.gamma"),
+            (r#"{"dynamics": {"decay": -0.1}}"#, "dynamics.decay"),
+            (r#"{"dynamics": {"decay": 1.5}}"#, "dynamics.decay"),
+            (r#"{"dynamics": {"max_repulsion": -0.1}}"#, "dynamics.max_repulsion"),
+            (r#"{"dynamics": {"max_repulsion": 2}}"#, "dynamics.max_repulsion"),
+            (r#"{"dynamics": {"post_drift": -0.1}}"#, "dynamics.post_drift"),
+            (r#"{"dynamics": {"post_drift": 1.01}}"#, "dynamics.post_drift"),
+            (r#"{"clusters": {"k": 0}}"#, "clusters.k"),
+            (r#"{"clusters": {"batch_size": 0}}"#, "clusters.batch_size"),
+        ];
+        for (json, field) in cases {
+            assert_eq!(invalid_field(json), field, "{json}");
+        }
+        // The ends of the unit interval are allowed.
+        assert!(build(r#"{"dynamics": {"decay": 0, "max_repulsion": 1, "post_drift": 1}}"#).is_ok());
+    }
+
+    #[test]
+    fn non_finite_values_from_code_are_rejected() {
+        let builder = MotionSpaceBuilder::new().dim(8);
+        let gamma = builder.clone().kernel(Kernel::RBF { gamma: f32::NAN }).build();
+        assert!(matches!(gamma, Err(ConfigError::Invalid { field: "kernel.gamma", .. })));
+        let dynamics = Dynamics { decay: f32::NAN, ..Dynamics::default() };
+        let decay = builder.clone().dynamics(dynamics).build();
+        assert!(matches!(decay, Err(ConfigError::Invalid { field: "dynamics.decay", .. })));
+        let embedder = Embedder { dim: 8, token_weight: f32::INFINITY, ..Embedder::default() };
+        let weight = builder.embedder(embedder).build();
+        assert!(matches!(weight, Err(ConfigError::Invalid { field: "embedder.token_weight", .. })));
+    }
+
+    #[test]
+    fn malformed_json_is_a_parse_error() {
+        assert!(matches!(MotionConfig::from_json_str("{"), Err(ConfigError::Parse(_))));
+        assert!(matches!(MotionConfig::from_json_str(r#"{"dim": "big"}"#), Err(ConfigError::Parse(_))));
+        let err = MotionConfig::from_json_file("/nonexistent/motion.json").unwrap_err();
+        assert!(matches!(err, ConfigError::Io { path, .. } if path == "/nonexistent/motion.json"));
+    }
+}
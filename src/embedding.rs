@@ -1,22 +1,195 @@
+//! Hashed bag-of-words and character 3-gram text embedding.
+
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
-use thiserror::Error;
 
-use crate::motion_core::{MotionEntry, MotionPost, MotionUser};
 use crate::math::VecN;
 
+/// Dimension of the default text embedding.
 pub const EMBEDDING_DIM: usize = 128;
 
-pub fn embed_post(text: &str) -> VecN {
-    let mut data = vec![0.0_f32; EMBEDDING_DIM];
-    
-    add_text_features(&mut data, text);
-    let mut v = VecN::new(data);
+/// Parameters of the hashed text embedding.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Embedder {
+    /// Number of hash buckets, i.e. the embedding dimension.
+    pub dim: usize,
+    /// Bucket increment per whitespace token.
+    pub token_weight: f32,
+    /// Bucket increment per character 3-gram.
+    pub trigram_weight: f32,
+}
+
+impl Default for Embedder {
+    fn default() -> Self {
+        Self {
+            dim: EMBEDDING_DIM,
+            token_weight: 1.0,
+            trigram_weight: 0.3,
+        }
+    }
+}
+
+impl Embedder {
+    /// Embeds `text` as a unit vector; text without any tokens embeds as zero.
+    pub fn embed(&self, text: &str) -> VecN {
+        let mut data = vec![0.0_f32; self.dim];
+
+        add_text_features(&mut data, text, self.token_weight, self.trigram_weight);
+        let mut v = VecN::new(data);
+
+        if v.norm() > 0.0 {
+            let _ = v.normalize();
+        }
+
+        v
+    }
 
-    if v.norm() > 0.0 {
-        let _ = v.normalize();
+    /// The bucket a whitespace token of `text` adds to.
+    pub fn token_bucket(&self, token: &str) -> usize {
+        token_bucket(&token.to_lowercase(), self.dim)
     }
 
-    v
+    /// Embeds `texts` on up to `workers` threads, preserving order.
+    pub fn embed_batch(&self, texts: &[&str], workers: usize) -> Vec<VecN> {
+        if texts.is_empty() {
+            return Vec::new();
+        }
+        let chunk = texts.len().div_ceil(workers.max(1));
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = texts
+                .chunks(chunk)
+                .map(|chunk| scope.spawn(move || chunk.iter().map(|t| self.embed(t)).collect::<Vec<_>>()))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("embedding worker panicked"))
+                .collect()
+        })
+    }
+}
+
+/// Bucket -> the tokens that have hashed into it, with how often each was
+/// seen. The embedding itself cannot be inverted; this remembers enough of
+/// the corpus to name the tokens most likely behind a bucket's weight.
+///
+/// Counts are kept per post so a deleted or edited post's tokens can be
+/// taken back out.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenIndex {
+    buckets: Vec<HashMap<String, u32>>,
+    /// Post id -> the tokens it was counted with.
+    #[serde(default)]
+    posts: HashMap<String, Vec<String>>,
+}
+
+impl TokenIndex {
+    /// An empty index over `dim` buckets.
+    pub fn new(dim: usize) -> Self {
+        Self { buckets: vec![HashMap::new(); dim], posts: HashMap::new() }
+    }
+
+    /// Number of buckets.
+    pub fn dim(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Counts every token of `post_id`'s text, tokenized as the embedder
+    /// does. Replaces whatever was counted for the post before.
+    pub fn observe(&mut self, post_id: &str, text: &str) {
+        self.forget(post_id);
+        if self.buckets.is_empty() {
+            return;
+        }
+        let tokens = text.to_lowercase().split_whitespace().map(str::to_string).collect();
+        self.insert(post_id.to_string(), tokens);
+    }
+
+    /// Takes the tokens of `post_id` back out of the counts.
+    pub fn forget(&mut self, post_id: &str) {
+        self.remove(post_id);
+    }
+
+    /// Moves the posts for which `take` returns true, and their counts, into
+    /// a new index.
+    pub fn take_posts(&mut self, mut take: impl FnMut(&str) -> bool) -> TokenIndex {
+        let mut taken = TokenIndex::new(self.dim());
+        let ids: Vec<String> = self.posts.keys().filter(|id| take(id)).cloned().collect();
+        for id in ids {
+            if let Some(tokens) = self.remove(&id) {
+                taken.insert(id, tokens);
+            }
+        }
+        taken
+    }
+
+    /// Tokens seen in `bucket` with their counts.
+    pub fn tokens(&self, bucket: usize) -> impl Iterator<Item = (&str, u32)> {
+        self.buckets
+            .get(bucket)
+            .into_iter()
+            .flat_map(|tokens| tokens.iter().map(|(t, &c)| (t.as_str(), c)))
+    }
+
+    /// The `n` tokens that best explain `weights`, a vector in embedding
+    /// space such as a topic centroid. Each bucket's weight is shared among
+    /// its tokens in proportion to how often they were seen.
+    pub fn top_tokens(&self, weights: &[f32], n: usize) -> Vec<(String, f32)> {
+        let mut scored = Vec::new();
+        for (tokens, &weight) in self.buckets.iter().zip(weights) {
+            if weight <= 0.0 {
+                continue;
+            }
+            let total: u32 = tokens.values().sum();
+            for (token, &count) in tokens {
+                scored.push((token.clone(), weight * count as f32 / total as f32));
+            }
+        }
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored.truncate(n);
+        scored
+    }
+
+    /// Adds the counts of `other`, built over a different set of posts.
+    pub fn merge(&mut self, other: TokenIndex) {
+        for (ours, theirs) in self.buckets.iter_mut().zip(other.buckets) {
+            for (token, count) in theirs {
+                let c = ours.entry(token).or_default();
+                *c = c.saturating_add(count);
+            }
+        }
+        self.posts.extend(other.posts);
+    }
+
+    fn insert(&mut self, post_id: String, tokens: Vec<String>) {
+        let dim = self.buckets.len();
+        for token in &tokens {
+            let count = self.buckets[token_bucket(token, dim)].entry(token.clone()).or_default();
+            *count = count.saturating_add(1);
+        }
+        self.posts.insert(post_id, tokens);
+    }
+
+    fn remove(&mut self, post_id: &str) -> Option<Vec<String>> {
+        let tokens = self.posts.remove(post_id)?;
+        let dim = self.buckets.len();
+        for token in &tokens {
+            let bucket = &mut self.buckets[token_bucket(token, dim)];
+            if let Some(count) = bucket.get_mut(token) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    bucket.remove(token);
+                }
+            }
+        }
+        Some(tokens)
+    }
+}
+
+/// Embeds with the default [`Embedder`].
+pub fn embed_post(text: &str) -> VecN {
+    Embedder::default().embed(text)
 }
 
 fn hash_bytes(bytes: &[u8]) -> u64 {
@@ -29,11 +202,15 @@ fn hash_bytes(bytes: &[u8]) -> u64 {
     hash
 }
 
-fn hash_str(s: &str) -> u64 {
+pub(crate) fn hash_str(s: &str) -> u64 {
     hash_bytes(s.as_bytes())
 }
 
-fn add_text_features(bucket: &mut [f32], text: &str) {
+fn token_bucket(token: &str, dim: usize) -> usize {
+    (hash_str(token) % dim as u64) as usize
+}
+
+fn add_text_features(bucket: &mut [f32], text: &str, token_weight: f32, trigram_weight: f32) {
     if bucket.is_empty() {
         return;
     }
@@ -42,9 +219,7 @@ fn add_text_features(bucket: &mut [f32], text: &str) {
 
     // 1) token-level bag of words
     for token in lower.split_whitespace() {
-        let h = hash_str(token);
-        let idx = (h % dim) as usize;
-        bucket[idx] += 1.0;
+        bucket[token_bucket(token, bucket.len())] += token_weight;
     }
 
     // 2) character 3-grams
@@ -64,7 +239,7 @@ fn add_text_features(bucket: &mut [f32], text: &str) {
 
         let h = hash_bytes(&buf[..len]);
         let idx = (h % dim) as usize;
-        bucket[idx] += 0.3;
+        bucket[idx] += trigram_weight;
     }
 }
 
@@ -0,0 +1,623 @@
+//! Feed building: rank candidate posts for a user, then re-rank them for
+//! diversity.
+//!
+//! Candidates are the posts nearest to the user's coord under the space's
+//! kernel. Their relevance is that similarity, optionally discounted by age.
+//! The feed is then picked greedily by maximal marginal relevance (MMR):
+//!
+//! ```text
+//! mmr(c) = lambda * relevance(c) - (1 - lambda) * max_{s in feed} K(c, s)
+//! ```
+//!
+//! so `lambda = 1` is plain nearest-neighbour ranking and smaller values
+//! trade relevance for posts unlike the ones already picked.
+//!
+//! An [`Exploration`] strategy can loosen that greedy pick so users are not
+//! only ever shown what their current coord already agrees with. Randomized
+//! strategies draw from a seeded RNG, so a feed is reproducible given its seed.
+
+use std::str::FromStr;
+
+use chrono::Utc;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Beta, Distribution};
+use serde::{Deserialize, Serialize};
+
+use crate::motion_core::{CoreError, MotionEntry, MotionPost, MotionSpace};
+
+/// How slots are filled beyond picking the best score.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum Exploration {
+    /// Always take the best MMR score.
+    #[default]
+    Greedy,
+    /// With probability `epsilon`, fill a slot with a uniformly random
+    /// eligible post instead, including ones outside the candidate set.
+    EpsilonGreedy {
+        /// Probability of exploring a slot, in [0, 1].
+        epsilon: f32,
+    },
+    /// Sample each slot with probability proportional to
+    /// `exp(mmr / temperature)`.
+    Softmax {
+        /// Higher temperatures flatten the distribution. At zero or below
+        /// every slot takes the best score, the limit as the temperature
+        /// falls to zero.
+        temperature: f32,
+    },
+    /// Scale each post's relevance by a draw from
+    /// `Beta(1 + positive, 1 + negative)` of its engagement, so posts with
+    /// little feedback get a chance to prove themselves.
+    Thompson,
+}
+
+impl FromStr for Exploration {
+    type Err = String;
+
+    /// `greedy`, `thompson`, `epsilon:<p>` or `softmax:<temperature>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let number = |what: &str| -> Result<f32, String> {
+            let arg = arg.ok_or_else(|| format!("{name} needs a {what}, e.g. {name}:0.1"))?;
+            arg.parse().map_err(|_| format!("invalid {what} {arg:?}"))
+        };
+        match name {
+            "greedy" => Ok(Exploration::Greedy),
+            "thompson" => Ok(Exploration::Thompson),
+            "epsilon" => match number("probability")? {
+                epsilon if (0.0..=1.0).contains(&epsilon) => Ok(Exploration::EpsilonGreedy { epsilon }),
+                epsilon => Err(format!("epsilon must be in [0, 1], not {epsilon}")),
+            },
+            "softmax" => match number("temperature")? {
+                temperature if temperature.is_finite() && temperature > 0.0 => {
+                    Ok(Exploration::Softmax { temperature })
+                }
+                temperature => Err(format!("temperature must be positive, not {temperature}")),
+            },
+            _ => Err(format!(
+                "unknown exploration {s:?} (expected greedy, thompson, epsilon:<p> or softmax:<t>)"
+            )),
+        }
+    }
+}
+
+/// Parameters of a feed; see [`FeedBuilder`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FeedConfig {
+    /// Number of posts in the feed.
+    pub size: usize,
+    /// Nearest posts considered before re-ranking.
+    pub candidates: usize,
+    /// MMR trade-off in [0, 1] between relevance and diversity.
+    pub lambda: f32,
+    /// At most this many posts per author.
+    pub max_per_author: Option<usize>,
+    /// Posts older than this (in millis) are left out.
+    pub max_age_ms: Option<i64>,
+    /// Relevance halves every this many millis of post age.
+    pub half_life_ms: Option<i64>,
+    /// Whether the user's own posts are eligible.
+    pub include_own: bool,
+    /// How slots are filled beyond the best MMR score.
+    pub exploration: Exploration,
+    /// Seed for randomized exploration; drawn from the OS when unset.
+    pub seed: Option<u64>,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            size: 10,
+            candidates: 100,
+            lambda: 0.7,
+            max_per_author: Some(2),
+            max_age_ms: None,
+            half_life_ms: None,
+            include_own: false,
+            exploration: Exploration::Greedy,
+            seed: None,
+        }
+    }
+}
+
+/// One post of a feed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FeedItem {
+    /// The post.
+    pub post_id: String,
+    /// Its author.
+    pub author_id: String,
+    /// Unix millis the post was created at.
+    pub created_at: i64,
+    /// Kernel similarity between the user and the post.
+    pub similarity: f32,
+    /// Similarity after the freshness discount and, with Thompson sampling,
+    /// the engagement draw.
+    pub relevance: f32,
+    /// MMR score the post was picked with.
+    pub score: f32,
+    /// Picked by exploration rather than by its score.
+    pub explored: bool,
+}
+
+/// Builds a user's feed: the nearest posts, re-ranked by MMR.
+pub struct FeedBuilder<'a> {
+    space: &'a MotionSpace,
+    config: FeedConfig,
+    now_ms: Option<i64>,
+}
+
+impl MotionSpace {
+    /// Starts building a feed over this space.
+    pub fn feed(&self) -> FeedBuilder<'_> {
+        FeedBuilder::new(self)
+    }
+}
+
+impl<'a> FeedBuilder<'a> {
+    /// A builder with the default [`FeedConfig`].
+    pub fn new(space: &'a MotionSpace) -> Self {
+        Self {
+            space,
+            config: FeedConfig::default(),
+            now_ms: None,
+        }
+    }
+
+    /// Replaces every setting with `config`.
+    pub fn config(mut self, config: FeedConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets [`FeedConfig::size`].
+    pub fn size(mut self, size: usize) -> Self {
+        self.config.size = size;
+        self
+    }
+
+    /// Sets [`FeedConfig::candidates`].
+    pub fn candidates(mut self, candidates: usize) -> Self {
+        self.config.candidates = candidates;
+        self
+    }
+
+    /// Sets [`FeedConfig::lambda`], clamped to [0, 1].
+    pub fn lambda(mut self, lambda: f32) -> Self {
+        self.config.lambda = lambda.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets [`FeedConfig::max_per_author`].
+    pub fn max_per_author(mut self, max: Option<usize>) -> Self {
+        self.config.max_per_author = max;
+        self
+    }
+
+    /// Sets [`FeedConfig::max_age_ms`].
+    pub fn max_age_ms(mut self, max_age_ms: Option<i64>) -> Self {
+        self.config.max_age_ms = max_age_ms;
+        self
+    }
+
+    /// Sets [`FeedConfig::half_life_ms`].
+    pub fn half_life_ms(mut self, half_life_ms: Option<i64>) -> Self {
+        self.config.half_life_ms = half_life_ms;
+        self
+    }
+
+    /// Sets [`FeedConfig::include_own`].
+    pub fn include_own(mut self, include_own: bool) -> Self {
+        self.config.include_own = include_own;
+        self
+    }
+
+    /// Sets [`FeedConfig::exploration`].
+    pub fn exploration(mut self, exploration: Exploration) -> Self {
+        self.config.exploration = exploration;
+        self
+    }
+
+    /// Sets [`FeedConfig::seed`].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    /// Reference time for post ages; defaults to the current time.
+    pub fn now(mut self, now_ms: i64) -> Self {
+        self.now_ms = Some(now_ms);
+        self
+    }
+
+    /// Builds the feed for `user_id` from their current coord.
+    pub fn build(&self, user_id: &str) -> Result<Vec<FeedItem>, CoreError> {
+        let user = self
+            .space
+            .user(user_id)
+            .ok_or_else(|| CoreError::UserNotFound { user_id: user_id.to_string() })?;
+        let coord = user.coord.as_ref().ok_or_else(|| CoreError::CoordNotLoaded {
+            user_id: user_id.to_string(),
+        })?;
+        let exclude = (!self.config.include_own).then_some(user_id);
+        self.build_for_coord(&coord.data, exclude)
+    }
+
+    /// Builds a feed around an arbitrary coord, skipping posts by
+    /// `exclude_author`.
+    pub fn build_for_coord(&self, coord: &[f32], exclude_author: Option<&str>) -> Result<Vec<FeedItem>, CoreError> {
+        let mut rng = match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        let mut candidates = self.eligible(coord, exclude_author, &mut rng)?;
+        let rest = candidates.split_off(candidates.len().min(self.config.candidates.max(self.config.size)));
+        self.rerank(candidates, rest, &mut rng)
+    }
+
+    /// Every eligible post with its similarity and relevance, best first.
+    fn eligible(
+        &self,
+        coord: &[f32],
+        exclude_author: Option<&str>,
+        rng: &mut StdRng,
+    ) -> Result<Vec<Candidate<'a>>, CoreError> {
+        let space = self.space;
+        let now = self.now_ms.unwrap_or_else(|| Utc::now().timestamp_millis());
+        let mut candidates = Vec::new();
+        for entry in &space.entries {
+            let MotionEntry::Post(post) = entry else { continue };
+            if exclude_author == Some(post.author_id.as_str()) {
+                continue;
+            }
+            // Posts from the future count as brand new.
+            let age = (now - post.created_at).max(0);
+            if self.config.max_age_ms.is_some_and(|max| age > max) {
+                continue;
+            }
+            let similarity = space.kernel.apply(coord, &post.coord.data)?;
+            let mut relevance = match self.config.half_life_ms {
+                Some(half_life) if half_life > 0 => {
+                    similarity * 0.5f32.powf(age as f32 / half_life as f32)
+                }
+                _ => similarity,
+            };
+            if self.config.exploration == Exploration::Thompson {
+                let engagement = post.engagement;
+                let beta = Beta::new(1.0 + engagement.positive as f32, 1.0 + engagement.negative as f32)
+                    .expect("beta parameters are at least one");
+                relevance *= beta.sample(rng);
+            }
+            candidates.push(Candidate { post, similarity, relevance, redundancy: 0.0, rank: 0 });
+        }
+        candidates.sort_by(|a, b| b.relevance.total_cmp(&a.relevance));
+        for (rank, candidate) in candidates.iter_mut().enumerate() {
+            candidate.rank = rank;
+        }
+        Ok(candidates)
+    }
+
+    /// Picks the feed from `candidates`; epsilon-greedy exploration may also
+    /// pick from `rest`.
+    fn rerank(
+        &self,
+        mut candidates: Vec<Candidate<'a>>,
+        mut rest: Vec<Candidate<'a>>,
+        rng: &mut StdRng,
+    ) -> Result<Vec<FeedItem>, CoreError> {
+        let lambda = self.config.lambda;
+        let mut feed: Vec<FeedItem> = Vec::with_capacity(self.config.size);
+        if !matches!(self.config.exploration, Exploration::EpsilonGreedy { epsilon } if epsilon > 0.0) {
+            // Nothing else picks from `rest`, so its redundancy need not be kept.
+            rest.clear();
+        }
+
+        while feed.len() < self.config.size {
+            let allowed = |c: &Candidate| match self.config.max_per_author {
+                Some(max) => feed.iter().filter(|item| item.author_id == c.post.author_id).count() < max,
+                None => true,
+            };
+            let scored: Vec<(usize, f32)> = candidates
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| allowed(c))
+                .map(|(i, c)| (i, lambda * c.relevance - (1.0 - lambda) * c.redundancy))
+                .collect();
+
+            // `swap_remove` reorders the candidates, so equal scores are
+            // broken by relevance rank rather than position.
+            let best = scored
+                .iter()
+                .max_by(|a, b| a.1.total_cmp(&b.1).then(candidates[b.0].rank.cmp(&candidates[a.0].rank)))
+                .copied();
+            let is_best = |i: usize| best.is_some_and(|(b, _)| b == i);
+
+            let (pick, score, explored) = match self.config.exploration {
+                Exploration::EpsilonGreedy { epsilon } if rng.random::<f32>() < epsilon => {
+                    let pool: Vec<(bool, usize)> = candidates
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| allowed(c))
+                        .map(|(i, _)| (false, i))
+                        .chain(rest.iter().enumerate().filter(|(_, c)| allowed(c)).map(|(i, _)| (true, i)))
+                        .collect();
+                    if pool.is_empty() {
+                        break;
+                    }
+                    let (from_rest, i) = pool[rng.random_range(0..pool.len())];
+                    let explored = from_rest || !is_best(i);
+                    let pick = if from_rest { rest.swap_remove(i) } else { candidates.swap_remove(i) };
+                    let score = lambda * pick.relevance - (1.0 - lambda) * pick.redundancy;
+                    (pick, score, explored)
+                }
+                Exploration::Softmax { temperature } if temperature > 0.0 && !scored.is_empty() => {
+                    let max = scored.iter().map(|&(_, s)| s).fold(f32::NEG_INFINITY, f32::max);
+                    let weights: Vec<f32> = scored.iter().map(|&(_, s)| ((s - max) / temperature).exp()).collect();
+                    let mut draw = rng.random::<f32>() * weights.iter().sum::<f32>();
+                    let mut chosen = scored.len() - 1;
+                    for (n, w) in weights.iter().enumerate() {
+                        if draw < *w {
+                            chosen = n;
+                            break;
+                        }
+                        draw -= w;
+                    }
+                    let (i, score) = scored[chosen];
+                    (candidates.swap_remove(i), score, !is_best(i))
+                }
+                _ => {
+                    let Some((i, score)) = best else { break };
+                    (candidates.swap_remove(i), score, false)
+                }
+            };
+
+            for other in candidates.iter_mut().chain(&mut rest) {
+                let sim = self.space.kernel.apply(&pick.post.coord.data, &other.post.coord.data)?;
+                other.redundancy = other.redundancy.max(sim);
+            }
+            feed.push(FeedItem {
+                post_id: pick.post.id.clone(),
+                author_id: pick.post.author_id.clone(),
+                created_at: pick.post.created_at,
+                similarity: pick.similarity,
+                relevance: pick.relevance,
+                score,
+                explored,
+            });
+        }
+        Ok(feed)
+    }
+}
+
+struct Candidate<'a> {
+    post: &'a MotionPost,
+    similarity: f32,
+    relevance: f32,
+    /// Highest kernel similarity to any post already in the feed.
+    redundancy: f32,
+    /// Position in the eligible posts by relevance, best first.
+    rank: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::VecN;
+    use crate::motion_core::{Engagement, MotionUser};
+
+    const NOW: i64 = 1_000_000_000;
+    const HOUR_MS: i64 = 3_600_000;
+
+    /// A 2-dimensional space with `alice` at `[1, 0]`.
+    fn space() -> MotionSpace {
+        let mut space = MotionSpace::new(2);
+        let mut alice = MotionUser::new("alice", 2);
+        alice.coord = Some(VecN::new(vec![1.0, 0.0]));
+        space.enter(MotionEntry::User(alice));
+        space
+    }
+
+    fn add_post(space: &mut MotionSpace, id: &str, author_id: &str, coord: [f32; 2], created_at: i64) {
+        let post = MotionPost::new(id.to_string(), author_id.to_string(), VecN::new(coord.to_vec()))
+            .with_created_at(created_at);
+        space.enter(MotionEntry::Post(post));
+    }
+
+    fn ids(feed: &[FeedItem]) -> Vec<&str> {
+        feed.iter().map(|item| item.post_id.as_str()).collect()
+    }
+
+    #[test]
+    fn equal_scores_keep_relevance_order() {
+        let mut space = space();
+        add_post(&mut space, "best", "bob", [1.0, 0.0], NOW);
+        for id in ["tie-1", "tie-2", "tie-3"] {
+            add_post(&mut space, id, "bob", [0.0, 1.0], NOW);
+        }
+        let feed = space
+            .feed()
+            .size(4)
+            .lambda(1.0)
+            .max_per_author(None)
+            .now(NOW)
+            .build("alice")
+            .unwrap();
+        assert_eq!(ids(&feed), ["best", "tie-1", "tie-2", "tie-3"]);
+    }
+
+    #[test]
+    fn authors_are_capped() {
+        let mut space = space();
+        add_post(&mut space, "b1", "bob", [1.0, 0.0], NOW);
+        add_post(&mut space, "b2", "bob", [0.9, 0.1], NOW);
+        add_post(&mut space, "b3", "bob", [0.8, 0.2], NOW);
+        add_post(&mut space, "c1", "carol", [0.0, 1.0], NOW);
+
+        let feed = |max| space.feed().size(4).lambda(1.0).max_per_author(max).now(NOW).build("alice").unwrap();
+        assert_eq!(ids(&feed(Some(1))), ["b1", "c1"]);
+        assert_eq!(ids(&feed(Some(2))), ["b1", "b2", "c1"]);
+        assert_eq!(ids(&feed(None)), ["b1", "b2", "b3", "c1"]);
+    }
+
+    #[test]
+    fn posts_past_the_max_age_are_left_out() {
+        let mut space = space();
+        add_post(&mut space, "old", "bob", [1.0, 0.0], NOW - 48 * HOUR_MS);
+        add_post(&mut space, "recent", "bob", [0.0, 1.0], NOW - HOUR_MS);
+        add_post(&mut space, "future", "bob", [0.0, 1.0], NOW + HOUR_MS);
+
+        let feed = |max_age| {
+            let feed = space.feed().lambda(1.0).max_per_author(None).max_age_ms(max_age);
+            feed.now(NOW).build("alice").unwrap()
+        };
+        assert_eq!(ids(&feed(None)), ["old", "recent", "future"]);
+        assert_eq!(ids(&feed(Some(24 * HOUR_MS))), ["recent", "future"]);
+    }
+
+    #[test]
+    fn own_posts_are_excluded_unless_asked_for() {
+        let mut space = space();
+        add_post(&mut space, "mine", "alice", [1.0, 0.0], NOW);
+        add_post(&mut space, "theirs", "bob", [0.0, 1.0], NOW);
+
+        let feed = |own| space.feed().include_own(own).now(NOW).build("alice").unwrap();
+        assert_eq!(ids(&feed(false)), ["theirs"]);
+        assert_eq!(ids(&feed(true)), ["mine", "theirs"]);
+    }
+
+    /// `alice` at `[1, 0]` and six posts by different authors fanned out
+    /// from her coord to the orthogonal axis, nearest first.
+    fn fan() -> MotionSpace {
+        let mut space = space();
+        for i in 0..6 {
+            let angle = i as f32 * 0.3;
+            add_post(&mut space, &format!("p{i}"), &format!("author-{i}"), [angle.cos(), angle.sin()], NOW);
+        }
+        space
+    }
+
+    fn build(space: &MotionSpace, exploration: Exploration, seed: u64) -> Vec<FeedItem> {
+        space.feed().size(4).exploration(exploration).seed(seed).now(NOW).build("alice").unwrap()
+    }
+
+    #[test]
+    fn the_same_seed_builds_the_same_feed() {
+        let space = fan();
+        let summary = |feed: &[FeedItem]| {
+            feed.iter().map(|item| format!("{} {} {}", item.post_id, item.score, item.explored)).collect::<Vec<_>>()
+        };
+        for exploration in [
+            Exploration::EpsilonGreedy { epsilon: 0.5 },
+            Exploration::Softmax { temperature: 0.5 },
+            Exploration::Thompson,
+        ] {
+            let feeds: Vec<_> = (0..20).map(|seed| summary(&build(&space, exploration, seed))).collect();
+            for (seed, feed) in feeds.iter().enumerate() {
+                assert_eq!(&summary(&build(&space, exploration, seed as u64)), feed, "{exploration:?}");
+            }
+            assert!(feeds.iter().any(|feed| feed != &feeds[0]), "{exploration:?} ignores its seed");
+        }
+    }
+
+    #[test]
+    fn epsilon_greedy_explores_beyond_the_candidates() {
+        let space = fan();
+        // Candidates are at least as many as the feed is long, so this
+        // leaves p0..p2 as candidates and p3..p5 to exploration only.
+        let feed = |epsilon, lambda, seed| {
+            let exploration = Exploration::EpsilonGreedy { epsilon };
+            let feed = space.feed().size(3).candidates(1).lambda(lambda).exploration(exploration);
+            feed.seed(seed).now(NOW).build("alice").unwrap()
+        };
+        assert_eq!(ids(&feed(0.0, 1.0, 0)), ["p0", "p1", "p2"]);
+
+        let mut picked = std::collections::HashSet::new();
+        for seed in 0..30 {
+            let feed = feed(1.0, 1.0, seed);
+            let mut remaining = vec!["p0", "p1", "p2"];
+            for item in &feed {
+                // By relevance alone the greedy pick is the nearest candidate left.
+                assert_eq!(item.explored, item.post_id != remaining[0], "seed {seed}");
+                remaining.retain(|id| *id != item.post_id);
+                picked.insert(item.post_id.clone());
+            }
+        }
+        assert_eq!(picked.len(), 6, "{picked:?}");
+
+        let post = |id: &str| &space.post(id).unwrap().coord.data;
+        for seed in 0..30 {
+            let feed = feed(1.0, 0.5, seed);
+            for (n, item) in feed.iter().enumerate() {
+                // Posts explored from outside the candidates are still
+                // scored against everything picked before them.
+                let redundancy = feed[..n]
+                    .iter()
+                    .map(|prev| space.kernel.apply(post(&item.post_id), post(&prev.post_id)).unwrap())
+                    .fold(0.0, f32::max);
+                let expected = 0.5 * item.relevance - 0.5 * redundancy;
+                assert!((item.score - expected).abs() < 1e-6, "seed {seed}: {} != {expected}", item.score);
+            }
+        }
+    }
+
+    #[test]
+    fn softmax_follows_the_temperature() {
+        let space = fan();
+        let greedy = build(&space, Exploration::Greedy, 0);
+        for seed in 0..20 {
+            let feed = build(&space, Exploration::Softmax { temperature: 1e-4 }, seed);
+            assert_eq!(ids(&feed), ids(&greedy));
+            assert!(feed.iter().all(|item| !item.explored));
+        }
+
+        let mut firsts = std::collections::HashSet::new();
+        for seed in 0..50 {
+            let feed = build(&space, Exploration::Softmax { temperature: 100.0 }, seed);
+            firsts.insert(feed[0].post_id.clone());
+            assert_eq!(feed[0].explored, feed[0].post_id != greedy[0].post_id, "seed {seed}");
+        }
+        assert!(firsts.len() >= 4, "{firsts:?}");
+    }
+
+    #[test]
+    fn thompson_sampling_favours_well_received_posts() {
+        let mut space = space();
+        add_post(&mut space, "panned", "bob", [1.0, 0.0], NOW);
+        add_post(&mut space, "loved", "carol", [0.95, 0.05], NOW);
+        for entry in &mut space.entries {
+            if let MotionEntry::Post(post) = entry {
+                post.engagement = match post.id.as_str() {
+                    "panned" => Engagement { positive: 0, negative: 200 },
+                    _ => Engagement { positive: 200, negative: 0 },
+                };
+            }
+        }
+        assert_eq!(ids(&build(&space, Exploration::Greedy, 0)), ["panned", "loved"]);
+        for seed in 0..20 {
+            let feed = build(&space, Exploration::Thompson, seed);
+            assert_eq!(ids(&feed), ["loved", "panned"], "seed {seed}");
+            assert!(feed.iter().all(|item| item.relevance < item.similarity && !item.explored));
+        }
+    }
+
+    #[test]
+    fn explorations_parse_and_reject_out_of_range_parameters() {
+        assert_eq!("greedy".parse::<Exploration>().unwrap(), Exploration::Greedy);
+        assert_eq!("thompson".parse::<Exploration>().unwrap(), Exploration::Thompson);
+        assert_eq!("epsilon:0".parse::<Exploration>().unwrap(), Exploration::EpsilonGreedy { epsilon: 0.0 });
+        assert_eq!("epsilon:1".parse::<Exploration>().unwrap(), Exploration::EpsilonGreedy { epsilon: 1.0 });
+        assert_eq!("softmax:0.2".parse::<Exploration>().unwrap(), Exploration::Softmax { temperature: 0.2 });
+        for bad in [
+            "", "random", "epsilon", "epsilon:x", "epsilon:-0.1", "epsilon:1.5", "epsilon:nan", "softmax",
+            "softmax:0", "softmax:-1", "softmax:inf", "softmax:nan",
+        ] {
+            assert!(bad.parse::<Exploration>().is_err(), "{bad:?} parsed");
+        }
+    }
+}
@@ -0,0 +1,570 @@
+//! Directed, weighted interaction graph kept alongside the geometry.
+//!
+//! Every applied interaction adds to one edge: user -> user for the user
+//! kinds, user -> post for the post kinds (the user is the one who engaged).
+//! Repeated interactions aggregate into the same edge, which keeps a count,
+//! the sum of the signed weights and the first and last time it was used.
+//!
+//! Snapshots store the graph as a flat edge list; the reverse indexes are
+//! rebuilt on load.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::motion_core::InteractionResult;
+use crate::motion_input::InteractionTarget;
+
+/// Every interaction from one user to one user or post, aggregated.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Edge {
+    /// Number of interactions.
+    pub count: u32,
+    /// How many of `count` were repelling interactions. Snapshots written
+    /// before this was tracked load it as zero.
+    #[serde(default)]
+    pub repelled: u32,
+    /// Sum of the signed interaction weights.
+    pub weight: f32,
+    /// Unix millis of the first interaction.
+    pub first_at: i64,
+    /// Unix millis of the latest interaction.
+    pub last_at: i64,
+}
+
+impl Edge {
+    fn new(weight: f32, repelled: bool, at: i64) -> Self {
+        Self { count: 1, repelled: u32::from(repelled), weight, first_at: at, last_at: at }
+    }
+
+    fn add(&mut self, weight: f32, repelled: bool, at: i64) {
+        self.count = self.count.saturating_add(1);
+        self.repelled = self.repelled.saturating_add(u32::from(repelled));
+        self.weight += weight;
+        self.first_at = self.first_at.min(at);
+        self.last_at = self.last_at.max(at);
+    }
+
+    fn merge(&mut self, other: &Edge) {
+        self.count = self.count.saturating_add(other.count);
+        self.repelled = self.repelled.saturating_add(other.repelled);
+        self.weight += other.weight;
+        self.first_at = self.first_at.min(other.first_at);
+        self.last_at = self.last_at.max(other.last_at);
+    }
+}
+
+/// Which side of an edge a user is on.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Edges the user made.
+    Out,
+    /// Edges pointing at the user.
+    In,
+}
+
+/// One edge as stored in snapshots.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EdgeRecord {
+    /// Whether `dst` is a user or a post.
+    pub target: InteractionTarget,
+    /// The user who interacted.
+    pub src: String,
+    /// The user or post they interacted with.
+    pub dst: String,
+    /// The aggregated interactions.
+    #[serde(flatten)]
+    pub edge: Edge,
+}
+
+/// Size and degree figures of an [`InteractionGraph`].
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DegreeStats {
+    /// Users with at least one edge.
+    pub users: usize,
+    /// Posts with at least one engager.
+    pub posts: usize,
+    /// User -> user edges.
+    pub user_edges: usize,
+    /// User -> post edges.
+    pub post_edges: usize,
+    /// User -> user edges per user.
+    pub mean_user_out_degree: f32,
+    /// Most users one user has an edge to.
+    pub max_user_out_degree: usize,
+    /// Most users with an edge into one user.
+    pub max_user_in_degree: usize,
+    /// Most users engaged with one post.
+    pub max_post_engagers: usize,
+}
+
+/// A weakly connected component.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Component {
+    /// User ids, sorted.
+    pub users: Vec<String>,
+    /// Post ids, sorted.
+    pub posts: Vec<String>,
+}
+
+/// Who interacted with whom; see the module docs.
+#[derive(Debug, Clone, Default)]
+pub struct InteractionGraph {
+    /// user -> user -> edge
+    follows: HashMap<String, HashMap<String, Edge>>,
+    /// user -> users with an edge into them
+    followers: HashMap<String, HashSet<String>>,
+    /// user -> post -> edge
+    engaged: HashMap<String, HashMap<String, Edge>>,
+    /// post -> users with an edge into it
+    engagers: HashMap<String, HashSet<String>>,
+}
+
+impl InteractionGraph {
+    /// An empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an applied interaction at `at` (unix millis). `repelled` marks
+    /// a repelling interaction.
+    pub fn record(&mut self, result: &InteractionResult, repelled: bool, at: i64) {
+        let (src, dst) = match result.kind.target() {
+            InteractionTarget::User => (&result.src_id, &result.dst_id),
+            InteractionTarget::Post => (&result.dst_id, &result.src_id),
+        };
+        self.add_edge(result.kind.target(), src, dst, result.weight, repelled, at);
+    }
+
+    fn add_edge(&mut self, target: InteractionTarget, src: &str, dst: &str, weight: f32, repelled: bool, at: i64) {
+        let (out, incoming) = self.maps_mut(target);
+        out.entry(src.to_string())
+            .or_default()
+            .entry(dst.to_string())
+            .and_modify(|e| e.add(weight, repelled, at))
+            .or_insert_with(|| Edge::new(weight, repelled, at));
+        incoming.entry(dst.to_string()).or_default().insert(src.to_string());
+    }
+
+    fn insert_edge(&mut self, target: InteractionTarget, src: String, dst: String, edge: Edge) {
+        let (out, incoming) = self.maps_mut(target);
+        incoming.entry(dst.clone()).or_default().insert(src.clone());
+        out.entry(src)
+            .or_default()
+            .entry(dst)
+            .and_modify(|e| e.merge(&edge))
+            .or_insert(edge);
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn maps_mut(
+        &mut self,
+        target: InteractionTarget,
+    ) -> (&mut HashMap<String, HashMap<String, Edge>>, &mut HashMap<String, HashSet<String>>) {
+        match target {
+            InteractionTarget::User => (&mut self.follows, &mut self.followers),
+            InteractionTarget::Post => (&mut self.engaged, &mut self.engagers),
+        }
+    }
+
+    /// The edge from user `src` to user `dst`.
+    pub fn user_edge(&self, src: &str, dst: &str) -> Option<&Edge> {
+        self.follows.get(src)?.get(dst)
+    }
+
+    /// The edge from `user_id` to `post_id`.
+    pub fn post_edge(&self, user_id: &str, post_id: &str) -> Option<&Edge> {
+        self.engaged.get(user_id)?.get(post_id)
+    }
+
+    /// Users `user_id` has an edge to (`Out`) or from (`In`), with that edge.
+    pub fn user_neighbors(&self, user_id: &str, direction: Direction) -> Vec<(&str, &Edge)> {
+        match direction {
+            Direction::Out => self
+                .follows
+                .get(user_id)
+                .map(|edges| edges.iter().map(|(id, e)| (id.as_str(), e)).collect())
+                .unwrap_or_default(),
+            Direction::In => self
+                .followers
+                .get(user_id)
+                .map(|srcs| {
+                    srcs.iter()
+                        .filter_map(|src| Some((src.as_str(), self.user_edge(src, user_id)?)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Posts `user_id` has engaged with.
+    pub fn posts_engaged(&self, user_id: &str) -> Vec<(&str, &Edge)> {
+        self.engaged
+            .get(user_id)
+            .map(|edges| edges.iter().map(|(id, e)| (id.as_str(), e)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Users who engaged with `post_id`.
+    pub fn post_engagers(&self, post_id: &str) -> Vec<(&str, &Edge)> {
+        self.engagers
+            .get(post_id)
+            .map(|users| {
+                users
+                    .iter()
+                    .filter_map(|u| Some((u.as_str(), self.post_edge(u, post_id)?)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether user `src` has an edge to user `dst`.
+    pub fn has_user_edge(&self, src: &str, dst: &str) -> bool {
+        self.user_edge(src, dst).is_some()
+    }
+
+    /// Erases every edge into or out of `user_id`.
+    pub fn remove_user(&mut self, user_id: &str) {
+        if let Some(edges) = self.follows.remove(user_id) {
+            for dst in edges.keys() {
+                remove_from_index(&mut self.followers, dst, user_id);
+            }
+        }
+        if let Some(srcs) = self.followers.remove(user_id) {
+            for src in srcs {
+                remove_edge(&mut self.follows, &src, user_id);
+            }
+        }
+        if let Some(edges) = self.engaged.remove(user_id) {
+            for post in edges.keys() {
+                remove_from_index(&mut self.engagers, post, user_id);
+            }
+        }
+    }
+
+    /// Erases every edge into `post_id`.
+    pub fn remove_post(&mut self, post_id: &str) {
+        if let Some(users) = self.engagers.remove(post_id) {
+            for user in users {
+                remove_edge(&mut self.engaged, &user, post_id);
+            }
+        }
+    }
+
+    /// Adds every edge of `other` to this graph, merging edges both have.
+    pub fn merge(&mut self, other: InteractionGraph) {
+        for record in other.edges() {
+            self.insert_edge(record.target, record.src, record.dst, record.edge);
+        }
+    }
+
+    /// Splits the graph by the shard of each edge's source user.
+    pub fn partition(self, parts: usize, part_of: impl Fn(&str) -> usize) -> Vec<InteractionGraph> {
+        let mut graphs = vec![InteractionGraph::default(); parts.max(1)];
+        for record in self.edges() {
+            let part = part_of(&record.src);
+            graphs[part].insert_edge(record.target, record.src, record.dst, record.edge);
+        }
+        graphs
+    }
+
+    /// Every edge, in no particular order.
+    pub fn edges(&self) -> Vec<EdgeRecord> {
+        let flatten = |target, map: &HashMap<String, HashMap<String, Edge>>| {
+            map.iter()
+                .flat_map(move |(src, edges)| {
+                    edges.iter().map(move |(dst, edge)| EdgeRecord {
+                        target,
+                        src: src.clone(),
+                        dst: dst.clone(),
+                        edge: *edge,
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut records = flatten(InteractionTarget::User, &self.follows);
+        records.extend(flatten(InteractionTarget::Post, &self.engaged));
+        records
+    }
+
+    /// Counts and degree extremes over the whole graph.
+    pub fn degree_stats(&self) -> DegreeStats {
+        let users: HashSet<&str> = self
+            .follows
+            .keys()
+            .chain(self.followers.keys())
+            .chain(self.engaged.keys())
+            .map(String::as_str)
+            .collect();
+        let user_edges: usize = self.follows.values().map(HashMap::len).sum();
+        let mut stats = DegreeStats {
+            users: users.len(),
+            posts: self.engagers.len(),
+            user_edges,
+            post_edges: self.engaged.values().map(HashMap::len).sum(),
+            max_user_out_degree: self.follows.values().map(HashMap::len).max().unwrap_or(0),
+            max_user_in_degree: self.followers.values().map(HashSet::len).max().unwrap_or(0),
+            max_post_engagers: self.engagers.values().map(HashSet::len).max().unwrap_or(0),
+            ..DegreeStats::default()
+        };
+        if stats.users > 0 {
+            stats.mean_user_out_degree = user_edges as f32 / stats.users as f32;
+        }
+        stats
+    }
+
+    /// PageRank over the user -> user edges, each weighted by its summed
+    /// weight; edges with a non-positive total are ignored. Rank held by
+    /// users without usable out-edges is spread evenly. Scores sum to one.
+    pub fn pagerank(&self, damping: f32, iterations: usize) -> HashMap<String, f32> {
+        let mut ids: Vec<&str> = self
+            .follows
+            .keys()
+            .chain(self.followers.keys())
+            .map(String::as_str)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let n = ids.len();
+        if n == 0 {
+            return HashMap::new();
+        }
+        let index: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let out: Vec<Vec<(usize, f32)>> = ids
+            .iter()
+            .map(|id| {
+                self.follows
+                    .get(*id)
+                    .map(|edges| {
+                        edges
+                            .iter()
+                            .filter(|(_, e)| e.weight > 0.0)
+                            .map(|(dst, e)| (index[dst.as_str()], e.weight))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect();
+        let totals: Vec<f32> = out.iter().map(|edges| edges.iter().map(|(_, w)| w).sum()).collect();
+
+        let base = (1.0 - damping) / n as f32;
+        let mut rank = vec![1.0 / n as f32; n];
+        for _ in 0..iterations {
+            let dangling: f32 = (0..n).filter(|&i| out[i].is_empty()).map(|i| rank[i]).sum();
+            let mut next = vec![base + damping * dangling / n as f32; n];
+            for (i, edges) in out.iter().enumerate() {
+                for &(j, w) in edges {
+                    next[j] += damping * rank[i] * w / totals[i];
+                }
+            }
+            rank = next;
+        }
+        ids.into_iter().map(str::to_string).zip(rank).collect()
+    }
+
+    /// Weakly connected components over users and posts, largest first.
+    pub fn connected_components(&self) -> Vec<Component> {
+        let mut parent: HashMap<Node, Node> = HashMap::new();
+        for (src, edges) in &self.follows {
+            for dst in edges.keys() {
+                union(&mut parent, (InteractionTarget::User, src.as_str()), (InteractionTarget::User, dst.as_str()));
+            }
+        }
+        for (user, edges) in &self.engaged {
+            for post in edges.keys() {
+                union(&mut parent, (InteractionTarget::User, user.as_str()), (InteractionTarget::Post, post.as_str()));
+            }
+        }
+
+        let nodes: Vec<_> = parent.keys().copied().collect();
+        let mut groups: HashMap<Node, Component> = HashMap::new();
+        for node in nodes {
+            let root = find(&mut parent, node);
+            let component = groups.entry(root).or_default();
+            match node.0 {
+                InteractionTarget::User => component.users.push(node.1.to_string()),
+                InteractionTarget::Post => component.posts.push(node.1.to_string()),
+            }
+        }
+        let mut components: Vec<Component> = groups.into_values().collect();
+        for c in &mut components {
+            c.users.sort();
+            c.posts.sort();
+        }
+        components.sort_by(|a, b| {
+            (b.users.len() + b.posts.len())
+                .cmp(&(a.users.len() + a.posts.len()))
+                .then_with(|| a.users.cmp(&b.users))
+                .then_with(|| a.posts.cmp(&b.posts))
+        });
+        components
+    }
+}
+
+/// A union-find node. Tagged so a user and a post with the same id stay apart.
+type Node<'a> = (InteractionTarget, &'a str);
+
+fn find<'a>(parent: &mut HashMap<Node<'a>, Node<'a>>, node: Node<'a>) -> Node<'a> {
+    let mut root = node;
+    while let Some(&p) = parent.get(&root) {
+        if p == root {
+            break;
+        }
+        root = p;
+    }
+    let mut current = node;
+    while current != root {
+        let next = parent[&current];
+        parent.insert(current, root);
+        current = next;
+    }
+    root
+}
+
+fn union<'a>(parent: &mut HashMap<Node<'a>, Node<'a>>, a: Node<'a>, b: Node<'a>) {
+    parent.entry(a).or_insert(a);
+    parent.entry(b).or_insert(b);
+    let (ra, rb) = (find(parent, a), find(parent, b));
+    if ra != rb {
+        parent.insert(ra, rb);
+    }
+}
+
+fn remove_from_index(index: &mut HashMap<String, HashSet<String>>, key: &str, value: &str) {
+    if let Some(set) = index.get_mut(key) {
+        set.remove(value);
+        if set.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+fn remove_edge(map: &mut HashMap<String, HashMap<String, Edge>>, src: &str, dst: &str) {
+    if let Some(edges) = map.get_mut(src) {
+        edges.remove(dst);
+        if edges.is_empty() {
+            map.remove(src);
+        }
+    }
+}
+
+impl Serialize for InteractionGraph {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.edges().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for InteractionGraph {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut graph = InteractionGraph::default();
+        for record in Vec::<EdgeRecord>::deserialize(deserializer)? {
+            graph.insert_edge(record.target, record.src, record.dst, record.edge);
+        }
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motion_input::InteractionType;
+
+    fn interact(graph: &mut InteractionGraph, kind: InteractionType, src_id: &str, dst_id: &str, weight: f32) {
+        let result = InteractionResult {
+            kind,
+            src_id: src_id.to_string(),
+            dst_id: dst_id.to_string(),
+            weight,
+            similarity: 0.5,
+        };
+        graph.record(&result, weight < 0.0, 0);
+    }
+
+    fn follow(graph: &mut InteractionGraph, src: &str, dst: &str) {
+        interact(graph, InteractionType::Follow, src, dst, 1.0);
+    }
+
+    fn assert_ranks(ranks: &HashMap<String, f32>, expected: &[(&str, f32)]) {
+        assert_eq!(ranks.len(), expected.len(), "{ranks:?}");
+        for (id, rank) in expected {
+            assert!((ranks[*id] - rank).abs() < 1e-4, "{id}: {} != {rank}", ranks[*id]);
+        }
+        assert!((ranks.values().sum::<f32>() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn edges_aggregate_repeated_interactions() {
+        let mut graph = InteractionGraph::new();
+        interact(&mut graph, InteractionType::Like, "p1", "alice", 0.5);
+        interact(&mut graph, InteractionType::Dislike, "p1", "alice", -0.25);
+        let edge = graph.post_edge("alice", "p1").unwrap();
+        assert_eq!((edge.count, edge.repelled), (2, 1));
+        assert!((edge.weight - 0.25).abs() < 1e-6);
+        assert_eq!(graph.post_engagers("p1").len(), 1);
+    }
+
+    #[test]
+    fn pagerank_of_a_small_graph() {
+        // c links into a two-cycle and nothing links to c:
+        //   c = (1 - d) / 3,  b = (1 - d) / 3 + d * a,  a + b + c = 1.
+        let mut graph = InteractionGraph::new();
+        follow(&mut graph, "a", "b");
+        follow(&mut graph, "b", "a");
+        follow(&mut graph, "c", "a");
+        let ranks = graph.pagerank(0.85, 100);
+        assert_ranks(&ranks, &[("a", 0.9 / 1.85), ("b", 0.95 - 0.9 / 1.85), ("c", 0.05)]);
+    }
+
+    #[test]
+    fn pagerank_of_a_cycle_is_uniform() {
+        let mut graph = InteractionGraph::new();
+        follow(&mut graph, "a", "b");
+        follow(&mut graph, "b", "c");
+        follow(&mut graph, "c", "a");
+        assert_ranks(&graph.pagerank(0.85, 100), &[("a", 1.0 / 3.0), ("b", 1.0 / 3.0), ("c", 1.0 / 3.0)]);
+    }
+
+    #[test]
+    fn pagerank_spreads_dangling_rank_evenly() {
+        // b has no out-edges, so its rank is shared by both users:
+        //   a = (1 - d) / 2 + d * b / 2,  a + b = 1.
+        let mut graph = InteractionGraph::new();
+        follow(&mut graph, "a", "b");
+        assert_ranks(&graph.pagerank(0.85, 100), &[("a", 0.5 / 1.425), ("b", 1.0 - 0.5 / 1.425)]);
+
+        // An edge with a negative total counts as no edge at all.
+        interact(&mut graph, InteractionType::UserToUser, "b", "a", -0.5);
+        assert_ranks(&graph.pagerank(0.85, 100), &[("a", 0.5 / 1.425), ("b", 1.0 - 0.5 / 1.425)]);
+    }
+
+    #[test]
+    fn pagerank_of_an_empty_graph_is_empty() {
+        assert!(InteractionGraph::new().pagerank(0.85, 10).is_empty());
+    }
+
+    #[test]
+    fn components_split_when_users_are_deleted() {
+        let mut graph = InteractionGraph::new();
+        follow(&mut graph, "a", "b");
+        follow(&mut graph, "b", "c");
+        interact(&mut graph, InteractionType::Like, "p1", "c", 0.5);
+        interact(&mut graph, InteractionType::Like, "p1", "d", 0.5);
+        interact(&mut graph, InteractionType::Like, "p2", "e", 0.5);
+        let sizes = |graph: &InteractionGraph| -> Vec<(usize, usize)> {
+            graph.connected_components().iter().map(|c| (c.users.len(), c.posts.len())).collect()
+        };
+        assert_eq!(sizes(&graph), [(4, 1), (1, 1)]);
+
+        graph.remove_user("b");
+        let components = graph.connected_components();
+        assert_eq!(components[0].users, ["c", "d"]);
+        assert_eq!(components[0].posts, ["p1"]);
+        assert_eq!(sizes(&graph), [(2, 1), (1, 1)]);
+
+        graph.remove_user("e");
+        graph.remove_post("p1");
+        assert!(graph.connected_components().is_empty());
+        assert_eq!(graph.degree_stats().users, 0);
+    }
+}
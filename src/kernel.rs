@@ -1,11 +1,21 @@
+//! Similarity kernels and element-wise helpers over `f32` slices.
+
 use crate::math::MathError;
+use crate::simd;
 use serde::{Deserialize, Serialize};
 
+/// Similarity between two coords, in `[0, 1]` for the kernels here.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Kernel {
-    RBF { gamma: f32 },
+    /// Gaussian `exp(-gamma * |x - y|^2)`.
+    RBF {
+        /// Inverse length scale; larger values make similarity fall off faster.
+        gamma: f32,
+    },
 }
 
+/// Gaussian RBF similarity of `x` and `y`.
 pub fn rbf_kernel(x: &[f32], y: &[f32], gamma: f32) -> Result<f32, MathError> {
     if x.len() != y.len() {
         return Err(MathError::DimensionMismatch {
@@ -14,26 +24,19 @@ pub fn rbf_kernel(x: &[f32], y: &[f32], gamma: f32) -> Result<f32, MathError> {
         });
     }
 
-    let sq_dist: f32 = x
-        .iter()
-        .zip(y.iter())
-        .map(|(a, b)| {
-            let d = a - b;
-            d * d
-        })
-        .sum();
-
-    Ok((-gamma * sq_dist).exp())
+    Ok((-gamma * simd::squared_distance(x, y)).exp())
 }
 
-pub fn apply_kernel<F>(a: &[f32], mut f: F) -> Vec<f32>
+/// Maps `f` over `a`.
+pub fn apply_kernel<F>(a: &[f32], f: F) -> Vec<f32>
 where
     F: FnMut(f32) -> f32,
 {
-    a.iter().copied().map(|x| f(x)).collect()
+    a.iter().copied().map(f).collect()
 }
 
-pub fn apply_kernel2<F>(a: &[f32], b: &[f32], mut f: F) -> Result<Vec<f32>, MathError>
+/// Maps `f` over `a` and `b` pairwise. The slices must have the same length.
+pub fn apply_kernel2<F>(a: &[f32], b: &[f32], f: F) -> Result<Vec<f32>, MathError>
 where
     F: FnMut(f32, f32) -> f32,
 {
@@ -44,13 +47,10 @@ where
         });
     }
 
-    Ok(a.iter()
-        .copied()
-        .zip(b.iter().copied())
-        .map(|(x, y)| f(x, y))
-        .collect())
+    Ok(simd::zip_map(a, b, f))
 }
 
+/// Maps `f` over `a` with each element's index.
 pub fn apply_kernel_indexed<F>(a: &[f32], mut f: F) -> Vec<f32>
 where
     F: FnMut(usize, f32) -> f32,
@@ -63,6 +63,7 @@ where
 }
 
 impl Kernel {
+    /// Similarity of `x` and `y` under this kernel.
     pub fn apply(&self, x: &[f32], y: &[f32]) -> Result<f32, MathError> {
         match self {
             Kernel::RBF { gamma } => rbf_kernel(x, y, *gamma),
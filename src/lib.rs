@@ -0,0 +1,53 @@
+//! Motion space: users and posts embedded in a shared vector space, where
+//! every interaction moves the participants' coords.
+//!
+//! The compute core ([`MotionSpace`], [`embedding`], [`kernel`], [`math`],
+//! [`feed`]) and the analytics over it ([`graph`], [`cluster`], [`topics`],
+//! [`projection`]) are synchronous and have no IO. The `runtime` feature (on
+//! by default) adds the tokio-driven [`MotionSpace::core_loop`], the
+//! [`pipeline`] embedding stage, the [`shard`]ed space and the line-based
+//! input loop used by the `motion` binary, and the `batch` feature adds the
+//! bulk file loader.
+
+#![warn(missing_docs)]
+
+#[cfg(feature = "batch")]
+pub mod batch;
+pub mod cluster;
+pub mod config;
+pub mod embedding;
+pub mod feed;
+pub mod graph;
+pub mod kernel;
+pub mod math;
+pub mod metrics;
+pub mod motion_core;
+pub mod motion_input;
+#[cfg(feature = "runtime")]
+pub mod pipeline;
+pub mod projection;
+#[cfg(feature = "runtime")]
+pub mod queue;
+#[cfg(feature = "runtime")]
+pub mod shard;
+pub mod simd;
+pub mod snapshot;
+pub mod topics;
+
+pub use crate::cluster::{ClusterChange, ClusterConfig, ClusterSummary, Clusters};
+pub use crate::config::{ConfigError, MotionConfig, MotionSpaceBuilder};
+pub use crate::embedding::{embed_post, Embedder, TokenIndex, EMBEDDING_DIM};
+pub use crate::feed::{Exploration, FeedBuilder, FeedConfig, FeedItem};
+pub use crate::graph::{Component, DegreeStats, Direction, Edge, InteractionGraph};
+pub use crate::kernel::Kernel;
+pub use crate::math::{MathError, VecN};
+pub use crate::motion_core::{
+    CoreError, Dynamics, Engagement, InteractionResult, MotionEntry, MotionOutput, MotionPost,
+    MotionSpace, MotionUser, PreparedInput, ScoredPost, ScoredUser, SpaceStats,
+};
+pub use crate::motion_input::{
+    DeleteInput, EditPostInput, Interaction, InteractionTarget, InteractionType, KindDynamics,
+    MotionInput, PostInput, UserInput,
+};
+pub use crate::snapshot::SnapshotError;
+pub use crate::topics::{Topic, TopicConfig, TopicLabel, TopicReport};
@@ -1,71 +1,753 @@
 use std::error::Error;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-mod embedding;
-mod kernel;
-mod math;
-mod motion_core;
-mod motion_input;
+use clap::Parser;
+use tokio::io::BufReader;
+use tokio::net::TcpListener;
+use serde::Serialize;
+use tokio::io::AsyncBufRead;
+use tokio::sync::{mpsc, watch};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
 
-use tokio::sync::mpsc;
+use motion_core::batch::BatchLoader;
+use motion_core::pipeline::EmbedPool;
+use motion_core::metrics::{serve_metrics, Metrics};
+use motion_core::motion_input::{InputError, LoopExit};
+use motion_core::queue::{shed_queue, QueueMonitor, QueueSender};
+use motion_core::projection::{render_svg, Method};
+use motion_core::shard::ShardedSpace;
+use motion_core::{
+    ClusterConfig, MotionConfig, TopicConfig, MotionEntry, MotionInput, MotionOutput, MotionSpace, MotionSpaceBuilder,
+    PreparedInput,
+};
 
-use crate::embedding::EMBEDDING_DIM;
-use crate::motion_core::{MotionEntry, MotionOutput, MotionSpace};
-use crate::motion_input::MotionInput;
+mod cli;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    // Channel from stdin loop -> core loop
-    let (input_tx, input_rx) = mpsc::channel::<MotionInput>(64);
+use crate::cli::{
+    Cli, ClustersArgs, Command, ExportArgs, FeedArgs, GraphArgs, InputSource, LoadArgs, LogFormat, OutputFormat,
+    PipelineArgs, ProjectArgs, SnapshotArgs, StatsArgs, TopicsArgs, WhoToFollowArgs,
+};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// How many coord components the non-verbose text output shows.
+const COORD_PREVIEW: usize = 6;
+
+/// How often `-v` reports the input queue while it is backed up.
+const QUEUE_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Broadcast once input should stop so the pipeline can drain and exit.
+#[derive(Clone)]
+struct Shutdown(Arc<watch::Sender<bool>>);
+
+impl Shutdown {
+    fn new() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+
+    fn trigger(&self) {
+        self.0.send_replace(true);
+    }
+
+    async fn wait(&self) {
+        let _ = self.0.subscribe().wait_for(|&stop| stop).await;
+    }
+}
+
+/// Outputs seen by the logger over one `serve` or `replay` run.
+#[derive(Debug, Default, Serialize)]
+struct RunSummary {
+    entered: u64,
+    updated: u64,
+    removed: u64,
+    interactions: u64,
+    cluster_changes: u64,
+    rejected: u64,
+    elapsed_ms: u128,
+}
+
+impl RunSummary {
+    fn record(&mut self, out: &MotionOutput) {
+        match out {
+            MotionOutput::Entered(_) => self.entered += 1,
+            MotionOutput::Updated(_) => self.updated += 1,
+            MotionOutput::Removed(_) => self.removed += 1,
+            MotionOutput::InteractionApplied(_) => self.interactions += 1,
+            MotionOutput::ClusterChanged(_) => self.cluster_changes += 1,
+            MotionOutput::Rejected { .. } => self.rejected += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OutputOptions {
+    format: OutputFormat,
+    quiet: bool,
+    verbose: bool,
+}
+
+fn main() -> Result<(), BoxError> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(dispatch());
+    // A stdin read blocks a runtime thread until a line or EOF arrives, even
+    // after shutdown stopped listening; everything is reported by now, so
+    // do not wait for it.
+    runtime.shutdown_background();
+    result
+}
+
+async fn dispatch() -> Result<(), BoxError> {
+    let cli = Cli::parse();
+    init_logging(cli.log_format, cli.quiet, cli.verbose);
+    let output = OutputOptions {
+        format: cli.format,
+        quiet: cli.quiet,
+        verbose: cli.verbose > 0,
+    };
+
+    match cli.command.unwrap_or_default() {
+        Command::Serve(args) => {
+            let space = open_space(cli.config.as_deref(), &args.snapshot)?;
+            run(space, args.input, &args.pipeline, &args.snapshot, output).await
+        }
+        Command::Replay(args) => {
+            let space = open_space(cli.config.as_deref(), &args.snapshot)?;
+            run(space, InputSource::File(args.file), &args.pipeline, &args.snapshot, output).await
+        }
+        Command::Load(args) => {
+            let space = open_space(cli.config.as_deref(), &args.snapshot)?;
+            load(space, &args, output)
+        }
+        Command::Export(args) => export(&args, output),
+        Command::Stats(args) => stats(&args, output),
+        Command::Feed(args) => feed(&args, output),
+        Command::WhoToFollow(args) => who_to_follow(&args, output),
+        Command::Graph(args) => graph(&args, output),
+        Command::Clusters(args) => clusters(&args, output),
+        Command::Topics(args) => topics(&args, output),
+        Command::Project(args) => project(&args, output),
+    }
+}
+
+/// Logs go to stderr so stdout carries only outputs and reports. `RUST_LOG`
+/// overrides the level picked from `-q`/`-v`.
+fn init_logging(format: LogFormat, quiet: bool, verbose: u8) {
+    let level = match (quiet, verbose) {
+        (true, _) => "error",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr);
+    match format {
+        LogFormat::Pretty => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+fn open_space(config: Option<&Path>, snapshot: &SnapshotArgs) -> Result<MotionSpace, BoxError> {
+    if let Some(path) = &snapshot.load {
+        if config.is_some() {
+            warn!("--config is ignored when loading a snapshot");
+        }
+        return Ok(MotionSpace::load_snapshot(path)?);
+    }
+    let config = match config {
+        Some(path) => MotionConfig::from_json_file(path)?,
+        None => MotionConfig::default(),
+    };
+    Ok(MotionSpaceBuilder::from_config(config).build()?)
+}
+
+async fn run(
+    mut space: MotionSpace,
+    source: InputSource,
+    pipeline: &PipelineArgs,
+    snapshot: &SnapshotArgs,
+    output: OutputOptions,
+) -> Result<(), BoxError> {
+    // Queue from input loop -> embedding pool; the only place inputs are shed
+    let (input_tx, input_rx) = shed_queue(pipeline.queue_size, pipeline.shed_policy);
+    let input_queue = input_tx.monitor();
+    // Channel from embedding pool -> core loop
+    let (prepared_tx, prepared_rx) = mpsc::channel::<PreparedInput>(pipeline.queue_size.max(1));
     // Channel from core loop -> logger
-    let (entry_tx, mut entry_rx) = mpsc::channel::<MotionOutput>(64);
+    let (entry_tx, mut entry_rx) = mpsc::channel::<MotionOutput>(pipeline.queue_size.max(1));
+
+    let interactive = matches!(source, InputSource::Stdin)
+        && !output.quiet
+        && output.format == OutputFormat::Text;
+
+    // Ctrl-C / SIGTERM or a `shutdown` command stops input; the rest drains
+    let shutdown = Shutdown::new();
+    spawn_signal_handler(shutdown.clone());
+    let started = Instant::now();
 
-    // Spawn the input loop (stdin driven)
+    // Spawn the input loop
+    let input_shutdown = shutdown.clone();
     let input_handle = tokio::spawn(async move {
-        if let Err(e) = MotionInput::input_loop(input_tx).await {
-            eprintln!("input loop error: {}", e);
+        if let Err(e) = read_source(source, input_tx, interactive, input_shutdown).await {
+            error!(error = %e, "input loop failed");
         }
     });
 
+    // Spawn the embedding stage
+    let mut pool = EmbedPool::new(space.embedder.clone());
+    if let Some(workers) = pipeline.embed_workers {
+        pool = pool.workers(workers);
+    }
+    let pool_handle = tokio::spawn(async move {
+        if let Err(e) = pool.run(input_rx, prepared_tx).await {
+            error!(error = %e, "embedding pool failed");
+        }
+    });
+
+    // Serve metrics for the core and the input queue if asked to
+    let metrics = pipeline.metrics_addr.as_ref().map(|_| Arc::new(Metrics::new()));
+    let metrics_handle = match (&pipeline.metrics_addr, &metrics) {
+        (Some(addr), Some(metrics)) => {
+            let addr = addr.clone();
+            let metrics = metrics.clone();
+            let queue = input_queue.clone();
+            let render = move || metrics.render() + &queue.stats().render("input");
+            Some(tokio::spawn(async move {
+                if let Err(e) = serve_metrics(&addr, render).await {
+                    error!(error = %e, "metrics endpoint failed");
+                }
+            }))
+        }
+        _ => None,
+    };
+
     // Spawn the core loop that processes inputs into motion space updates
+    let shards = pipeline.shards;
     let core_handle = tokio::spawn(async move {
-        let mut space = MotionSpace::new(EMBEDDING_DIM);
-        if let Err(e) = space.core_loop(input_rx, entry_tx).await {
-            eprintln!("core loop error: {}", e);
+        if shards <= 1 {
+            let result = match &metrics {
+                Some(metrics) => space.core_loop_observed(prepared_rx, entry_tx, metrics).await,
+                None => space.core_loop(prepared_rx, entry_tx).await,
+            };
+            if let Err(e) = result {
+                error!(error = %e, "core loop failed");
+            }
+            return Ok::<_, BoxError>(space);
+        }
+        let mut sharded = match metrics {
+            Some(metrics) => ShardedSpace::split_observed(space, shards, entry_tx, metrics),
+            None => ShardedSpace::split(space, shards, entry_tx),
+        };
+        if let Err(e) = sharded.run(prepared_rx).await {
+            error!(error = %e, "shard router failed");
         }
+        Ok(sharded.shutdown().await?)
+    });
+
+    // With -v, report the input queue while it is backed up
+    let reporter = output.verbose.then(|| {
+        let queue = input_queue.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(QUEUE_REPORT_INTERVAL);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                if queue.stats().depth > 0 {
+                    report_queue(&queue);
+                }
+            }
+        })
     });
 
     // Log entries as they are produced
-    while let Some(output) = entry_rx.recv().await {
-        log_output(&output);
+    let mut summary = RunSummary::default();
+    while let Some(out) = entry_rx.recv().await {
+        summary.record(&out);
+        log_output(&out, output);
     }
+    io::stdout().flush()?;
 
     // Ensure tasks complete (they may already be done if channels closed)
     let _ = input_handle.await;
-    let _ = core_handle.await;
+    let _ = pool_handle.await;
+    let space = core_handle.await??;
+    for handle in reporter.into_iter().chain(metrics_handle) {
+        handle.abort();
+    }
+
+    match &snapshot.save {
+        Some(path) => {
+            space.save_snapshot(path)?;
+            info!(path = %path.display(), "saved snapshot");
+        }
+        None if *shutdown.0.borrow() => {
+            warn!("no --save given, final state is discarded");
+        }
+        None => {}
+    }
+
+    summary.elapsed_ms = started.elapsed().as_millis();
+    if !output.quiet {
+        report_summary(&summary, &space, output)?;
+    }
+    if output.verbose || input_queue.stats().shed() > 0 {
+        report_queue(&input_queue);
+    }
+    Ok(())
+}
+
+fn report_summary(summary: &RunSummary, space: &MotionSpace, output: OutputOptions) -> Result<(), BoxError> {
+    let stats = space.stats();
+    match output.format {
+        OutputFormat::Json => {
+            let line = serde_json::json!({ "summary": summary, "space": stats });
+            eprintln!("{}", line);
+        }
+        OutputFormat::Text => {
+            eprintln!(
+                "processed in {} ms: {} entered, {} updated, {} removed, {} interactions, {} cluster changes, {} rejected",
+                summary.elapsed_ms,
+                summary.entered,
+                summary.updated,
+                summary.removed,
+                summary.interactions,
+                summary.cluster_changes,
+                summary.rejected,
+            );
+            eprintln!(
+                "space: {} users ({} unplaced), {} posts by {} authors, motion mean {:.4} max {:.4}",
+                stats.users,
+                stats.unplaced_users,
+                stats.posts,
+                stats.authors,
+                stats.mean_motion,
+                stats.max_motion,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The first Ctrl-C / SIGTERM triggers `shutdown`; a second one exits at once.
+fn spawn_signal_handler(shutdown: Shutdown) {
+    tokio::spawn(async move {
+        if let Err(e) = wait_for_signal().await {
+            error!(error = %e, "cannot listen for signals");
+            return;
+        }
+        info!("shutting down: draining queued inputs (signal again to abort)");
+        shutdown.trigger();
+        if wait_for_signal().await.is_ok() {
+            error!("aborted before the final snapshot");
+            std::process::exit(130);
+        }
+    });
+}
+
+async fn wait_for_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await
+    }
+}
+
+fn report_queue(queue: &QueueMonitor) {
+    let stats = queue.stats();
+    info!(
+        depth = stats.depth,
+        capacity = stats.capacity,
+        high_water = stats.high_water,
+        enqueued = stats.enqueued,
+        dropped_oldest = stats.dropped_oldest,
+        rejected = stats.rejected,
+        coalesced = stats.coalesced,
+        "input queue"
+    );
+}
+
+async fn read_source(
+    source: InputSource,
+    tx: QueueSender,
+    interactive: bool,
+    shutdown: Shutdown,
+) -> Result<(), BoxError> {
+    match source {
+        InputSource::Stdin => {
+            let stdin = BufReader::new(tokio::io::stdin());
+            read_until_shutdown(stdin, tx, interactive, &shutdown).await?;
+        }
+        InputSource::File(path) => {
+            let file = tokio::fs::File::open(&path).await?;
+            read_until_shutdown(BufReader::new(file), tx, false, &shutdown).await?;
+        }
+        InputSource::Socket(addr) => {
+            let listener = TcpListener::bind(&addr).await?;
+            info!(addr = %listener.local_addr()?, "listening");
+            loop {
+                let (stream, peer) = tokio::select! {
+                    accepted = listener.accept() => accepted?,
+                    _ = shutdown.wait() => break,
+                };
+                let tx = tx.clone();
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    let reader = BufReader::new(stream);
+                    if let Err(e) = read_until_shutdown(reader, tx, false, &shutdown).await {
+                        error!(%peer, error = %e, "connection failed");
+                    }
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads from one source until it ends or shutdown is triggered. A
+/// `shutdown` command from the source triggers it for every source.
+async fn read_until_shutdown<R>(
+    reader: R,
+    tx: QueueSender,
+    interactive: bool,
+    shutdown: &Shutdown,
+) -> Result<(), InputError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    tokio::select! {
+        exit = MotionInput::read_loop(reader, tx, interactive) => {
+            if exit? == LoopExit::Shutdown {
+                shutdown.trigger();
+            }
+        }
+        _ = shutdown.wait() => {}
+    }
+    Ok(())
+}
+
+fn load(mut space: MotionSpace, args: &LoadArgs, output: OutputOptions) -> Result<(), BoxError> {
+    let mut loader = BatchLoader::new();
+    for path in &args.users {
+        loader = loader.users(path);
+    }
+    for path in &args.posts {
+        loader = loader.posts(path);
+    }
+    for path in &args.interactions {
+        loader = loader.interactions(path);
+    }
+    if let Some(workers) = args.workers {
+        loader = loader.workers(workers);
+    }
+
+    let report = loader.load_with_progress(&mut space, |p| {
+        if !output.quiet {
+            eprintln!("applied {}/{}", p.applied, p.total);
+        }
+    });
 
+    match output.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&report)?),
+        OutputFormat::Text => {
+            println!(
+                "loaded {} users, {} posts, {} interactions in {} ms",
+                report.users, report.posts, report.interactions, report.elapsed_ms
+            );
+            if !report.bad_rows.is_empty() {
+                println!("{} bad rows:", report.bad_rows.len());
+                for row in &report.bad_rows {
+                    println!("  {}:{}  {}", row.file, row.line, row.reason);
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &args.snapshot.save {
+        space.save_snapshot(path)?;
+    }
     Ok(())
 }
 
-fn log_output(output: &MotionOutput) {
-    match output {
-        MotionOutput::Entered(entry) | MotionOutput::Updated(entry) => log_entry(entry),
+fn export(args: &ExportArgs, output: OutputOptions) -> Result<(), BoxError> {
+    let space = MotionSpace::load_snapshot(&args.snapshot)?;
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    for entry in &space.entries {
+        match output.format {
+            OutputFormat::Json => writeln!(out, "{}", serde_json::to_string(entry)?)?,
+            OutputFormat::Text => writeln!(out, "{}", format_entry(entry, output.verbose))?,
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn stats(args: &StatsArgs, output: OutputOptions) -> Result<(), BoxError> {
+    let space = MotionSpace::load_snapshot(&args.snapshot)?;
+    let stats = space.stats();
+    match output.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&stats)?),
+        OutputFormat::Text => {
+            println!("dim            {}", stats.dim);
+            println!("users          {} ({} unplaced)", stats.users, stats.unplaced_users);
+            println!("posts          {} by {} authors", stats.posts, stats.authors);
+            println!("motion         mean {:.4}  max {:.4}", stats.mean_motion, stats.max_motion);
+        }
+    }
+    Ok(())
+}
+
+fn feed(args: &FeedArgs, output: OutputOptions) -> Result<(), BoxError> {
+    const HOUR_MS: f64 = 3_600_000.0;
+    let space = MotionSpace::load_snapshot(&args.snapshot)?;
+    let mut builder = space.feed().exploration(args.explore);
+    if let Some(seed) = args.seed {
+        builder = builder.seed(seed);
+    }
+    let items = builder
+        .size(args.size)
+        .candidates(args.candidates)
+        .lambda(args.lambda)
+        .max_per_author((args.max_per_author > 0).then_some(args.max_per_author))
+        .max_age_ms(args.max_age_hours.map(|h| (h * HOUR_MS) as i64))
+        .half_life_ms(args.half_life_hours.map(|h| (h * HOUR_MS) as i64))
+        .build(&args.user)?;
+    for (rank, item) in items.iter().enumerate() {
+        match output.format {
+            OutputFormat::Json => println!("{}", serde_json::to_string(item)?),
+            OutputFormat::Text => println!(
+                "{:>3}. [{}] by {}  sim {:.4}  relevance {:.4}  score {:.4}{}",
+                rank + 1,
+                item.post_id,
+                item.author_id,
+                item.similarity,
+                item.relevance,
+                item.score,
+                if item.explored { "  (explored)" } else { "" }
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn who_to_follow(args: &WhoToFollowArgs, output: OutputOptions) -> Result<(), BoxError> {
+    let space = MotionSpace::load_snapshot(&args.snapshot)?;
+    for (rank, user) in space.recommend_users(&args.user, args.k)?.iter().enumerate() {
+        match output.format {
+            OutputFormat::Json => println!("{}", serde_json::to_string(user)?),
+            OutputFormat::Text => println!(
+                "{:>3}. [{}]  sim {:.4}  motion {:.4}  score {:.4}",
+                rank + 1,
+                user.user_id,
+                user.similarity,
+                user.motion,
+                user.score
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn graph(args: &GraphArgs, output: OutputOptions) -> Result<(), BoxError> {
+    let space = MotionSpace::load_snapshot(&args.snapshot)?;
+    let degrees = space.graph.degree_stats();
+    let mut ranks: Vec<(String, f32)> = space.graph.pagerank(args.damping, args.iterations).into_iter().collect();
+    ranks.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranks.truncate(args.top);
+    let components = space.graph.connected_components();
+    match output.format {
+        OutputFormat::Json => {
+            let report = serde_json::json!({
+                "degrees": degrees,
+                "pagerank": ranks
+                    .iter()
+                    .map(|(user_id, rank)| serde_json::json!({ "user_id": user_id, "rank": rank }))
+                    .collect::<Vec<_>>(),
+                "components": components.len(),
+                "largest_components": &components[..components.len().min(args.top)],
+            });
+            println!("{}", report);
+        }
+        OutputFormat::Text => {
+            println!("users          {} ({} user edges, mean out-degree {:.2})", degrees.users, degrees.user_edges, degrees.mean_user_out_degree);
+            println!("posts          {} ({} engagement edges)", degrees.posts, degrees.post_edges);
+            println!(
+                "max degree     out {}  in {}  post engagers {}",
+                degrees.max_user_out_degree, degrees.max_user_in_degree, degrees.max_post_engagers
+            );
+            println!("components     {}", components.len());
+            for (i, component) in components.iter().take(args.top).enumerate() {
+                println!("{:>3}. {} users  {} posts", i + 1, component.users.len(), component.posts.len());
+            }
+            println!("pagerank");
+            for (rank, (user_id, score)) in ranks.iter().enumerate() {
+                println!("{:>3}. [{}]  {:.5}", rank + 1, user_id, score);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn clusters(args: &ClustersArgs, output: OutputOptions) -> Result<(), BoxError> {
+    let mut space = MotionSpace::load_snapshot(&args.snapshot)?;
+    if args.k.is_some() || space.clusters.is_none() {
+        let k = args.k.unwrap_or(ClusterConfig::default().k);
+        if k == 0 || args.batch_size == 0 {
+            return Err("--k and --batch-size must be greater than zero".into());
+        }
+        space.enable_clusters(ClusterConfig { k, batch_size: args.batch_size });
+    }
+    let summaries = space.clusters.as_ref().map(|c| c.summaries()).unwrap_or_default();
+    for cluster in &summaries {
+        match output.format {
+            OutputFormat::Json => println!("{}", serde_json::to_string(cluster)?),
+            OutputFormat::Text => {
+                println!(
+                    "{:>3}. {} members  absorbed {}  centroid {}",
+                    cluster.id,
+                    cluster.members.len(),
+                    cluster.absorbed,
+                    format_coord(&cluster.centroid, output.verbose)
+                );
+                if !cluster.members.is_empty() {
+                    println!("     {}", cluster.members.join(" "));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The report is JSON in either output format; text pretty-prints it.
+fn topics(args: &TopicsArgs, output: OutputOptions) -> Result<(), BoxError> {
+    let space = MotionSpace::load_snapshot(&args.snapshot)?;
+    let config = TopicConfig {
+        min_similarity: args.min_similarity,
+        min_posts: args.min_posts,
+        labels: args.labels,
+    };
+    let report = space.discover_topics(&config)?;
+    let json = match output.format {
+        OutputFormat::Json => serde_json::to_string(&report)?,
+        OutputFormat::Text => serde_json::to_string_pretty(&report)?,
+    };
+    match &args.out {
+        Some(path) => {
+            std::fs::write(path, json + "\n")?;
+            info!(path = %path.display(), topics = report.topics.len(), noise = report.noise.len(), "topic report written");
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn project(args: &ProjectArgs, output: OutputOptions) -> Result<(), BoxError> {
+    let space = MotionSpace::load_snapshot(&args.snapshot)?;
+    let method = match args.method {
+        Method::Tsne { perplexity, .. } => Method::Tsne { perplexity, iterations: args.iterations },
+        method => method,
+    };
+    let points = space.project(method, args.dims)?;
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    match output.format {
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut out, &points)?;
+            writeln!(out)?;
+            out.flush()?;
+        }
+        OutputFormat::Text => {
+            let mut writer = csv::Writer::from_writer(out);
+            for point in &points {
+                writer.serialize(point)?;
+            }
+            writer.flush()?;
+        }
+    }
+    if let Some(path) = &args.svg {
+        fs::write(path, render_svg(&points, args.svg_size, args.svg_size))?;
+        info!(path = %path.display(), points = points.len(), "scatter plot written");
+    }
+    Ok(())
+}
+
+fn log_output(out: &MotionOutput, output: OutputOptions) {
+    if output.quiet {
+        if let MotionOutput::Rejected { error, .. } = out {
+            eprintln!("Rejected: {}", error);
+        }
+        return;
+    }
+    if output.format == OutputFormat::Json {
+        match serde_json::to_string(out) {
+            Ok(line) => println!("{}", line),
+            Err(e) => error!(error = %e, "failed to encode output"),
+        }
+        return;
+    }
+    match out {
+        MotionOutput::Entered(entry) | MotionOutput::Updated(entry) => {
+            println!("{}", format_entry(entry, output.verbose))
+        }
+        MotionOutput::Removed(entry) => println!("Removed [{}]", entry.id()),
+        MotionOutput::Rejected { error, .. } => println!("Rejected: {}", error),
         MotionOutput::InteractionApplied(result) => {
             println!(
-                "Interaction {} -> {}  weight {:.4}  sim {:.4}",
-                result.src_id, result.dst_id, result.weight, result.similarity
+                "Interaction {} {} -> {}  weight {:.4}  sim {:.4}",
+                result.kind.name(), result.src_id, result.dst_id, result.weight, result.similarity
             );
         }
+        MotionOutput::ClusterChanged(change) => println!(
+            "Cluster [{}] {} -> {}",
+            change.user_id,
+            format_cluster(change.from),
+            format_cluster(change.to)
+        ),
     };
 }
 
-fn log_entry(entry: &MotionEntry) {
+fn format_cluster(cluster: Option<usize>) -> String {
+    cluster.map_or_else(|| "none".to_string(), |c| c.to_string())
+}
+
+fn format_entry(entry: &MotionEntry, verbose: bool) -> String {
     match entry {
         MotionEntry::User(u) => {
-            let coord = u.coord.as_ref().map(|c| &c.data);
-            println!("User [{}]  coord {:?}", u.id, coord);
+            let coord = u
+                .coord
+                .as_ref()
+                .map(|c| format_coord(&c.data, verbose))
+                .unwrap_or_else(|| "None".to_string());
+            format!("User [{}]  motion {:.4}  coord {}", u.id, u.motion, coord)
         }
         MotionEntry::Post(p) => {
-            println!("Post [{}] coord {:?}", p.id, p.coord.data);
+            format!("Post [{}] by {}  coord {}", p.id, p.author_id, format_coord(&p.coord.data, verbose))
         }
     }
 }
+
+fn format_coord(data: &[f32], verbose: bool) -> String {
+    if verbose || data.len() <= COORD_PREVIEW {
+        return format!("{:?}", data);
+    }
+    let head: Vec<String> = data[..COORD_PREVIEW].iter().map(|x| format!("{:.4}", x)).collect();
+    format!("[{}, ...] ({} dims)", head.join(", "), data.len())
+}
@@ -1,23 +1,81 @@
+//! Flat-slice vector arithmetic, the cached-norm [`VecN`], and the small
+//! dense linear algebra the analytics need: a row-major [`Matrix`],
+//! covariance, symmetric eigen-decomposition and [`Pca`].
+
+use std::ops::{Index, IndexMut};
+
+use crate::simd;
+
 use thiserror::Error;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Error)]
+/// Errors of the vector and matrix operations.
+#[derive(Debug, Error, Clone, Serialize, Deserialize)]
 pub enum MathError {
+    /// Two vectors that must have the same length do not.
     #[error("dimension mismatch: left = {left}, right = {right}")]
-    DimensionMismatch { left: usize, right: usize },
+    DimensionMismatch {
+        /// Length of the left operand.
+        left: usize,
+        /// Length of the right operand.
+        right: usize,
+    },
 
+    /// A zero vector has no direction to normalize to.
     #[error("zero-length vector: cannot normalize")]
     ZeroNorm,
+
+    /// Two matrices have incompatible shapes for the operation.
+    #[error("shape mismatch: left is {left:?}, right is {right:?}")]
+    ShapeMismatch {
+        /// Rows and columns of the left operand.
+        left: (usize, usize),
+        /// Rows and columns of the right operand.
+        right: (usize, usize),
+    },
+
+    /// The operation needs a square matrix.
+    #[error("{rows}x{cols} matrix is not square")]
+    NotSquare {
+        /// Rows of the matrix.
+        rows: usize,
+        /// Columns of the matrix.
+        cols: usize,
+    },
+
+    /// Too few observations for the statistic.
+    #[error("need at least {needed} rows, found {found}")]
+    TooFewRows {
+        /// Rows the operation needs.
+        needed: usize,
+        /// Rows the matrix has.
+        found: usize,
+    },
+
+    /// PCA was asked for no components or more than the data has.
+    #[error("can keep 1 to {available} components of {available}-dimensional data, not {requested}")]
+    ComponentCount {
+        /// Components asked for.
+        requested: usize,
+        /// Columns of the data.
+        available: usize,
+    },
 }
 
+/// A dense `f32` vector with a cached norm.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VecN {
+    /// The components.
     pub data: Vec<f32>,
+    /// Cached length of `data`; `None` until [`VecN::norm`] computes it.
     pub norm: Option<f32>,
+    /// Reserved for a cached unit-length copy of `data`; never filled at the
+    /// moment.
     pub normalized: Option<Vec<f32>>,
 }
 
 impl VecN {
+    /// Wraps `data` with an empty cache.
     pub fn new(data: Vec<f32>) -> Self {
         Self {
             data,
@@ -26,24 +84,29 @@ impl VecN {
         }
     }
 
+    /// Copies `slice` into a new vector.
     pub fn from_slice(slice: &[f32]) -> Self {
         Self::new(slice.to_vec())
     }
 
+    /// Number of components.
     pub fn dim(&self) -> usize {
         self.data.len()
     }
     
+    /// Forgets the cached norm.
     pub fn clear_cache(&mut self) {
         self.norm = None;
         self.normalized = None;
     }
 
+    /// Replaces the components and clears the cache.
     pub fn set_data(&mut self, data: Vec<f32>) {
         self.data = data;
         self.clear_cache();
     }
 
+    /// Euclidean length, computed once and cached.
     pub fn norm(&mut self) -> f32 {
         if let Some(n) = self.norm {
             return n;
@@ -53,6 +116,8 @@ impl VecN {
         n
     }
 
+    /// Scales `data` to unit length in place and returns it. The cached norm
+    /// keeps the length from before scaling.
     pub fn normalize(&mut self) -> Result<&[f32], MathError> {
         if let Some(ref x) = self.normalized {
             return Ok(x);
@@ -73,6 +138,7 @@ impl VecN {
     }
 }
 
+/// Element-wise `a + b`.
 pub fn add(a: &[f32], b: &[f32]) -> Result<Vec<f32>, MathError> {
     if a.len() != b.len() {
         return Err(MathError::DimensionMismatch {
@@ -83,6 +149,7 @@ pub fn add(a: &[f32], b: &[f32]) -> Result<Vec<f32>, MathError> {
     Ok(a.iter().zip(b.iter()).map(|(x, y)| x + y).collect())
 }
 
+/// Element-wise `a - b`.
 pub fn sub(a: &[f32], b: &[f32]) -> Result<Vec<f32>, MathError> {
     if a.len() != b.len() {
         return Err(MathError::DimensionMismatch {
@@ -94,6 +161,7 @@ pub fn sub(a: &[f32], b: &[f32]) -> Result<Vec<f32>, MathError> {
 }
 
 
+/// Dot product of `a` and `b`.
 pub fn dot(a: &[f32], b: &[f32]) -> Result<f32, MathError> {
     if a.len() != b.len() {
         return Err(MathError::DimensionMismatch {
@@ -101,13 +169,15 @@ pub fn dot(a: &[f32], b: &[f32]) -> Result<f32, MathError> {
             right: b.len(),
         });
     }
-    Ok(a.iter().zip(b.iter()).map(|(x, y)| x * y).sum())
+    Ok(simd::dot(a, b))
 }
 
+/// `a * s`.
 pub fn scale(a: &[f32], s: f32) -> Vec<f32> {
     a.iter().map(|x| x * s).collect()
 }
 
+/// `a` scaled to unit length.
 pub fn normalize_slice(a: &[f32]) -> Result<VecN, MathError> {
     let mut v = VecN::from_slice(a);
     v.normalize()?; // populate cache / validate non-zero norm
@@ -115,3 +185,454 @@ pub fn normalize_slice(a: &[f32]) -> Result<VecN, MathError> {
 }
 
 
+
+/// Dense row-major `f32` matrix.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f32>,
+}
+
+impl Matrix {
+    /// A `rows` x `cols` matrix over row-major `data` of exactly that size.
+    pub fn new(rows: usize, cols: usize, data: Vec<f32>) -> Result<Self, MathError> {
+        if data.len() != rows * cols {
+            return Err(MathError::DimensionMismatch { left: rows * cols, right: data.len() });
+        }
+        Ok(Self { rows, cols, data })
+    }
+
+    /// A `rows` x `cols` matrix of zeros.
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Self { rows, cols, data: vec![0.0; rows * cols] }
+    }
+
+    /// The `n` x `n` identity.
+    pub fn identity(n: usize) -> Self {
+        let mut m = Self::zeros(n, n);
+        for i in 0..n {
+            m[(i, i)] = 1.0;
+        }
+        m
+    }
+
+    /// Stacks equally long rows.
+    pub fn from_rows<R: AsRef<[f32]>>(rows: &[R]) -> Result<Self, MathError> {
+        let cols = rows.first().map_or(0, |r| r.as_ref().len());
+        let mut data = Vec::with_capacity(rows.len() * cols);
+        for row in rows {
+            let row = row.as_ref();
+            if row.len() != cols {
+                return Err(MathError::DimensionMismatch { left: cols, right: row.len() });
+            }
+            data.extend_from_slice(row);
+        }
+        Ok(Self { rows: rows.len(), cols, data })
+    }
+
+    /// Number of rows.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Number of columns.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// `(rows, cols)`.
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    /// Every element, row-major.
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Row `r`.
+    pub fn row(&self, r: usize) -> &[f32] {
+        &self.data[r * self.cols..(r + 1) * self.cols]
+    }
+
+    /// Row `r`, mutably.
+    pub fn row_mut(&mut self, r: usize) -> &mut [f32] {
+        &mut self.data[r * self.cols..(r + 1) * self.cols]
+    }
+
+    /// Every row in order.
+    pub fn iter_rows(&self) -> impl Iterator<Item = &[f32]> {
+        (0..self.rows).map(|r| self.row(r))
+    }
+
+    /// The transposed matrix.
+    pub fn transpose(&self) -> Matrix {
+        let mut t = Matrix::zeros(self.cols, self.rows);
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                t[(c, r)] = self[(r, c)];
+            }
+        }
+        t
+    }
+
+    /// `self * v`.
+    pub fn mat_vec(&self, v: &[f32]) -> Result<Vec<f32>, MathError> {
+        if v.len() != self.cols {
+            return Err(MathError::ShapeMismatch { left: self.shape(), right: (v.len(), 1) });
+        }
+        Ok(self.iter_rows().map(|row| row.iter().zip(v).map(|(a, b)| a * b).sum()).collect())
+    }
+
+    /// `self * other`.
+    pub fn matmul(&self, other: &Matrix) -> Result<Matrix, MathError> {
+        if self.cols != other.rows {
+            return Err(MathError::ShapeMismatch { left: self.shape(), right: other.shape() });
+        }
+        let mut out = Matrix::zeros(self.rows, other.cols);
+        for r in 0..self.rows {
+            let out_row = &mut out.data[r * other.cols..(r + 1) * other.cols];
+            // i-k-j order walks both `other` and `out` row by row.
+            for (k, &a) in self.row(r).iter().enumerate() {
+                for (o, &b) in out_row.iter_mut().zip(other.row(k)) {
+                    *o += a * b;
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Mean of each column; zeros for a matrix without rows.
+    pub fn column_means(&self) -> Vec<f32> {
+        let mut sums = vec![0.0f64; self.cols];
+        for row in self.iter_rows() {
+            for (s, &x) in sums.iter_mut().zip(row) {
+                *s += x as f64;
+            }
+        }
+        sums.into_iter().map(|s| (s / self.rows.max(1) as f64) as f32).collect()
+    }
+
+    /// Sample covariance of the columns, treating each row as one
+    /// observation.
+    pub fn covariance(&self) -> Result<Matrix, MathError> {
+        if self.rows < 2 {
+            return Err(MathError::TooFewRows { needed: 2, found: self.rows });
+        }
+        let means = self.column_means();
+        let mut cov = vec![0.0f64; self.cols * self.cols];
+        let mut centered = vec![0.0f64; self.cols];
+        for row in self.iter_rows() {
+            for ((c, &x), &m) in centered.iter_mut().zip(row).zip(&means) {
+                *c = (x - m) as f64;
+            }
+            for i in 0..self.cols {
+                for j in i..self.cols {
+                    cov[i * self.cols + j] += centered[i] * centered[j];
+                }
+            }
+        }
+        let mut out = Matrix::zeros(self.cols, self.cols);
+        let n = (self.rows - 1) as f64;
+        for i in 0..self.cols {
+            for j in i..self.cols {
+                let v = (cov[i * self.cols + j] / n) as f32;
+                out[(i, j)] = v;
+                out[(j, i)] = v;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Eigenvalues and eigenvectors of a symmetric matrix by cyclic Jacobi
+    /// rotations, largest eigenvalue first. Only the upper triangle is read.
+    pub fn symmetric_eigen(&self) -> Result<Eigen, MathError> {
+        const MAX_SWEEPS: usize = 100;
+        if self.rows != self.cols {
+            return Err(MathError::NotSquare { rows: self.rows, cols: self.cols });
+        }
+        let n = self.rows;
+        let mut a: Vec<f64> = vec![0.0; n * n];
+        for i in 0..n {
+            for j in i..n {
+                a[i * n + j] = self[(i, j)] as f64;
+                a[j * n + i] = self[(i, j)] as f64;
+            }
+        }
+        let mut v: Vec<f64> = vec![0.0; n * n];
+        for i in 0..n {
+            v[i * n + i] = 1.0;
+        }
+
+        let scale: f64 = a.iter().map(|x| x * x).sum::<f64>().max(f64::MIN_POSITIVE);
+        for _ in 0..MAX_SWEEPS {
+            let off: f64 = (0..n)
+                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+                .map(|(i, j)| a[i * n + j] * a[i * n + j])
+                .sum();
+            if off <= scale * 1e-24 {
+                break;
+            }
+            for p in 0..n {
+                for q in p + 1..n {
+                    let apq = a[p * n + q];
+                    if apq == 0.0 {
+                        continue;
+                    }
+                    // Rotation angle that zeroes a[p][q].
+                    let theta = (a[q * n + q] - a[p * n + p]) / (2.0 * apq);
+                    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+                    for k in 0..n {
+                        let (akp, akq) = (a[k * n + p], a[k * n + q]);
+                        a[k * n + p] = c * akp - s * akq;
+                        a[k * n + q] = s * akp + c * akq;
+                    }
+                    for k in 0..n {
+                        let (apk, aqk) = (a[p * n + k], a[q * n + k]);
+                        a[p * n + k] = c * apk - s * aqk;
+                        a[q * n + k] = s * apk + c * aqk;
+                    }
+                    for k in 0..n {
+                        let (vkp, vkq) = (v[k * n + p], v[k * n + q]);
+                        v[k * n + p] = c * vkp - s * vkq;
+                        v[k * n + q] = s * vkp + c * vkq;
+                    }
+                }
+            }
+        }
+
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&i, &j| a[j * n + j].total_cmp(&a[i * n + i]));
+        let values = order.iter().map(|&i| a[i * n + i] as f32).collect();
+        let mut vectors = Matrix::zeros(n, n);
+        for (row, &col) in order.iter().enumerate() {
+            let out = vectors.row_mut(row);
+            for (k, x) in out.iter_mut().enumerate() {
+                *x = v[k * n + col] as f32;
+            }
+            // Eigenvectors are only defined up to sign; make the largest
+            // component positive so results are reproducible.
+            let pivot = out.iter().copied().max_by(|a, b| a.abs().total_cmp(&b.abs())).unwrap_or(0.0);
+            if pivot < 0.0 {
+                out.iter_mut().for_each(|x| *x = -*x);
+            }
+        }
+        Ok(Eigen { values, vectors })
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = f32;
+
+    fn index(&self, (r, c): (usize, usize)) -> &f32 {
+        &self.data[r * self.cols + c]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (r, c): (usize, usize)) -> &mut f32 {
+        &mut self.data[r * self.cols + c]
+    }
+}
+
+/// Result of [`Matrix::symmetric_eigen`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Eigen {
+    /// Descending.
+    pub values: Vec<f32>,
+    /// Row `i` is the unit eigenvector of `values[i]`.
+    pub vectors: Matrix,
+}
+
+/// Principal component analysis fitted on the rows of a matrix.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pca {
+    /// Column means of the fitted data, subtracted before projecting.
+    pub mean: Vec<f32>,
+    /// One unit-length component per row, most variance first.
+    pub components: Matrix,
+    /// Variance along each component.
+    pub explained_variance: Vec<f32>,
+    /// Variance of the data over all dimensions.
+    pub total_variance: f32,
+}
+
+impl Pca {
+    /// Keeps the top `k` components of `data`, one observation per row.
+    pub fn fit(data: &Matrix, k: usize) -> Result<Self, MathError> {
+        if k == 0 || k > data.cols() {
+            return Err(MathError::ComponentCount { requested: k, available: data.cols() });
+        }
+        let eigen = data.covariance()?.symmetric_eigen()?;
+        let total_variance = eigen.values.iter().map(|v| v.max(0.0)).sum();
+        let components = Matrix::from_rows(&eigen.vectors.iter_rows().take(k).collect::<Vec<_>>())?;
+        Ok(Self {
+            mean: data.column_means(),
+            components,
+            explained_variance: eigen.values.into_iter().take(k).map(|v| v.max(0.0)).collect(),
+            total_variance,
+        })
+    }
+
+    /// Share of the total variance each component explains.
+    pub fn explained_variance_ratio(&self) -> Vec<f32> {
+        self.explained_variance
+            .iter()
+            .map(|v| if self.total_variance > 0.0 { v / self.total_variance } else { 0.0 })
+            .collect()
+    }
+
+    /// Projects one observation onto the components.
+    pub fn transform_row(&self, row: &[f32]) -> Result<Vec<f32>, MathError> {
+        let centered = sub(row, &self.mean)?;
+        self.components.mat_vec(&centered)
+    }
+
+    /// Projects every row of `data` onto the components.
+    pub fn transform(&self, data: &Matrix) -> Result<Matrix, MathError> {
+        let k = self.components.rows();
+        let mut out = Vec::with_capacity(data.rows() * k);
+        for row in data.iter_rows() {
+            out.extend(self.transform_row(row)?);
+        }
+        Matrix::new(data.rows(), k, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 1e-4;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < EPS, "{actual:?} != {expected:?}");
+        }
+    }
+
+    /// Eigenvectors are only defined up to sign.
+    fn assert_close_up_to_sign(actual: &[f32], expected: &[f32]) {
+        if actual.iter().zip(expected).map(|(a, e)| a * e).sum::<f32>() < 0.0 {
+            assert_close(&scale(actual, -1.0), expected);
+        } else {
+            assert_close(actual, expected);
+        }
+    }
+
+    /// Checks `m * v = lambda * v` for every eigenpair.
+    fn assert_eigenpairs(m: &Matrix, eigen: &Eigen) {
+        for (row, &value) in eigen.vectors.iter_rows().zip(&eigen.values) {
+            let mv = m.mat_vec(row).unwrap();
+            assert_close(&mv, &scale(row, value));
+            let norm: f32 = row.iter().map(|x| x * x).sum::<f32>().sqrt();
+            assert!((norm - 1.0).abs() < EPS);
+        }
+    }
+
+    #[test]
+    fn matrix_products() {
+        let a = Matrix::from_rows(&[[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]).unwrap();
+        let b = Matrix::from_rows(&[[7.0, 8.0], [9.0, 10.0], [11.0, 12.0]]).unwrap();
+        let ab = a.matmul(&b).unwrap();
+        assert_eq!(ab, Matrix::from_rows(&[[58.0, 64.0], [139.0, 154.0]]).unwrap());
+        assert_eq!(a.mat_vec(&[1.0, 0.0, -1.0]).unwrap(), vec![-2.0, -2.0]);
+        assert_eq!(a.transpose().shape(), (3, 2));
+        assert_eq!(a.transpose()[(2, 1)], 6.0);
+        assert_eq!(Matrix::identity(3).matmul(&b).unwrap(), b);
+    }
+
+    #[test]
+    fn shape_mismatches_are_errors() {
+        let a = Matrix::zeros(2, 3);
+        assert!(matches!(
+            a.matmul(&Matrix::zeros(2, 3)),
+            Err(MathError::ShapeMismatch { left: (2, 3), right: (2, 3) })
+        ));
+        assert!(matches!(a.mat_vec(&[1.0, 2.0]), Err(MathError::ShapeMismatch { .. })));
+        assert!(matches!(Matrix::new(2, 2, vec![1.0; 3]), Err(MathError::DimensionMismatch { .. })));
+        assert!(matches!(
+            Matrix::from_rows(&[vec![1.0, 2.0], vec![3.0]]),
+            Err(MathError::DimensionMismatch { left: 2, right: 1 })
+        ));
+        assert!(matches!(a.symmetric_eigen(), Err(MathError::NotSquare { rows: 2, cols: 3 })));
+        assert!(matches!(
+            Matrix::zeros(1, 3).covariance(),
+            Err(MathError::TooFewRows { needed: 2, found: 1 })
+        ));
+    }
+
+    #[test]
+    fn eigen_of_2x2() {
+        // Eigenvalues 3 and 1, along (1, 1) and (1, -1).
+        let m = Matrix::from_rows(&[[2.0, 1.0], [1.0, 2.0]]).unwrap();
+        let eigen = m.symmetric_eigen().unwrap();
+        assert_close(&eigen.values, &[3.0, 1.0]);
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(eigen.vectors.row(0), &[h, h]);
+        assert_close_up_to_sign(eigen.vectors.row(1), &[h, -h]);
+        assert_eigenpairs(&m, &eigen);
+    }
+
+    #[test]
+    fn eigen_of_3x3() {
+        // Eigenvalues 2 + sqrt(2), 2 and 2 - sqrt(2).
+        let m = Matrix::from_rows(&[[2.0, -1.0, 0.0], [-1.0, 2.0, -1.0], [0.0, -1.0, 2.0]]).unwrap();
+        let eigen = m.symmetric_eigen().unwrap();
+        let r2 = std::f32::consts::SQRT_2;
+        assert_close(&eigen.values, &[2.0 + r2, 2.0, 2.0 - r2]);
+        assert_close_up_to_sign(eigen.vectors.row(0), &[0.5, -0.5 * r2, 0.5]);
+        assert_close_up_to_sign(eigen.vectors.row(1), &[0.5 * r2, 0.0, -0.5 * r2]);
+        assert_eigenpairs(&m, &eigen);
+    }
+
+    #[test]
+    fn eigen_of_identity() {
+        let eigen = Matrix::identity(4).symmetric_eigen().unwrap();
+        assert_close(&eigen.values, &[1.0; 4]);
+        assert_eq!(eigen.vectors, Matrix::identity(4));
+    }
+
+    #[test]
+    fn covariance_of_known_set() {
+        // Means (2, 3); deviations (-1, -2), (0, 0), (1, 2).
+        let data = Matrix::from_rows(&[[1.0, 1.0], [2.0, 3.0], [3.0, 5.0]]).unwrap();
+        assert_close(&data.column_means(), &[2.0, 3.0]);
+        let cov = data.covariance().unwrap();
+        assert_close(cov.as_slice(), &[1.0, 2.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn pca_of_collinear_data() {
+        // Every point lies on the line through (1, 2, 2) / 3.
+        let rows: Vec<[f32; 3]> = (-3..=3).map(|t| t as f32).map(|t| [t, 2.0 * t, 2.0 * t]).collect();
+        let data = Matrix::from_rows(&rows).unwrap();
+        let pca = Pca::fit(&data, 2).unwrap();
+        assert_close(pca.components.row(0), &[1.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0]);
+        assert_close(&pca.explained_variance_ratio(), &[1.0, 0.0]);
+        // Projections are the distances along the line.
+        let projected = pca.transform(&data).unwrap();
+        for (row, t) in projected.iter_rows().zip(-3..=3) {
+            assert_close(row, &[3.0 * t as f32, 0.0]);
+        }
+    }
+
+    #[test]
+    fn pca_component_count_is_checked() {
+        let data = Matrix::from_rows(&[[1.0, 0.0], [0.0, 1.0], [1.0, 1.0]]).unwrap();
+        assert!(matches!(
+            Pca::fit(&data, 0),
+            Err(MathError::ComponentCount { requested: 0, available: 2 })
+        ));
+        assert!(matches!(
+            Pca::fit(&data, 3),
+            Err(MathError::ComponentCount { requested: 3, available: 2 })
+        ));
+        let pca = Pca::fit(&data, 2).unwrap();
+        assert!(matches!(pca.transform_row(&[1.0]), Err(MathError::DimensionMismatch { .. })));
+    }
+}
@@ -0,0 +1,474 @@
+//! Counters and histograms for the core, rendered in the Prometheus text
+//! exposition format.
+//!
+//! A [`Metrics`] is shared between the core task that records into it and
+//! whatever renders it; every field is atomic, so recording never blocks. With
+//! the `runtime` feature, [`serve_metrics`] answers `GET /metrics` on a local
+//! port.
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::motion_core::{CoreError, MotionEntry, MotionOutput, MotionSpace};
+use crate::motion_input::{InteractionType, MotionInput};
+
+const SIMILARITY_BUCKETS: &[f64] = &[0.05, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0];
+const WEIGHT_BUCKETS: &[f64] = &[-0.25, -0.1, -0.05, 0.0, 0.05, 0.1, 0.2, 0.3, 0.5, 0.75, 1.0];
+const LATENCY_BUCKETS: &[f64] = &[
+    0.000_01, 0.000_025, 0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005, 0.01, 0.025,
+    0.05, 0.1,
+];
+
+/// Fixed-bucket histogram. Buckets are upper bounds; values above the last
+/// one only show up in `+Inf`.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<AtomicU64>,
+    /// f64 bits.
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    /// A histogram with the given increasing bucket upper bounds.
+    pub fn new(bounds: &'static [f64]) -> Self {
+        debug_assert!(bounds.windows(2).all(|w| w[0] < w[1]), "bucket bounds must increase");
+        Self {
+            bounds,
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    /// Counts `value` into its bucket.
+    pub fn observe(&self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|&bound| value <= bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f64::from_bits(bits) + value).to_bits())
+        });
+    }
+
+    /// Number of observed values.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Sum of the observed values.
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.sum.load(Ordering::Relaxed))
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        cumulative += self.counts[self.bounds.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}");
+        let _ = writeln!(out, "{name}_sum {}", self.sum());
+        let _ = writeln!(out, "{name}_count {cumulative}");
+    }
+}
+
+/// Everything the core records; see the module docs.
+#[derive(Debug)]
+pub struct Metrics {
+    /// Indexed like [`MotionInput::KINDS`].
+    inputs: [AtomicU64; 6],
+    /// Indexed like [`InteractionType::ALL`].
+    interactions_applied: [AtomicU64; 8],
+    /// Indexed like [`CoreError::KINDS`].
+    rejected: [AtomicU64; 8],
+    cluster_changes: AtomicU64,
+    users: AtomicI64,
+    posts: AtomicI64,
+    /// Kernel similarity of applied interactions.
+    pub similarity: Histogram,
+    /// Signed weight of applied interactions.
+    pub weight: Histogram,
+    /// Seconds the core spent on each input.
+    pub core_latency: Histogram,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            inputs: Default::default(),
+            interactions_applied: Default::default(),
+            rejected: Default::default(),
+            cluster_changes: AtomicU64::new(0),
+            users: AtomicI64::new(0),
+            posts: AtomicI64::new(0),
+            similarity: Histogram::new(SIMILARITY_BUCKETS),
+            weight: Histogram::new(WEIGHT_BUCKETS),
+            core_latency: Histogram::new(LATENCY_BUCKETS),
+        }
+    }
+}
+
+impl Metrics {
+    /// Zeroed metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the entries already in `space` (e.g. from a snapshot) to the
+    /// entry gauges.
+    pub fn track_space(&self, space: &MotionSpace) {
+        for entry in &space.entries {
+            self.add_entry(entry, 1);
+        }
+    }
+
+    /// Records one input the core handled, what it produced and how long it
+    /// took.
+    pub fn observe(&self, input: &MotionInput, outputs: &[MotionOutput], latency: Duration) {
+        self.observe_input(input);
+        for output in outputs {
+            self.observe_output(output);
+        }
+        self.core_latency.observe(latency.as_secs_f64());
+    }
+
+    /// Counts one input by kind.
+    pub fn observe_input(&self, input: &MotionInput) {
+        let idx = MotionInput::KINDS
+            .iter()
+            .position(|&k| k == input.kind())
+            .expect("every input kind is listed");
+        self.inputs[idx].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records what one output says about the space.
+    pub fn observe_output(&self, output: &MotionOutput) {
+        match output {
+            MotionOutput::Entered(entry) => self.add_entry(entry, 1),
+            MotionOutput::Removed(entry) => self.add_entry(entry, -1),
+            MotionOutput::Updated(_) => {}
+            MotionOutput::InteractionApplied(result) => {
+                let idx = InteractionType::ALL
+                    .iter()
+                    .position(|&k| k == result.kind)
+                    .expect("every interaction kind is listed");
+                self.interactions_applied[idx].fetch_add(1, Ordering::Relaxed);
+                self.similarity.observe(result.similarity as f64);
+                self.weight.observe(result.weight as f64);
+            }
+            MotionOutput::ClusterChanged(_) => {
+                self.cluster_changes.fetch_add(1, Ordering::Relaxed);
+            }
+            MotionOutput::Rejected { error, .. } => self.observe_rejected(error),
+        }
+    }
+
+    /// Counts one rejection by error kind.
+    pub fn observe_rejected(&self, error: &CoreError) {
+        let idx = CoreError::KINDS
+            .iter()
+            .position(|&k| k == error.kind())
+            .expect("every error kind is listed");
+        self.rejected[idx].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn add_entry(&self, entry: &MotionEntry, delta: i64) {
+        let gauge = match entry {
+            MotionEntry::User(_) => &self.users,
+            MotionEntry::Post(_) => &self.posts,
+        };
+        gauge.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    /// Prometheus text exposition of every metric.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        render_labeled(
+            &mut out,
+            "motion_inputs_total",
+            "Inputs handled by the core, by kind.",
+            "counter",
+            "kind",
+            MotionInput::KINDS.iter().copied().zip(&self.inputs),
+        );
+        render_labeled(
+            &mut out,
+            "motion_interactions_applied_total",
+            "Interactions applied, by interaction kind.",
+            "counter",
+            "kind",
+            InteractionType::ALL.iter().map(|k| k.name()).zip(&self.interactions_applied),
+        );
+        render_labeled(
+            &mut out,
+            "motion_rejected_total",
+            "Inputs the core rejected, by error.",
+            "counter",
+            "error",
+            CoreError::KINDS.iter().copied().zip(&self.rejected),
+        );
+        let _ = writeln!(out, "# HELP motion_cluster_changes_total Cluster membership changes.");
+        let _ = writeln!(out, "# TYPE motion_cluster_changes_total counter");
+        let _ = writeln!(out, "motion_cluster_changes_total {}", self.cluster_changes.load(Ordering::Relaxed));
+        let _ = writeln!(out, "# HELP motion_entries Entries in the space, by kind.");
+        let _ = writeln!(out, "# TYPE motion_entries gauge");
+        let _ = writeln!(out, "motion_entries{{kind=\"user\"}} {}", self.users.load(Ordering::Relaxed));
+        let _ = writeln!(out, "motion_entries{{kind=\"post\"}} {}", self.posts.load(Ordering::Relaxed));
+        self.similarity.render(
+            &mut out,
+            "motion_interaction_similarity",
+            "Kernel similarity of applied interactions.",
+        );
+        self.weight.render(
+            &mut out,
+            "motion_interaction_weight",
+            "Signed weight of applied interactions.",
+        );
+        self.core_latency.render(
+            &mut out,
+            "motion_core_latency_seconds",
+            "Time the core spends applying one input.",
+        );
+        out
+    }
+}
+
+fn render_labeled<'a>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    kind: &str,
+    label: &str,
+    values: impl Iterator<Item = (&'a str, &'a AtomicU64)>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (value, count) in values {
+        let _ = writeln!(out, "{name}{{{label}=\"{value}\"}} {}", count.load(Ordering::Relaxed));
+    }
+}
+
+/// Answers `GET /metrics` on `addr` with whatever `render` returns, until the
+/// listener fails. Anything else gets a 404.
+#[cfg(feature = "runtime")]
+pub async fn serve_metrics<F>(addr: &str, render: F) -> std::io::Result<()>
+where
+    F: Fn() -> String + Send + Sync + 'static,
+{
+    serve(tokio::net::TcpListener::bind(addr).await?, render).await
+}
+
+#[cfg(feature = "runtime")]
+async fn serve<F>(listener: tokio::net::TcpListener, render: F) -> std::io::Result<()>
+where
+    F: Fn() -> String + Send + Sync + 'static,
+{
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let render = Arc::new(render);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let render = render.clone();
+        tokio::spawn(async move {
+            let mut stream = BufReader::new(stream);
+            let mut request_line = String::new();
+            if stream.read_line(&mut request_line).await.is_err() {
+                return;
+            }
+            // Skip the headers; nothing in them matters here.
+            let mut header = String::new();
+            while stream.read_line(&mut header).await.is_ok_and(|n| n > 2) {
+                header.clear();
+            }
+            let path = request_line.split_whitespace().nth(1).unwrap_or("");
+            let (status, body) = if request_line.starts_with("GET ") && path == "/metrics" {
+                ("200 OK", render())
+            } else {
+                ("404 Not Found", "not found\n".to_string())
+            };
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.get_mut().write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+
+    use super::*;
+    use crate::motion_core::PreparedInput;
+    use crate::motion_input::{DeleteInput, Interaction, PostInput};
+
+    /// Checks `text` against the Prometheus text format: every family has
+    /// one HELP and one TYPE line before its samples, every sample value
+    /// parses, and histogram buckets are cumulative up to `+Inf == _count`.
+    /// Returns every sample as `name{labels} -> value`.
+    fn parse_exposition(text: &str) -> BTreeMap<String, f64> {
+        let mut help = HashSet::new();
+        let mut types = BTreeMap::new();
+        let mut samples = BTreeMap::new();
+        let mut last_bucket: Option<(String, f64)> = None;
+        assert!(text.ends_with('\n'));
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# HELP ") {
+                let (name, text) = rest.split_once(' ').expect("HELP has text");
+                assert!(!text.is_empty() && help.insert(name.to_string()), "{line}");
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = rest.split_once(' ').unwrap();
+                assert!(help.contains(name), "TYPE before HELP: {line}");
+                assert!(["counter", "gauge", "histogram"].contains(&kind), "{line}");
+                assert!(types.insert(name.to_string(), kind.to_string()).is_none(), "{line}");
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap_or_else(|| panic!("no value: {line}"));
+            let value: f64 = value.parse().unwrap_or_else(|_| panic!("bad value: {line}"));
+            let name = series.split('{').next().unwrap();
+            assert!(name.chars().all(|c| c.is_ascii_lowercase() || c == '_'), "{line}");
+            let labels = &series[name.len()..];
+            if !labels.is_empty() {
+                let inner = labels.strip_prefix('{').and_then(|l| l.strip_suffix('}')).expect(line);
+                let (key, quoted) = inner.split_once('=').expect(line);
+                assert!(!key.is_empty() && quoted.len() > 2, "{line}");
+                assert!(quoted.starts_with('"') && quoted.ends_with('"'), "{line}");
+            }
+            let is_histogram = |family: &&str| types.get(*family).is_some_and(|t| t == "histogram");
+            let family = ["_bucket", "_sum", "_count"]
+                .iter()
+                .find_map(|suffix| name.strip_suffix(suffix).filter(is_histogram))
+                .unwrap_or(name);
+            assert!(types.contains_key(family), "sample before TYPE: {line}");
+            if name.ends_with("_bucket") {
+                if let Some((previous, count)) = &last_bucket
+                    && previous == family
+                {
+                    assert!(value >= *count, "buckets not cumulative: {line}");
+                }
+                last_bucket = Some((family.to_string(), value));
+            } else if name.ends_with("_count") && family != name {
+                assert_eq!(samples[&format!("{family}_bucket{{le=\"+Inf\"}}")], value, "{line}");
+            }
+            assert!(samples.insert(series.to_string(), value).is_none(), "duplicate: {line}");
+        }
+        samples
+    }
+
+    #[test]
+    fn histograms_render_cumulative_buckets() {
+        static BOUNDS: [f64; 3] = [1.0, 2.0, 5.0];
+        let histogram = Histogram::new(&BOUNDS);
+        for value in [0.5, 1.0, 1.5, 7.0, 3.0] {
+            histogram.observe(value);
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.sum(), 13.0);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "test_values", "Values seen.");
+        assert_eq!(
+            out,
+            concat!(
+                "# HELP test_values Values seen.\n",
+                "# TYPE test_values histogram\n",
+                "test_values_bucket{le=\"1\"} 2\n",
+                "test_values_bucket{le=\"2\"} 3\n",
+                "test_values_bucket{le=\"5\"} 4\n",
+                "test_values_bucket{le=\"+Inf\"} 5\n",
+                "test_values_sum 13\n",
+                "test_values_count 5\n",
+            )
+        );
+        parse_exposition(&out);
+    }
+
+    #[test]
+    fn every_label_is_rendered_and_counted() {
+        let metrics = Metrics::new();
+        let mut space = MotionSpace::new(16);
+        let inputs = [
+            MotionInput::Post(PostInput::new("p1", "alice", "hello world")),
+            MotionInput::Interaction(Interaction::new(InteractionType::Like, "p1", "bob")),
+            MotionInput::Interaction(Interaction::new(InteractionType::Like, "missing", "bob")),
+            MotionInput::DeletePost(DeleteInput::new("p1")),
+        ];
+        for input in inputs {
+            space.process_observed(PreparedInput::from(input), Some(&metrics));
+        }
+
+        let samples = parse_exposition(&metrics.render());
+        let value = |series: &str| *samples.get(series).unwrap_or_else(|| panic!("missing {series}"));
+        for kind in MotionInput::KINDS {
+            assert!(samples.contains_key(&format!("motion_inputs_total{{kind=\"{kind}\"}}")), "{kind}");
+        }
+        for kind in InteractionType::ALL {
+            let series = format!("motion_interactions_applied_total{{kind=\"{}\"}}", kind.name());
+            assert!(samples.contains_key(&series), "{series}");
+        }
+        for kind in CoreError::KINDS {
+            assert!(samples.contains_key(&format!("motion_rejected_total{{error=\"{kind}\"}}")), "{kind}");
+        }
+        assert_eq!(value("motion_inputs_total{kind=\"post\"}"), 1.0);
+        assert_eq!(value("motion_inputs_total{kind=\"interaction\"}"), 2.0);
+        assert_eq!(value("motion_inputs_total{kind=\"delete_post\"}"), 1.0);
+        assert_eq!(value("motion_inputs_total{kind=\"user\"}"), 0.0);
+        assert_eq!(value("motion_interactions_applied_total{kind=\"like\"}"), 1.0);
+        assert_eq!(value("motion_interactions_applied_total{kind=\"post\"}"), 1.0);
+        assert_eq!(value("motion_rejected_total{error=\"post_not_found\"}"), 1.0);
+        assert_eq!(value("motion_entries{kind=\"user\"}"), 2.0);
+        assert_eq!(value("motion_entries{kind=\"post\"}"), 0.0);
+        assert_eq!(value("motion_interaction_similarity_count"), 2.0);
+        assert_eq!(value("motion_core_latency_seconds_count"), 4.0);
+        assert_eq!(value("motion_cluster_changes_total"), 0.0);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn metrics_are_served_over_http() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        async fn get(addr: std::net::SocketAddr, request: &str) -> String {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        }
+
+        let metrics = std::sync::Arc::new(Metrics::new());
+        metrics.observe_input(&MotionInput::Post(PostInput::new("p1", "alice", "hi")));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let rendered = metrics.clone();
+        let server = tokio::spawn(serve(listener, move || rendered.render()));
+
+        let response = get(addr, "GET /metrics HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n").await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut head = head.lines();
+        assert_eq!(head.next(), Some("HTTP/1.1 200 OK"));
+        let headers: Vec<&str> = head.collect();
+        assert!(headers.contains(&"Content-Type: text/plain; version=0.0.4"), "{headers:?}");
+        assert!(headers.contains(&format!("Content-Length: {}", body.len()).as_str()), "{headers:?}");
+        assert_eq!(body, metrics.render());
+        assert_eq!(parse_exposition(body)["motion_inputs_total{kind=\"post\"}"], 1.0);
+
+        for request in ["GET /other HTTP/1.1\r\n\r\n", "POST /metrics HTTP/1.1\r\n\r\n"] {
+            let response = get(addr, request).await;
+            assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{response}");
+        }
+        server.abort();
+    }
+}
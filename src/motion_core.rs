@@ -1,41 +1,127 @@
+//! The motion space itself: entries, the interaction dynamics and the input
+//! processing loop.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "runtime")]
 use tokio::sync::mpsc::{Sender, Receiver};
 use thiserror::Error;
+use tracing::{debug, debug_span};
 
-use crate::embedding::embed_post;
+use crate::cluster::{ClusterChange, ClusterConfig, Clusters};
+use crate::embedding::{Embedder, TokenIndex};
+use crate::graph::{Direction, Edge, InteractionGraph};
 use crate::math::{MathError, VecN};
+use crate::config::MotionSpaceBuilder;
 use crate::kernel::{apply_kernel2, Kernel};
-use crate::motion_input::{MotionInput, Interaction, InteractionType};
+use crate::metrics::Metrics;
+use crate::motion_input::{MotionInput, Interaction, InteractionTarget, InteractionType, PostInput};
 
 
-#[derive(Debug, Error)]
+/// Why an input could not be applied to the space.
+#[derive(Debug, Error, Clone, Serialize, Deserialize)]
 pub enum CoreError {
+    /// The input names a user the space does not know.
     #[error("user not found for id: {user_id}")]
-    UserNotFound { user_id: String },
+    UserNotFound {
+        /// The missing user.
+        user_id: String,
+    },
     
+    /// The input names a post the space does not know.
     #[error("post not found for id: {post_id}")]
-    PostNotFound { post_id: String },
+    PostNotFound {
+        /// The missing post.
+        post_id: String,
+    },
+
+    /// A post with the same id is already in the space.
+    #[error("a post with id {post_id} already exists")]
+    DuplicatePost {
+        /// The id already in use.
+        post_id: String,
+    },
 
+    /// The user has no coord yet, so there is nothing to move or compare.
     #[error("no coord loaded for user id: {user_id}")]
-    CoordNotLoaded { user_id: String },
+    CoordNotLoaded {
+        /// The unplaced user.
+        user_id: String,
+    },
 
+    /// A user engaged with themselves or their own post while
+    /// [`MotionSpace::exclude_self_engagement`] is on.
+    #[error("user {user_id} cannot engage with own content {target_id}")]
+    SelfEngagement {
+        /// The acting user.
+        user_id: String,
+        /// Their own post, or their own id.
+        target_id: String,
+    },
+
+    /// An interaction's alpha is NaN or infinite.
+    #[error("interaction alpha must be finite, not {alpha}")]
+    InvalidAlpha {
+        /// The rejected alpha.
+        alpha: f32,
+    },
+
+    /// A vector operation failed, e.g. on mismatched dimensions.
     #[error("math error: {0}")]
     Math(#[from] MathError), 
    
+    /// The output channel of the core loop closed.
     #[error("channel closed while sending motion entry")]
     ChannelError
 }
 
+impl CoreError {
+    /// Every [`kind`](CoreError::kind), in metrics order.
+    pub const KINDS: [&'static str; 8] = [
+        "user_not_found",
+        "post_not_found",
+        "duplicate_post",
+        "coord_not_loaded",
+        "self_engagement",
+        "invalid_alpha",
+        "math",
+        "channel",
+    ];
+
+    /// Variant name without its fields, e.g. `"post_not_found"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CoreError::UserNotFound { .. } => "user_not_found",
+            CoreError::PostNotFound { .. } => "post_not_found",
+            CoreError::DuplicatePost { .. } => "duplicate_post",
+            CoreError::CoordNotLoaded { .. } => "coord_not_loaded",
+            CoreError::SelfEngagement { .. } => "self_engagement",
+            CoreError::InvalidAlpha { .. } => "invalid_alpha",
+            CoreError::Math(_) => "math",
+            CoreError::ChannelError => "channel",
+        }
+    }
+}
+
 
 
+/// A user's position in the space. `coord` stays `None` until their first
+/// post or engagement; `motion` is a decaying measure of recent activity.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MotionUser {
+    /// User id.
     pub id: String,
+    /// Position in the space, unit length once set.
     pub coord: Option<VecN>,
+    /// Recent activity; grows with every interaction and decays.
     pub motion: f32,
 }
 
 impl MotionUser {
+    /// An unplaced user without motion. `_dim` is unused.
     pub fn new(id: impl Into<String>, _dim: usize) -> Self {
         let motion = 0.0;
         Self {
@@ -46,30 +132,76 @@ impl MotionUser {
     }
 }
 
+/// A post placed in the space by its text embedding.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MotionPost {
+    /// Post id.
     pub id: String,
+    /// The author's user id.
+    pub author_id: String,
+    /// Unix timestamp in milliseconds.
+    pub created_at: i64,
+    /// Current position; starts at `origin` and drifts toward the users who engage with it.
     pub coord: VecN,
+    /// The text embedding the post entered with.
+    pub origin: VecN,
+    /// Extra feature vectors; not used by the core itself.
     pub features: Vec<VecN>,
+    /// Reactions from the audience.
+    #[serde(default)]
+    pub engagement: Engagement,
+}
+
+/// How the audience has reacted to a post. The author's own activity is not
+/// counted.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Engagement {
+    /// Attracting interactions (likes, shares, replies, ...).
+    pub positive: u32,
+    /// Repelling interactions (dislikes, hides, ...).
+    pub negative: u32,
+}
+
+impl Engagement {
+    /// Takes back the interactions aggregated in `edge`.
+    fn retract(&mut self, edge: &Edge) {
+        self.positive = self.positive.saturating_sub(edge.count.saturating_sub(edge.repelled));
+        self.negative = self.negative.saturating_sub(edge.repelled);
+    }
 }
 
 impl MotionPost {
-    pub fn new(id: String, coord: VecN) -> Self {
+    /// A post created now at `coord`, which also becomes its origin.
+    pub fn new(id: String, author_id: String, coord: VecN) -> Self {
         Self {
             id,
+            author_id,
+            created_at: Utc::now().timestamp_millis(),
+            origin: coord.clone(),
             coord,
             features: Vec::new(),
+            engagement: Engagement::default(),
         }
     }
+
+    /// Sets the creation time, in unix millis.
+    pub fn with_created_at(mut self, created_at: i64) -> Self {
+        self.created_at = created_at;
+        self
+    }
 }
 
+/// A user or a post.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum MotionEntry {
+    /// A user.
     User(MotionUser),
+    /// A post.
     Post(MotionPost),
 }
 
 impl MotionEntry {
+    /// The user or post id.
     pub fn id(&self) -> &str {
         match self {
             MotionEntry::User(u) => &u.id,
@@ -78,49 +210,552 @@ impl MotionEntry {
     }
 }
 
+/// Outcome of one applied interaction. `weight` is signed: negative means the
+/// user was pushed away from the target.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct InteractionResult {
+    /// The kind of interaction.
+    pub kind: InteractionType,
+    /// The post for post kinds, the acting user for user kinds.
     pub src_id: String,
+    /// The engaging user for post kinds, the target user for user kinds.
     pub dst_id: String,
+    /// Signed step size the interaction was applied with.
     pub weight: f32,
+    /// Kernel similarity of the two sides before the interaction.
     pub similarity: f32,
 }
 
+impl InteractionResult {
+    /// Users whose coords the interaction moved.
+    pub fn moved_users(&self) -> Vec<&str> {
+        match self.kind.target() {
+            InteractionTarget::Post => vec![self.dst_id.as_str()],
+            InteractionTarget::User => vec![self.src_id.as_str(), self.dst_id.as_str()],
+        }
+    }
+}
+
+/// Events emitted by [`MotionSpace::process`] and [`MotionSpace::core_loop`].
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum MotionOutput {
+    /// A user or post was added.
     Entered(MotionEntry),
+    /// A post's text changed.
     Updated(MotionEntry),
-    InteractionApplied(InteractionResult) 
+    /// A user or post was deleted.
+    Removed(MotionEntry),
+    /// An interaction moved its participants.
+    InteractionApplied(InteractionResult),
+    /// A user's cluster assignment changed; only emitted when clustering is on.
+    ClusterChanged(ClusterChange),
+    /// The input could not be applied and is echoed back alongside the error.
+    Rejected {
+        /// Why the input was rejected.
+        error: CoreError,
+        /// The input as it was sent.
+        input: MotionInput,
+    },
+}
+
+/// A post matched by a similarity query.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScoredPost {
+    /// The post.
+    pub post_id: String,
+    /// Its author.
+    pub author_id: String,
+    /// Kernel similarity to the query coord.
+    pub similarity: f32,
+}
+
+/// A user matched by [`MotionSpace::recommend_users`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScoredUser {
+    /// The recommended user.
+    pub user_id: String,
+    /// Kernel similarity to the user recommendations are for.
+    pub similarity: f32,
+    /// The recommended user's motion.
+    pub motion: f32,
+    /// Similarity boosted by motion; the ranking key.
+    pub score: f32,
+}
+
+/// Summary counts over a space.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SpaceStats {
+    /// Dimension of every coord.
+    pub dim: usize,
+    /// Users, placed or not.
+    pub users: usize,
+    /// Users that have not been placed yet (`coord` is `None`).
+    pub unplaced_users: usize,
+    /// Posts.
+    pub posts: usize,
+    /// Distinct post authors.
+    pub authors: usize,
+    /// Mean motion over all users.
+    pub mean_motion: f32,
+    /// Highest motion of any user.
+    pub max_motion: f32,
 }
 
+/// Global tuning for the motion update, shared by every interaction kind.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Dynamics {
+    /// Fraction of `motion` lost on every interaction.
+    pub decay: f32,
+    /// Upper bound on the size of a single repulsive step. Repulsion is
+    /// strongest when the two coords are close, so without a cap a dislike
+    /// of a nearby post can fling the user past the antipode and back.
+    pub max_repulsion: f32,
+    /// How far a post moves toward an engaging user, as a fraction of the
+    /// interaction weight. Zero pins posts to their text embedding.
+    pub post_drift: f32,
+}
+
+impl Default for Dynamics {
+    fn default() -> Self {
+        Self {
+            decay: 0.02,
+            max_repulsion: 0.25,
+            post_drift: 0.05,
+        }
+    }
+}
+
+impl Dynamics {
+    /// Signed interaction weight: positive pulls toward the target, negative
+    /// pushes away. The sign comes from the kind's polarity and the sign of
+    /// `alpha`, so a negative alpha turns any kind into a repulsion.
+    pub fn weight(&self, alpha: f32, similarity: f32, polarity: f32) -> f32 {
+        let magnitude = 1.0 - (-alpha.abs() * similarity).exp();
+        if polarity * alpha < 0.0 {
+            -magnitude.min(self.max_repulsion)
+        } else {
+            magnitude
+        }
+    }
+}
+
+// Below this squared distance two coords are treated as coincident and
+// repulsion has no direction to push in.
+const MIN_REPULSION_DIST_SQ: f32 = 1e-8;
+
+/// Moves `from` by `step` along the line to `to` and renormalizes. A negative
+/// step moves away from `to`. Returns `None` when the result would be
+/// degenerate, in which case the caller keeps the old coord.
+fn step_coord(from: &[f32], to: &[f32], step: f32) -> Result<Option<VecN>, CoreError> {
+    if step < 0.0 {
+        let dist_sq: f32 = from.iter().zip(to).map(|(a, b)| (a - b) * (a - b)).sum();
+        if dist_sq < MIN_REPULSION_DIST_SQ {
+            return Ok(None);
+        }
+    }
+
+    let data = apply_kernel2(from, to, |f, t| f * (1.0 - step) + t * step)?;
+    let mut coord = VecN::new(data);
+    if coord.normalize().is_err() || coord.data.iter().any(|x| !x.is_finite()) {
+        return Ok(None);
+    }
+
+    debug_assert!((coord.data.iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-3);
+    Ok(Some(coord))
+}
+
+/// One span per input, carrying the ids it refers to.
+pub(crate) fn input_span(input: &MotionInput) -> tracing::Span {
+    match input {
+        MotionInput::User(user) => debug_span!("input", kind = "user", id = %user.id),
+        MotionInput::Post(post) => debug_span!("input", kind = "post", id = %post.id, user_id = %post.user_id),
+        MotionInput::Interaction(i) => debug_span!(
+            "input",
+            kind = "interaction",
+            interaction = i.interaction_type.name(),
+            src_id = %i.src_id,
+            dst_id = %i.dst_id,
+        ),
+        MotionInput::DeleteUser(user) => debug_span!("input", kind = "delete_user", id = %user.id),
+        MotionInput::DeletePost(post) => debug_span!("input", kind = "delete_post", id = %post.id),
+        MotionInput::EditPost(edit) => debug_span!("input", kind = "edit_post", id = %edit.id),
+    }
+}
+
+/// An input on its way to the core. Posts and edits may carry their text
+/// embedding, computed off the core task; when absent the core embeds inline.
+#[derive(Debug, Clone)]
+pub struct PreparedInput {
+    /// The input itself.
+    pub input: MotionInput,
+    /// Embedding of the input's text, if already computed.
+    pub embedding: Option<VecN>,
+}
+
+impl From<MotionInput> for PreparedInput {
+    fn from(input: MotionInput) -> Self {
+        Self { input, embedding: None }
+    }
+}
+
+/// All users and posts, plus the kernel and dynamics that govern how they move.
+/// Serializes to a self-contained snapshot.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MotionSpace {
+    /// Dimension of every coord.
     pub dim: usize,
+    /// Users and posts, in the order they entered.
     pub entries: Vec<MotionEntry>,
+    /// Similarity kernel interactions are weighted by.
     pub kernel: Kernel,
+    /// Embeds post texts.
+    #[serde(default)]
+    pub embedder: Embedder,
+    /// How interactions move coords.
+    #[serde(default)]
+    pub dynamics: Dynamics,
+    /// Reject interactions where a user engages with their own post or with themselves.
+    #[serde(default)]
+    pub exclude_self_engagement: bool,
+    /// Author id -> ids of their posts, in entry order. `None` disables the
+    /// index and author lookups scan `entries` instead.
+    #[serde(default)]
+    pub user_posts: Option<HashMap<String, Vec<String>>>,
+    /// Embedding bucket -> tokens of the post texts seen so far, used to
+    /// label topics. `None` disables the index.
+    #[serde(default)]
+    pub token_index: Option<TokenIndex>,
+    /// Every applied interaction, aggregated per edge.
+    #[serde(default)]
+    pub graph: InteractionGraph,
+    /// Interest communities over user coords. `None` disables clustering.
+    #[serde(default)]
+    pub clusters: Option<Clusters>,
 }
 
 impl MotionSpace {
+    /// An empty space with the default kernel, dynamics and indexes and no
+    /// clustering. [`MotionSpace::builder`] configures every part.
     pub fn new(dim: usize) -> Self {
         let kernel = Kernel::RBF { gamma: 2.0 };
         Self {
             dim,
             entries: Vec::new(),
             kernel,
+            embedder: Embedder { dim, ..Embedder::default() },
+            dynamics: Dynamics::default(),
+            exclude_self_engagement: false,
+            user_posts: Some(HashMap::new()),
+            token_index: Some(TokenIndex::new(dim)),
+            graph: InteractionGraph::new(),
+            clusters: None,
         }
     }
 
+    /// Starts a [`MotionSpaceBuilder`].
+    pub fn builder() -> MotionSpaceBuilder {
+        MotionSpaceBuilder::new()
+    }
+
+    /// Adds `entry` as is, keeping the author index up to date. No events are
+    /// emitted and no checks are made; meant for loading.
     pub fn enter(&mut self, entry: MotionEntry) {
+        if let (MotionEntry::Post(p), Some(index)) = (&entry, self.user_posts.as_mut()) {
+            index
+                .entry(p.author_id.clone())
+                .or_default()
+                .push(p.id.clone());
+        }
         self.entries.push(entry);
     }
+
+    /// The user with id `user_id`.
+    pub fn user(&self, user_id: &str) -> Option<&MotionUser> {
+        self.entries.iter().find_map(|e| match e {
+            MotionEntry::User(u) if u.id == user_id => Some(u),
+            _ => None,
+        })
+    }
+
+    /// The post with id `post_id`.
+    pub fn post(&self, post_id: &str) -> Option<&MotionPost> {
+        self.entries.iter().find_map(|e| match e {
+            MotionEntry::Post(p) if p.id == post_id => Some(p),
+            _ => None,
+        })
+    }
+
+    /// The `k` posts most similar to `coord` under the space's kernel, best
+    /// first. Posts by `exclude_author` are skipped.
+    pub fn nearest_posts(
+        &self,
+        coord: &[f32],
+        k: usize,
+        exclude_author: Option<&str>,
+    ) -> Result<Vec<ScoredPost>, CoreError> {
+        let mut scored = Vec::new();
+        for entry in &self.entries {
+            let MotionEntry::Post(p) = entry else { continue };
+            if exclude_author == Some(p.author_id.as_str()) {
+                continue;
+            }
+            scored.push(ScoredPost {
+                post_id: p.id.clone(),
+                author_id: p.author_id.clone(),
+                similarity: self.kernel.apply(coord, &p.coord.data)?,
+            });
+        }
+        scored.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        scored.truncate(k);
+        Ok(scored)
+    }
+
+    /// Nearest posts to a user's coord, excluding their own.
+    pub fn recommend_posts(&self, user_id: &str, k: usize) -> Result<Vec<ScoredPost>, CoreError> {
+        let user = self
+            .user(user_id)
+            .ok_or_else(|| CoreError::UserNotFound { user_id: user_id.to_string() })?;
+        let coord = user.coord.as_ref().ok_or_else(|| CoreError::CoordNotLoaded {
+            user_id: user_id.to_string(),
+        })?;
+        self.nearest_posts(&coord.data, k, Some(user_id))
+    }
+
+    /// The `k` placed users most similar to `coord` under the space's kernel,
+    /// boosted by how active they are, best first. Users in `exclude` are
+    /// skipped.
+    pub fn nearest_users(
+        &self,
+        coord: &[f32],
+        k: usize,
+        exclude: &HashSet<String>,
+    ) -> Result<Vec<ScoredUser>, CoreError> {
+        let mut scored = Vec::new();
+        for entry in &self.entries {
+            let MotionEntry::User(other) = entry else { continue };
+            if exclude.contains(&other.id) {
+                continue;
+            }
+            let Some(other_coord) = &other.coord else { continue };
+            let similarity = self.kernel.apply(coord, &other_coord.data)?;
+            scored.push(ScoredUser {
+                user_id: other.id.clone(),
+                similarity,
+                motion: other.motion,
+                // Log-damped so a very active user cannot outrank a close match
+                // on activity alone.
+                score: similarity * (1.0 + other.motion.max(0.0).ln_1p()),
+            });
+        }
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(k);
+        Ok(scored)
+    }
+
+    /// Who-to-follow: other placed users ranked by kernel similarity to
+    /// `user_id`, boosted by how active they are. Users `user_id` has already
+    /// interacted with directly are left out.
+    pub fn recommend_users(&self, user_id: &str, k: usize) -> Result<Vec<ScoredUser>, CoreError> {
+        let user = self
+            .user(user_id)
+            .ok_or_else(|| CoreError::UserNotFound { user_id: user_id.to_string() })?;
+        let coord = user.coord.as_ref().ok_or_else(|| CoreError::CoordNotLoaded {
+            user_id: user_id.to_string(),
+        })?;
+        self.nearest_users(&coord.data, k, &self.followed(user_id))
+    }
+
+    /// `user_id` and every user they have an edge to: who
+    /// [`recommend_users`](Self::recommend_users) leaves out.
+    pub(crate) fn followed(&self, user_id: &str) -> HashSet<String> {
+        let mut followed: HashSet<String> = self
+            .graph
+            .user_neighbors(user_id, Direction::Out)
+            .into_iter()
+            .map(|(id, _)| id.to_string())
+            .collect();
+        followed.insert(user_id.to_string());
+        followed
+    }
+
+    /// Summary counts over the space.
+    pub fn stats(&self) -> SpaceStats {
+        let mut stats = SpaceStats {
+            dim: self.dim,
+            ..SpaceStats::default()
+        };
+        let mut authors = HashSet::new();
+        let mut motion_sum = 0.0;
+        for entry in &self.entries {
+            match entry {
+                MotionEntry::User(u) => {
+                    stats.users += 1;
+                    if u.coord.is_none() {
+                        stats.unplaced_users += 1;
+                    }
+                    motion_sum += u.motion;
+                    stats.max_motion = stats.max_motion.max(u.motion);
+                }
+                MotionEntry::Post(p) => {
+                    stats.posts += 1;
+                    authors.insert(p.author_id.as_str());
+                }
+            }
+        }
+        stats.authors = authors.len();
+        if stats.users > 0 {
+            stats.mean_motion = motion_sum / stats.users as f32;
+        }
+        stats
+    }
+
+    /// Posts written by `user_id`, oldest first.
+    pub fn posts_by_user(&self, user_id: &str) -> Vec<&MotionPost> {
+        match &self.user_posts {
+            Some(index) => index
+                .get(user_id)
+                .map(|ids| ids.iter().filter_map(|id| self.post(id)).collect())
+                .unwrap_or_default(),
+            None => self
+                .entries
+                .iter()
+                .filter_map(|e| match e {
+                    MotionEntry::Post(p) if p.author_id == user_id => Some(p),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Removes a post and drops it from its author's index.
+    pub fn remove_post(&mut self, post_id: &str) -> Result<MotionPost, CoreError> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|e| matches!(e, MotionEntry::Post(p) if p.id == post_id))
+            .ok_or_else(|| CoreError::PostNotFound { post_id: post_id.to_string() })?;
+        let MotionEntry::Post(post) = self.entries.remove(idx) else {
+            unreachable!("post idx must point to a post");
+        };
+        if let Some(index) = self.user_posts.as_mut()
+            && let Some(ids) = index.get_mut(&post.author_id)
+        {
+            ids.retain(|id| id != post_id);
+            if ids.is_empty() {
+                index.remove(&post.author_id);
+            }
+        }
+        if let Some(index) = self.token_index.as_mut() {
+            index.forget(post_id);
+        }
+        self.graph.remove_post(post_id);
+        Ok(post)
+    }
+
+    /// Erases a user and every post they authored. Returns the removed
+    /// entries, user first. Posts they engaged with go back to their origin:
+    /// their pull cannot be told apart from other engagers', so all drift on
+    /// those posts is dropped. Past pulls on other users' coords are not undone.
+    pub fn remove_user(&mut self, user_id: &str) -> Result<Vec<MotionEntry>, CoreError> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|e| matches!(e, MotionEntry::User(u) if u.id == user_id))
+            .ok_or_else(|| CoreError::UserNotFound { user_id: user_id.to_string() })?;
+        let mut removed = vec![self.entries.remove(idx)];
+
+        if let Some(index) = self.user_posts.as_mut() {
+            index.remove(user_id);
+        }
+        let engaged = self.engaged(user_id);
+        self.retract_engagement(user_id, &engaged);
+        self.entries.retain(|e| match e {
+            MotionEntry::Post(p) if p.author_id == user_id => {
+                removed.push(e.clone());
+                false
+            }
+            _ => true,
+        });
+        self.graph.remove_user(user_id);
+        for entry in &removed[1..] {
+            self.graph.remove_post(entry.id());
+            if let Some(index) = self.token_index.as_mut() {
+                index.forget(entry.id());
+            }
+        }
+        Ok(removed)
+    }
+
+    /// The post edges of `user_id`, owned.
+    pub(crate) fn engaged(&self, user_id: &str) -> Vec<(String, Edge)> {
+        self.graph
+            .posts_engaged(user_id)
+            .into_iter()
+            .map(|(post_id, edge)| (post_id.to_string(), *edge))
+            .collect()
+    }
+
+    /// Takes the engagement `user_id` added back from the posts in `engaged`
+    /// that are held here, and resets those posts to their origin. Their own
+    /// posts never counted it.
+    pub(crate) fn retract_engagement(&mut self, user_id: &str, engaged: &[(String, Edge)]) {
+        let engaged: HashMap<&str, &Edge> = engaged.iter().map(|(post_id, edge)| (post_id.as_str(), edge)).collect();
+        for entry in &mut self.entries {
+            if let MotionEntry::Post(p) = entry
+                && p.author_id != user_id
+                && let Some(edge) = engaged.get(p.id.as_str())
+            {
+                p.engagement.retract(edge);
+                p.coord = p.origin.clone();
+            }
+        }
+    }
+
+    /// Replaces a post's text embedding. Drift accumulated from engagement is
+    /// discarded along with the old text.
+    pub fn edit_post(&mut self, post_id: &str, text: &str) -> Result<MotionPost, CoreError> {
+        let embedding = self.embedder.embed(text);
+        self.replace_post_embedding(post_id, embedding)
+    }
+
+    /// [`MotionSpace::edit_post`] with the new text already embedded.
+    pub fn replace_post_embedding(&mut self, post_id: &str, embedding: VecN) -> Result<MotionPost, CoreError> {
+        let post = self
+            .entries
+            .iter_mut()
+            .find_map(|e| match e {
+                MotionEntry::Post(p) if p.id == post_id => Some(p),
+                _ => None,
+            })
+            .ok_or_else(|| CoreError::PostNotFound { post_id: post_id.to_string() })?;
+        post.origin = embedding.clone();
+        post.coord = embedding;
+        Ok(post.clone())
+    }
+
+    /// Whether `user_id` wrote `post_id`.
+    pub fn is_author(&self, user_id: &str, post_id: &str) -> bool {
+        match &self.user_posts {
+            Some(index) => index
+                .get(user_id)
+                .is_some_and(|ids| ids.iter().any(|id| id == post_id)),
+            None => self.post(post_id).is_some_and(|p| p.author_id == user_id),
+        }
+    }
    
 
+    /// Moves `actor_id` and `target_id` toward each other, or apart for a
+    /// repelling kind. Both users need a coord. Not recorded in the graph; use
+    /// [`MotionSpace::apply_interaction`] for that.
     pub fn apply_user_to_user(
         &mut self,
         actor_id: &str,
         target_id: &str,
+        kind: InteractionType,
         alpha: f32,
     ) -> Result<InteractionResult, CoreError> {
+        let kind_dynamics = kind.dynamics();
         let actor_idx = self
             .entries
             .iter()
@@ -157,36 +792,44 @@ impl MotionSpace {
         };
         
         let similarity = self.kernel.apply(&actor_data, &target_data)?;
-        let weight = 1.0 - (-alpha * similarity).exp();
+        let weight = self.dynamics.weight(alpha, similarity, kind_dynamics.polarity);
         
         let step = 0.5 * weight;
         
-        let new_actor_data = apply_kernel2(&actor_data, &target_data, |a, t| a * (1.0 - step) + t * step)?;
-        let new_target_data = apply_kernel2(&target_data, &actor_data, |t, a| t * (1.0 - step) + a * step)?;
+        let new_actor_coord = step_coord(&actor_data, &target_data, step)?;
+        let new_target_coord = step_coord(&target_data, &actor_data, step)?;
 
-        let mut new_actor_coord = VecN::new(new_actor_data);
-        let mut new_target_coord = VecN::new(new_target_data);
-        let _ = new_actor_coord.normalize();
-        let _ = new_target_coord.normalize();
-
-        let decay = 0.02;
-        let gain_target = 1.0;
-        let gain_actor = 0.5;
+        let decay = self.dynamics.decay;
+        let gain_target = kind_dynamics.motion_gain;
+        let gain_actor = 0.5 * kind_dynamics.motion_gain;
         
-        let new_target_motion = (1.0 - decay) * target_motion + gain_target * weight;
-        let new_actor_motion = (1.0 - decay) * actor_motion + gain_actor * weight;
+        let new_target_motion = (1.0 - decay) * target_motion + gain_target * weight.abs();
+        let new_actor_motion = (1.0 - decay) * actor_motion + gain_actor * weight.abs();
         
         if let MotionEntry::User(u) = &mut self.entries[target_idx] {
-            u.coord = Some(new_target_coord);
+            if let Some(coord) = new_target_coord {
+                u.coord = Some(coord);
+            }
             u.motion = new_target_motion;
         }
         if let MotionEntry::User(u) = &mut self.entries[actor_idx] {
-            u.coord = Some(new_actor_coord);
+            if let Some(coord) = new_actor_coord {
+                u.coord = Some(coord);
+            }
             u.motion = new_actor_motion;
         }
         
-        println!("sim={:.4} weight={:.4} actor motion={:.4} target motion={:.4}", similarity, weight, new_actor_motion, new_target_motion);
+        debug!(
+            actor_id,
+            target_id,
+            similarity,
+            weight,
+            actor_motion = new_actor_motion,
+            target_motion = new_target_motion,
+            "user moved toward user"
+        );
         Ok(InteractionResult {
+            kind,
             src_id: actor_id.to_string(),
             dst_id: target_id.to_string(),
             weight,
@@ -194,34 +837,44 @@ impl MotionSpace {
         })
     }
 
+    /// Moves `user_id` toward `post_id`, or away for a repelling kind, and lets
+    /// the post drift toward an engaging user from the audience. An unknown
+    /// user drawn to the post is created at it. Not recorded in the graph; use
+    /// [`MotionSpace::apply_interaction`] for that.
     pub fn apply_post_to_user(
         &mut self,
         user_id: &str,
         post_id: &str,
+        kind: InteractionType,
         alpha: f32,
     ) -> Result<InteractionResult, CoreError> {
+        let kind_dynamics = kind.dynamics();
         let post_idx = self
             .entries
             .iter()
             .position(|e| matches!(e, MotionEntry::Post(p) if p.id == post_id))
             .ok_or_else(|| CoreError::PostNotFound { post_id: post_id.to_string() })?;
 
-        let post_coord = match &self.entries[post_idx] {
-            MotionEntry::Post(p) => p.coord.clone(),
+        let (post_coord, is_author) = match &self.entries[post_idx] {
+            MotionEntry::Post(p) => (p.coord.clone(), p.author_id == user_id),
             _ => unreachable!("post idx must point to a post"),
         };
-        let user_idx = match self
+        let user_idx = self
             .entries
             .iter()
-            .position(|e| matches!(e, MotionEntry::User(u) if u.id == user_id))
-        {
-            Some(idx) => idx,
-            None => {
-                let motion_user = MotionUser::new(user_id, self.dim);
-                self.entries.push(MotionEntry::User(motion_user));
-                self.entries.len() - 1
-            }
-        };
+            .position(|e| matches!(e, MotionEntry::User(u) if u.id == user_id));
+        // A user without a coord is seeded at the first post they are drawn
+        // to; there is nothing to push away from until then. Checked before
+        // an unknown user is created so a rejection leaves no trace.
+        let repulsive = kind_dynamics.polarity * alpha < 0.0;
+        let placed = user_idx.is_some_and(|idx| matches!(&self.entries[idx], MotionEntry::User(u) if u.coord.is_some()));
+        if repulsive && !placed {
+            return Err(CoreError::CoordNotLoaded { user_id: user_id.to_string() });
+        }
+        let user_idx = user_idx.unwrap_or_else(|| {
+            self.entries.push(MotionEntry::User(MotionUser::new(user_id, self.dim)));
+            self.entries.len() - 1
+        });
         let user_coord = match &mut self.entries[user_idx] {
             MotionEntry::User(u) => u.coord.get_or_insert_with(|| post_coord.clone()),
             _ => unreachable!("user idx must point to a user"),
@@ -230,29 +883,41 @@ impl MotionSpace {
         let post_data = post_coord.data.clone();
 
         let similarity = self.kernel.apply(&user_data, &post_data)?;
-        let weight = 1.0 - (-alpha * similarity).exp();
-         
-        
-        let new_data = apply_kernel2(&user_data, &post_data, |u, p| {
-            u * (1.0 - weight) + p * weight
-        })?;
+        let weight = self.dynamics.weight(alpha, similarity, kind_dynamics.polarity);
 
-        let mut new_coord = VecN::new(new_data);
-        let _ = new_coord.normalize();
-       
-        let decay = 0.02;
-        let gain = 1.0;
+        let new_coord = step_coord(&user_data, &post_data, weight)?;
+        // Only positive engagement from the audience pulls the post; rejection
+        // and the author's own activity leave it in place.
+        let new_post_coord = if weight > 0.0 && self.dynamics.post_drift > 0.0 && !is_author {
+            step_coord(&post_data, &user_data, self.dynamics.post_drift * weight)?
+        } else {
+            None
+        };
+
+        let decay = self.dynamics.decay;
+        let gain = kind_dynamics.motion_gain;
 
-        
         if let MotionEntry::User(u) = &mut self.entries[user_idx] {
-            let new_motion = (1.0 - decay) * u.motion + gain * weight;
+            let new_motion = (1.0 - decay) * u.motion + gain * weight.abs();
 
-            u.coord = Some(new_coord);
+            if let Some(coord) = new_coord {
+                u.coord = Some(coord);
+            }
             u.motion = new_motion;
-            println!("sim={:.4} weight={:.4} motion={:.4}", similarity, weight, u.motion);
+            debug!(user_id, post_id, similarity, weight, motion = u.motion, "user moved toward post");
+        }
+        if let MotionEntry::Post(p) = &mut self.entries[post_idx] {
+            if let Some(coord) = new_post_coord {
+                p.coord = coord;
+            }
+            if !is_author {
+                let counter = if repulsive { &mut p.engagement.negative } else { &mut p.engagement.positive };
+                *counter = counter.saturating_add(1);
+            }
         }
 
         Ok(InteractionResult {
+            kind,
             src_id: post_id.to_string(),
             dst_id: user_id.to_string(),
             weight,
@@ -260,71 +925,557 @@ impl MotionSpace {
         })
     }
 
+    /// Applies `interaction` and records it in the graph. A NaN or infinite
+    /// alpha is rejected before anything moves.
     pub fn apply_interaction(&mut self, interaction: Interaction) -> Result<InteractionResult, CoreError> {
-        match interaction.interaction_type {
-            InteractionType::PostToUser => {
-                self.apply_post_to_user(&interaction.dst_id, &interaction.src_id, interaction.alpha)
+        let kind = interaction.interaction_type;
+        let alpha = interaction.alpha();
+        if !alpha.is_finite() {
+            return Err(CoreError::InvalidAlpha { alpha });
+        }
+        if self.exclude_self_engagement {
+            let (user_id, target_id, own) = match kind.target() {
+                InteractionTarget::Post => (
+                    &interaction.dst_id,
+                    &interaction.src_id,
+                    self.is_author(&interaction.dst_id, &interaction.src_id),
+                ),
+                InteractionTarget::User => (
+                    &interaction.src_id,
+                    &interaction.dst_id,
+                    interaction.src_id == interaction.dst_id,
+                ),
+            };
+            if own {
+                return Err(CoreError::SelfEngagement {
+                    user_id: user_id.clone(),
+                    target_id: target_id.clone(),
+                });
+            }
+        }
+        let result = match kind.target() {
+            InteractionTarget::Post => {
+                self.apply_post_to_user(&interaction.dst_id, &interaction.src_id, kind, alpha)
             },
-            InteractionType::UserToUser => {
-                self.apply_user_to_user(&interaction.src_id, &interaction.dst_id, interaction.alpha)
+            InteractionTarget::User => {
+                self.apply_user_to_user(&interaction.src_id, &interaction.dst_id, kind, alpha)
             },
+        }?;
+        let at = interaction.at.unwrap_or_else(|| Utc::now().timestamp_millis());
+        self.graph.record(&result, kind.dynamics().polarity * alpha < 0.0, at);
+        Ok(result)
+    }
+
+    /// [`MotionSpace::apply_interaction`] as events: an engaging user the
+    /// space did not know yet is reported as entered before the interaction.
+    pub(crate) fn interaction_outputs(&mut self, interaction: Interaction) -> Result<Vec<MotionOutput>, CoreError> {
+        let new_user = (interaction.interaction_type.target() == InteractionTarget::Post
+            && self.user(&interaction.dst_id).is_none())
+        .then(|| interaction.dst_id.clone());
+        let res = self.apply_interaction(interaction)?;
+        let mut outputs = Vec::with_capacity(2);
+        if let Some(user) = new_user.and_then(|id| self.user(&id)) {
+            outputs.push(MotionOutput::Entered(MotionEntry::User(user.clone())));
         }
+        outputs.push(MotionOutput::InteractionApplied(res));
+        Ok(outputs)
     }
 
-    pub async fn core_loop(&mut self, mut rx: Receiver<MotionInput>, tx: Sender<MotionOutput>) -> Result<(), CoreError> {
-        while let Some(input) = rx.recv().await {
-            match input {
-                MotionInput::Post(post) => {
-                    let embedding: VecN = embed_post(&post.text);
-                    let motion_post = MotionPost::new(
-                        post.id.clone(),
-                        embedding,
-                    );
-
-                    let entry = MotionEntry::Post(motion_post);
-                    self.enter(entry.clone());
-                    tx.send(MotionOutput::Entered(entry))
-                        .await
-                        .map_err(|_| CoreError::ChannelError)?;
-                   
-                    if self.entries.iter().all(|e| !matches!(e, MotionEntry::User(u) if u.id == post.user_id)) {
-                        let motion_user = MotionUser::new(&post.user_id, self.dim);
-                        let user_entry = MotionEntry::User(motion_user);
-                        self.enter(user_entry.clone());
-                        tx.send(MotionOutput::Entered(user_entry))
-                            .await
-                            .map_err(|_| CoreError::ChannelError)?;
-                    }
+    /// Enters a post whose text has already been embedded, creating its
+    /// author if needed and pulling the author toward it.
+    pub fn enter_post(&mut self, post: &PostInput, embedding: VecN) -> Result<Vec<MotionOutput>, CoreError> {
+        if self.post(&post.id).is_some() {
+            return Err(CoreError::DuplicatePost { post_id: post.id.clone() });
+        }
+        let mut outputs = Vec::new();
+        let mut motion_post = MotionPost::new(
+            post.id.clone(),
+            post.user_id.clone(),
+            embedding,
+        );
+        if let Some(created_at) = post.created_at {
+            motion_post = motion_post.with_created_at(created_at);
+        }
 
-                    let interaction = Interaction {
-                        interaction_type: InteractionType::PostToUser, 
-                        src_id: post.id.clone(),
-                        dst_id: post.user_id.clone(),
-                        alpha: 0.5,
-                    };
-                    let res = self.apply_interaction(interaction)?;
-                    tx.send(MotionOutput::InteractionApplied(res))
-                        .await
-                        .map_err(|_| CoreError::ChannelError)?;
-                }
-                MotionInput::User(user) => {
-                    let motion_user = MotionUser::new(&user.id, self.dim);
+        let entry = MotionEntry::Post(motion_post);
+        self.enter(entry.clone());
+        outputs.push(MotionOutput::Entered(entry));
+        if let Some(index) = self.token_index.as_mut() {
+            index.observe(&post.id, &post.text);
+        }
+       
+        if self.entries.iter().all(|e| !matches!(e, MotionEntry::User(u) if u.id == post.user_id)) {
+            let motion_user = MotionUser::new(&post.user_id, self.dim);
+            let user_entry = MotionEntry::User(motion_user);
+            self.enter(user_entry.clone());
+            outputs.push(MotionOutput::Entered(user_entry));
+        }
+
+        // Authorship bypasses `apply_interaction` so it is never
+        // rejected as self-engagement.
+        let res = self.apply_post_to_user(
+            &post.user_id,
+            &post.id,
+            InteractionType::PostToUser,
+            InteractionType::PostToUser.dynamics().alpha,
+        )?;
+        outputs.push(MotionOutput::InteractionApplied(res));
+        Ok(outputs)
+    }
 
-                    let entry = MotionEntry::User(motion_user);
+    /// Applies one input and returns the events it produced. On error the
+    /// space may hold partial effects of the input (e.g. a post entered but
+    /// not yet linked to its author).
+    pub fn process(&mut self, input: MotionInput) -> Result<Vec<MotionOutput>, CoreError> {
+        self.process_prepared(PreparedInput::from(input))
+    }
 
-                    self.enter(entry.clone());
-                    tx.send(MotionOutput::Entered(entry))
-                        .await
-                        .map_err(|_| CoreError::ChannelError)?;
+    /// Like [`MotionSpace::process`], but uses the embedding carried by the
+    /// input instead of embedding post text inline.
+    pub fn process_prepared(&mut self, prepared: PreparedInput) -> Result<Vec<MotionOutput>, CoreError> {
+        let span = input_span(&prepared.input);
+        let _entered = span.enter();
+        let result = self.apply_prepared(prepared);
+        if let Err(error) = &result {
+            debug!(%error, "input rejected");
+        }
+        result
+    }
+
+    fn apply_prepared(&mut self, prepared: PreparedInput) -> Result<Vec<MotionOutput>, CoreError> {
+        let PreparedInput { input, embedding } = prepared;
+        let mut outputs = Vec::new();
+        match input {
+            MotionInput::Post(post) => {
+                let embedding: VecN = embedding.unwrap_or_else(|| self.embedder.embed(&post.text));
+                outputs = self.enter_post(&post, embedding)?;
+            }
+            // Known users are left as they are, so any number of readers
+            // can announce the same user.
+            MotionInput::User(user) if self.user(&user.id).is_some() => {}
+            MotionInput::User(user) => {
+                let motion_user = MotionUser::new(&user.id, self.dim);
+
+                let entry = MotionEntry::User(motion_user);
+
+                self.enter(entry.clone());
+                outputs.push(MotionOutput::Entered(entry));
+            }
+            MotionInput::Interaction(interaction) => {
+                outputs = self.interaction_outputs(interaction)?;
+            }
+            MotionInput::DeleteUser(user) => {
+                outputs.extend(self.remove_user(&user.id)?.into_iter().map(MotionOutput::Removed));
+            }
+            MotionInput::DeletePost(post) => {
+                let post = self.remove_post(&post.id)?;
+                outputs.push(MotionOutput::Removed(MotionEntry::Post(post)));
+            }
+            MotionInput::EditPost(edit) => {
+                let embedding = embedding.unwrap_or_else(|| self.embedder.embed(&edit.text));
+                let post = self.replace_post_embedding(&edit.id, embedding)?;
+                // Replaces the old text's tokens.
+                if let Some(index) = self.token_index.as_mut() {
+                    index.observe(&edit.id, &edit.text);
+                }
+                outputs.push(MotionOutput::Updated(MotionEntry::Post(post)));
+            }
+        }
+        if self.clusters.is_some() {
+            let changes = self.cluster_outputs(&outputs);
+            outputs.extend(changes);
+        }
+        Ok(outputs)
+    }
+
+    /// Cluster membership changes caused by `outputs`: moved users are
+    /// re-assigned and removed users leave their cluster.
+    fn cluster_outputs(&mut self, outputs: &[MotionOutput]) -> Vec<MotionOutput> {
+        let mut changes = Vec::new();
+        for output in outputs {
+            match output {
+                MotionOutput::InteractionApplied(result) => {
+                    changes.extend(result.moved_users().into_iter().filter_map(|id| self.recluster(id)));
                 }
-                MotionInput::Interaction(interaction) => {
-                    let res = self.apply_interaction(interaction)?; 
-                    tx.send(MotionOutput::InteractionApplied(res))
-                        .await
-                        .map_err(|_| CoreError::ChannelError)?; 
+                MotionOutput::Removed(MotionEntry::User(user)) => {
+                    if let Some(change) = self.clusters.as_mut().and_then(|c| c.remove(&user.id)) {
+                        changes.push(MotionOutput::ClusterChanged(change));
+                    }
                 }
+                _ => {}
+            }
+        }
+        changes
+    }
+
+    /// Re-assigns a user to the cluster nearest their current coord.
+    fn recluster(&mut self, user_id: &str) -> Option<MotionOutput> {
+        let coord = self.user(user_id)?.coord.as_ref()?.data.clone();
+        self.clusters
+            .as_mut()?
+            .observe(user_id, &coord)
+            .map(MotionOutput::ClusterChanged)
+    }
+
+    /// Turns on clustering and assigns every placed user, in entry order.
+    /// Replaces any clusters the space already had.
+    pub fn enable_clusters(&mut self, config: ClusterConfig) -> Vec<ClusterChange> {
+        let mut clusters = Clusters::new(config);
+        let mut changes = Vec::new();
+        for entry in &self.entries {
+            if let MotionEntry::User(MotionUser { id, coord: Some(coord), .. }) = entry {
+                changes.extend(clusters.observe(id, &coord.data));
+            }
+        }
+        clusters.flush();
+        self.clusters = Some(clusters);
+        changes
+    }
+
+    /// Applies one input, turning an error into a `Rejected` output, and
+    /// records it into `metrics` if given.
+    pub fn process_observed(&mut self, prepared: PreparedInput, metrics: Option<&Metrics>) -> Vec<MotionOutput> {
+        let started = Instant::now();
+        let input = prepared.input.clone();
+        let outputs = match self.process_prepared(prepared) {
+            Ok(outputs) => outputs,
+            Err(error) => vec![MotionOutput::Rejected { error, input: input.clone() }],
+        };
+        if let Some(metrics) = metrics {
+            metrics.observe(&input, &outputs, started.elapsed());
+        }
+        outputs
+    }
+
+    /// Runs until `rx` closes. Inputs that fail are reported as
+    /// `MotionOutput::Rejected` and the loop moves on; only a closed output
+    /// channel stops it early.
+    ///
+    /// Accepts raw [`MotionInput`]s or [`PreparedInput`]s from an embedding
+    /// stage such as `pipeline::EmbedPool`.
+    #[cfg(feature = "runtime")]
+    pub async fn core_loop<T>(&mut self, rx: Receiver<T>, tx: Sender<MotionOutput>) -> Result<(), CoreError>
+    where
+        T: Into<PreparedInput>,
+    {
+        self.run_core_loop(rx, tx, None).await
+    }
+
+    /// [`core_loop`](Self::core_loop) that also records every input, output
+    /// and per-input latency into `metrics`.
+    #[cfg(feature = "runtime")]
+    pub async fn core_loop_observed<T>(
+        &mut self,
+        rx: Receiver<T>,
+        tx: Sender<MotionOutput>,
+        metrics: &Metrics,
+    ) -> Result<(), CoreError>
+    where
+        T: Into<PreparedInput>,
+    {
+        metrics.track_space(self);
+        self.run_core_loop(rx, tx, Some(metrics)).await
+    }
+
+    #[cfg(feature = "runtime")]
+    async fn run_core_loop<T>(
+        &mut self,
+        mut rx: Receiver<T>,
+        tx: Sender<MotionOutput>,
+        metrics: Option<&Metrics>,
+    ) -> Result<(), CoreError>
+    where
+        T: Into<PreparedInput>,
+    {
+        while let Some(input) = rx.recv().await {
+            let outputs = self.process_observed(input.into(), metrics);
+            for output in outputs {
+                tx.send(output)
+                    .await
+                    .map_err(|_| CoreError::ChannelError)?;
             }
         }
         Ok(())
     }    
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motion_input::{DeleteInput, EditPostInput, UserInput};
+
+    fn post(id: &str, user_id: &str, text: &str) -> MotionInput {
+        MotionInput::Post(PostInput::new(id, user_id, text))
+    }
+
+    fn interact(kind: InteractionType, src_id: &str, dst_id: &str) -> MotionInput {
+        MotionInput::Interaction(Interaction::new(kind, src_id, dst_id))
+    }
+
+    fn assert_unit(coord: &VecN) {
+        assert!(coord.data.iter().all(|x| x.is_finite()), "non-finite coord {:?}", coord.data);
+        let norm = coord.data.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-3, "norm {norm}");
+    }
+
+    fn indexed(space: &MotionSpace, token: &str) -> Option<u32> {
+        let bucket = space.embedder.token_bucket(token);
+        space
+            .token_index
+            .as_ref()
+            .unwrap()
+            .tokens(bucket)
+            .find_map(|(t, count)| (t == token).then_some(count))
+    }
+
+    #[test]
+    fn deleting_a_user_erases_their_tokens() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "myprivatediagnosis shared")).unwrap();
+        space.process(post("p2", "bob", "shared news")).unwrap();
+        assert_eq!(indexed(&space, "shared"), Some(2));
+
+        space.process(MotionInput::DeleteUser(DeleteInput::new("alice"))).unwrap();
+        assert_eq!(indexed(&space, "myprivatediagnosis"), None);
+        assert_eq!(indexed(&space, "shared"), Some(1));
+        let snapshot = serde_json::to_string(&space).unwrap();
+        assert!(!snapshot.contains("myprivatediagnosis"));
+    }
+
+    #[test]
+    fn deleting_or_editing_a_post_replaces_its_tokens() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "old words")).unwrap();
+        space.process(post("p2", "alice", "gone soon")).unwrap();
+
+        space.process(MotionInput::EditPost(EditPostInput::new("p1", "new words"))).unwrap();
+        assert_eq!(indexed(&space, "old"), None);
+        assert_eq!(indexed(&space, "new"), Some(1));
+        assert_eq!(indexed(&space, "words"), Some(1));
+
+        space.process(MotionInput::DeletePost(DeleteInput::new("p2"))).unwrap();
+        assert_eq!(indexed(&space, "gone"), None);
+    }
+
+    #[test]
+    fn repulsion_moves_away_and_keeps_coords_unit_length() {
+        let mut space = MotionSpace::new(32);
+        space.process(post("p1", "alice", "rust async runtimes")).unwrap();
+        space.process(post("p2", "bob", "rust compilers and async")).unwrap();
+        let bob = space.user("bob").unwrap().coord.clone().unwrap();
+        let before = space.kernel.apply(&bob.data, &space.post("p1").unwrap().coord.data).unwrap();
+
+        space.process(interact(InteractionType::Dislike, "p1", "bob")).unwrap();
+        let bob = space.user("bob").unwrap().coord.clone().unwrap();
+        let after = space.kernel.apply(&bob.data, &space.post("p1").unwrap().coord.data).unwrap();
+        assert!(after < before, "{after} >= {before}");
+        assert_unit(&bob);
+    }
+
+    #[test]
+    fn coords_stay_finite_and_unit_length() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut space = MotionSpace::new(16);
+        let words = ["cats", "dogs", "rust", "jazz", "tea", "rain", "maps", "code"];
+        for i in 0..12 {
+            let text = format!("{} {}", words[i % words.len()], words[(i * 3 + 1) % words.len()]);
+            space.process(post(&format!("p{i}"), &format!("u{}", i % 4), &text)).unwrap();
+        }
+        let kinds = [
+            InteractionType::Like,
+            InteractionType::Share,
+            InteractionType::Dislike,
+            InteractionType::Hide,
+            InteractionType::Follow,
+            InteractionType::UserToUser,
+        ];
+        for _ in 0..2000 {
+            let kind = kinds[rng.random_range(0..kinds.len())];
+            let user = format!("u{}", rng.random_range(0..4));
+            let src = match kind.target() {
+                InteractionTarget::Post => format!("p{}", rng.random_range(0..12)),
+                InteractionTarget::User => format!("u{}", rng.random_range(0..4)),
+            };
+            let alpha = rng.random_range(-20.0..20.0);
+            let interaction = match kind.target() {
+                InteractionTarget::Post => Interaction::new(kind, src, user),
+                InteractionTarget::User => Interaction::new(kind, user, src),
+            };
+            space.process(MotionInput::Interaction(interaction.with_alpha(alpha))).unwrap();
+        }
+        for entry in &space.entries {
+            match entry {
+                MotionEntry::User(u) => assert_unit(u.coord.as_ref().unwrap()),
+                MotionEntry::Post(p) => assert_unit(&p.coord),
+            }
+        }
+    }
+
+    #[test]
+    fn repelling_a_coincident_coord_leaves_it_in_place() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "same text")).unwrap();
+        space.process(post("p2", "bob", "same text")).unwrap();
+        let before = space.user("bob").unwrap().coord.clone().unwrap();
+        space.process(interact(InteractionType::Dislike, "p1", "bob")).unwrap();
+        assert_eq!(space.user("bob").unwrap().coord.as_ref().unwrap().data, before.data);
+    }
+
+    #[test]
+    fn rejected_repulsion_does_not_create_the_user() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "hello")).unwrap();
+        let entries = space.entries.len();
+        let err = space.process(interact(InteractionType::Dislike, "p1", "carol")).unwrap_err();
+        assert!(matches!(err, CoreError::CoordNotLoaded { .. }));
+        assert!(space.user("carol").is_none());
+        assert_eq!(space.entries.len(), entries);
+    }
+
+    #[test]
+    fn engaging_user_is_entered_before_the_interaction() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "hello")).unwrap();
+        let outputs = space.process(interact(InteractionType::Like, "p1", "carol")).unwrap();
+        assert!(matches!(&outputs[0], MotionOutput::Entered(MotionEntry::User(u)) if u.id == "carol"));
+        assert!(matches!(&outputs[1], MotionOutput::InteractionApplied(_)));
+        let outputs = space.process(interact(InteractionType::Like, "p1", "carol")).unwrap();
+        assert_eq!(outputs.len(), 1);
+    }
+
+    #[test]
+    fn duplicate_post_ids_are_rejected() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "first")).unwrap();
+        let err = space.process(post("p1", "bob", "second")).unwrap_err();
+        assert!(matches!(err, CoreError::DuplicatePost { post_id } if post_id == "p1"));
+        assert_eq!(space.stats().posts, 1);
+        assert!(space.user("bob").is_none());
+    }
+
+    #[test]
+    fn announcing_a_known_user_again_changes_nothing() {
+        let mut space = MotionSpace::new(16);
+        let user = || MotionInput::User(UserInput::new("alice"));
+        assert_eq!(space.process(user()).unwrap().len(), 1);
+        space.process(post("p1", "alice", "hello")).unwrap();
+        let coord = space.user("alice").unwrap().coord.clone();
+        assert!(space.process(user()).unwrap().is_empty());
+        assert_eq!(space.stats().users, 1);
+        assert_eq!(space.user("alice").unwrap().coord.as_ref().map(|c| &c.data), coord.as_ref().map(|c| &c.data));
+    }
+
+    #[test]
+    fn deleting_a_user_takes_back_their_engagement() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "rust async runtimes")).unwrap();
+        space.process(post("p2", "bob", "rust compilers")).unwrap();
+        space.process(interact(InteractionType::Like, "p1", "bob")).unwrap();
+        space.process(interact(InteractionType::Dislike, "p1", "bob")).unwrap();
+        space.process(interact(InteractionType::Dislike, "p1", "bob")).unwrap();
+        space.process(interact(InteractionType::Like, "p1", "carol")).unwrap();
+        assert_eq!(space.post("p1").unwrap().engagement, Engagement { positive: 2, negative: 2 });
+
+        space.process(MotionInput::DeleteUser(DeleteInput::new("bob"))).unwrap();
+        assert_eq!(space.post("p1").unwrap().engagement, Engagement { positive: 1, negative: 0 });
+    }
+
+    #[test]
+    fn deleting_a_user_resets_the_posts_they_engaged_with() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "rust async runtimes")).unwrap();
+        space.process(post("p2", "alice", "gardening in spring")).unwrap();
+        space.process(post("p3", "bob", "compilers and parsers")).unwrap();
+        space.process(post("p4", "carol", "sourdough baking")).unwrap();
+        space.process(interact(InteractionType::Like, "p1", "bob")).unwrap();
+        space.process(interact(InteractionType::Like, "p2", "carol")).unwrap();
+        let moved = |space: &MotionSpace, id: &str| {
+            let p = space.post(id).unwrap();
+            p.coord.data != p.origin.data
+        };
+        assert!(moved(&space, "p1") && moved(&space, "p2"));
+
+        space.process(MotionInput::DeleteUser(DeleteInput::new("bob"))).unwrap();
+        assert!(!moved(&space, "p1"));
+        // Only posts bob engaged with are touched.
+        assert!(moved(&space, "p2"));
+    }
+
+    fn recommended(space: &MotionSpace, user_id: &str) -> Vec<String> {
+        space.recommend_users(user_id, 10).unwrap().into_iter().map(|u| u.user_id).collect()
+    }
+
+    fn set_motion(space: &mut MotionSpace, user_id: &str, motion: f32) {
+        for entry in &mut space.entries {
+            if let MotionEntry::User(u) = entry
+                && u.id == user_id
+            {
+                u.motion = motion;
+            }
+        }
+    }
+
+    #[test]
+    fn recommended_users_leave_out_followed_and_unplaced_users() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "rust async runtimes")).unwrap();
+        space.process(post("p2", "bob", "rust async runtimes")).unwrap();
+        space.process(post("p3", "carol", "rust async runtimes")).unwrap();
+        space.process(MotionInput::User(UserInput::new("dave"))).unwrap();
+        assert_eq!(recommended(&space, "alice").len(), 2);
+
+        space.process(interact(InteractionType::Follow, "alice", "bob")).unwrap();
+        assert_eq!(recommended(&space, "alice"), ["carol"]);
+        // Following is one-way: bob may still be pointed at alice.
+        assert!(recommended(&space, "bob").contains(&"alice".to_string()));
+    }
+
+    #[test]
+    fn recommended_users_are_boosted_by_motion() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "rust async runtimes")).unwrap();
+        space.process(post("p2", "bob", "rust async runtimes")).unwrap();
+        space.process(post("p3", "carol", "rust async runtimes")).unwrap();
+        set_motion(&mut space, "bob", 0.0);
+        set_motion(&mut space, "carol", 3.0);
+
+        let users = space.recommend_users("alice", 10).unwrap();
+        assert_eq!(users.iter().map(|u| u.user_id.as_str()).collect::<Vec<_>>(), ["carol", "bob"]);
+        assert!((users[0].similarity - users[1].similarity).abs() < 1e-6);
+        assert!((users[0].score - users[0].similarity * (1.0 + 3.0f32.ln_1p())).abs() < 1e-5);
+        assert!((users[1].score - users[1].similarity).abs() < 1e-6);
+    }
+
+    #[test]
+    fn recommending_to_an_unplaced_user_fails() {
+        let mut space = MotionSpace::new(16);
+        space.process(MotionInput::User(UserInput::new("dave"))).unwrap();
+        let err = space.recommend_users("dave", 5).unwrap_err();
+        assert!(matches!(err, CoreError::CoordNotLoaded { .. }));
+        let err = space.recommend_users("erin", 5).unwrap_err();
+        assert!(matches!(err, CoreError::UserNotFound { .. }));
+    }
+
+    #[test]
+    fn non_finite_alpha_is_rejected_before_anything_moves() {
+        let mut space = MotionSpace::new(16);
+        space.process(post("p1", "alice", "rust async runtimes")).unwrap();
+        space.process(post("p2", "bob", "garden tomatoes")).unwrap();
+        let before = serde_json::to_string(&space).unwrap();
+        for alpha in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            for (kind, src, dst) in [(InteractionType::Like, "p1", "bob"), (InteractionType::Like, "p1", "carol")] {
+                let input = MotionInput::Interaction(Interaction::new(kind, src, dst).with_alpha(alpha));
+                let err = space.process(input).unwrap_err();
+                assert!(matches!(err, CoreError::InvalidAlpha { .. }), "{err}");
+                assert_eq!(err.kind(), "invalid_alpha");
+            }
+            let follow = Interaction::new(InteractionType::Follow, "alice", "bob").with_alpha(alpha);
+            assert!(matches!(space.apply_interaction(follow), Err(CoreError::InvalidAlpha { .. })));
+        }
+        assert_eq!(serde_json::to_string(&space).unwrap(), before);
+        assert!(space.process(interact(InteractionType::Like, "p1", "bob")).is_ok());
+    }
+}
@@ -1,38 +1,72 @@
+//! Inputs to the motion space and the interactive stdin front end.
+
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc::Sender;
+#[cfg(feature = "runtime")]
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+#[cfg(feature = "runtime")]
+use crate::queue::{Admission, QueueSender};
 use thiserror::Error;
+#[cfg(feature = "runtime")]
 use chrono::Utc;
+#[cfg(feature = "runtime")]
 use std::collections::HashSet;
+#[cfg(feature = "runtime")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Numbers the posts the REPL names itself, across every reader.
+#[cfg(feature = "runtime")]
+static NEXT_POST: AtomicU64 = AtomicU64::new(0);
 
+/// Why an input loop stopped with an error.
 #[derive(Debug, Error)]
 pub enum InputError {
+    /// A line could not be turned into an input.
     #[error("input is not valid")]
     InvalidInput,
+    /// The queue to the core closed.
     #[error("channel closed while sending post input")]
     ChannelError,
 }
 
+/// A new post.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PostInput {
+    /// Post id; must be unique in the space.
     pub id: String,
+    /// Author's user id.
     pub user_id: String,
-    pub text: String
+    /// Post text.
+    pub text: String,
+    /// Unix millis; defaults to the time the core applies the post.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
 }
 impl PostInput {
+    /// A post created when the core applies it.
     pub fn new(id: impl Into<String>, user_id: impl Into<String>, text: impl Into<String>) -> Self {
         Self {
             id: id.into(),
             user_id: user_id.into(),
-            text: text.into()
+            text: text.into(),
+            created_at: None,
         }
     }
+
+    /// Sets the creation time, in unix millis.
+    pub fn with_created_at(mut self, created_at: i64) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
 }
 
+/// Announces a user. Announcing a known user again does nothing.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserInput {
+    /// User id.
     pub id: String,
 }
 impl UserInput {
+    /// A user input for `id`.
     pub fn new(id: impl Into<String>) -> Self {
         Self {
             id: id.into(),
@@ -40,37 +74,289 @@ impl UserInput {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Deletes a user or a post, depending on the [`MotionInput`] variant.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeleteInput {
+    /// Id of the user or post.
+    pub id: String,
+}
+impl DeleteInput {
+    /// A delete input for `id`.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+        }
+    }
+}
+
+/// New text for an existing post.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EditPostInput {
+    /// Post id.
+    pub id: String,
+    /// The replacement text.
+    pub text: String,
+}
+impl EditPostInput {
+    /// An edit of post `id` to `text`.
+    pub fn new(id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            text: text.into(),
+        }
+    }
+}
+
+/// Serialized in snake_case; the short [`name`](InteractionType::name)s used
+/// by the REPL and in metrics labels are accepted as well.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
 pub enum InteractionType {
+    /// Generic engagement with a post.
+    #[serde(alias = "post")]
     PostToUser,
+    /// Generic interaction with another user.
+    #[serde(alias = "user")]
     UserToUser,
+    /// Liking a post.
+    Like,
+    /// Sharing a post.
+    Share,
+    /// Replying to a post.
+    Reply,
+    /// Disliking a post; repels.
+    Dislike,
+    /// Hiding a post; repels.
+    Hide,
+    /// Following another user.
+    Follow,
+}
+
+/// What an interaction points at. Post kinds carry `src_id = post`, `dst_id = user`;
+/// user kinds carry `src_id = actor`, `dst_id = target`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum InteractionTarget {
+    /// The target is a post.
+    Post,
+    /// The target is a user.
+    User,
+}
+
+/// Per-kind defaults for the motion update.
+#[derive(Debug, Clone, Copy)]
+pub struct KindDynamics {
+    /// Default interaction strength.
+    pub alpha: f32,
+    /// +1.0 pulls the user toward the target, -1.0 pushes it away.
+    pub polarity: f32,
+    /// How much the interaction adds to the user's motion.
+    pub motion_gain: f32,
+}
+
+impl InteractionType {
+    /// Every kind.
+    pub const ALL: [InteractionType; 8] = [
+        InteractionType::PostToUser,
+        InteractionType::UserToUser,
+        InteractionType::Like,
+        InteractionType::Share,
+        InteractionType::Reply,
+        InteractionType::Dislike,
+        InteractionType::Hide,
+        InteractionType::Follow,
+    ];
+
+    /// Short name used by the REPL and in metrics labels.
+    pub fn name(&self) -> &'static str {
+        match self {
+            InteractionType::PostToUser => "post",
+            InteractionType::UserToUser => "user",
+            InteractionType::Like => "like",
+            InteractionType::Share => "share",
+            InteractionType::Reply => "reply",
+            InteractionType::Dislike => "dislike",
+            InteractionType::Hide => "hide",
+            InteractionType::Follow => "follow",
+        }
+    }
+
+    /// The kind with the short name `s`, ignoring case.
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|k| k.name().eq_ignore_ascii_case(s))
+    }
+
+    /// Whether the kind points at a post or a user.
+    pub fn target(&self) -> InteractionTarget {
+        match self {
+            InteractionType::UserToUser | InteractionType::Follow => InteractionTarget::User,
+            _ => InteractionTarget::Post,
+        }
+    }
+
+    /// The kind's defaults for the motion update.
+    pub fn dynamics(&self) -> KindDynamics {
+        let (alpha, polarity, motion_gain) = match self {
+            InteractionType::PostToUser => (0.5, 1.0, 1.0),
+            InteractionType::UserToUser => (0.5, 1.0, 1.0),
+            InteractionType::Like => (0.5, 1.0, 1.0),
+            InteractionType::Share => (1.0, 1.0, 1.5),
+            InteractionType::Reply => (0.8, 1.0, 1.2),
+            InteractionType::Dislike => (0.5, -1.0, 0.5),
+            InteractionType::Hide => (0.3, -1.0, 0.2),
+            InteractionType::Follow => (1.0, 1.0, 1.5),
+        };
+        KindDynamics { alpha, polarity, motion_gain }
+    }
 }
+
+/// One user interacting with a post or another user; see
+/// [`InteractionTarget`] for which id is which.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Interaction {
+    /// The kind of interaction.
     pub interaction_type: InteractionType, 
+    /// The post for post kinds, the acting user for user kinds.
     pub src_id: String,
+    /// The engaging user for post kinds, the target user for user kinds.
     pub dst_id: String,
-    pub alpha: f32,
+    /// Overrides the kind's default alpha when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<f32>,
+    /// Unix millis; defaults to the time the core applies the interaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<i64>,
 }
 
+impl Interaction {
+    /// An interaction with the kind's default alpha, applied now.
+    pub fn new(interaction_type: InteractionType, src_id: impl Into<String>, dst_id: impl Into<String>) -> Self {
+        Self {
+            interaction_type,
+            src_id: src_id.into(),
+            dst_id: dst_id.into(),
+            alpha: None,
+            at: None,
+        }
+    }
+
+    /// Overrides the kind's default alpha.
+    pub fn with_alpha(mut self, alpha: f32) -> Self {
+        self.alpha = Some(alpha);
+        self
+    }
+
+    /// Sets when the interaction happened, in unix millis.
+    pub fn with_at(mut self, at: i64) -> Self {
+        self.at = Some(at);
+        self
+    }
+
+    /// The effective alpha.
+    pub fn alpha(&self) -> f32 {
+        self.alpha.unwrap_or_else(|| self.interaction_type.dynamics().alpha)
+    }
+}
+
+/// One line of the JSON protocol, e.g.
+/// `{"type":"interaction","interaction_type":"like","src_id":"post-1","dst_id":"alice"}`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum MotionInput {
+    /// Announces a user.
     User(UserInput),
+    /// Enters a post.
     Post(PostInput),
+    /// Applies an interaction.
     Interaction(Interaction),
+    /// Erases the user together with every post they wrote.
+    DeleteUser(DeleteInput),
+    /// Erases a post.
+    DeletePost(DeleteInput),
+    /// Replaces a post's text and re-embeds it.
+    EditPost(EditPostInput),
+}
+
+impl MotionInput {
+    /// Every [`kind`](MotionInput::kind), in metrics order.
+    pub const KINDS: [&'static str; 6] = ["user", "post", "interaction", "delete_user", "delete_post", "edit_post"];
+
+    /// The JSON `type` tag, e.g. `"delete_post"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MotionInput::User(_) => "user",
+            MotionInput::Post(_) => "post",
+            MotionInput::Interaction(_) => "interaction",
+            MotionInput::DeleteUser(_) => "delete_user",
+            MotionInput::DeletePost(_) => "delete_post",
+            MotionInput::EditPost(_) => "edit_post",
+        }
+    }
+
+    /// Text that has to be embedded before the input can be applied.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            MotionInput::Post(post) => Some(&post.text),
+            MotionInput::EditPost(edit) => Some(&edit.text),
+            _ => None,
+        }
+    }
+}
+
+/// Why [`MotionInput::read_loop`] stopped reading.
+#[cfg(feature = "runtime")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopExit {
+    /// The input ended.
+    Eof,
+    /// `q`: this reader is done, other readers keep going.
+    Quit,
+    /// `shutdown`: the whole process should drain and exit.
+    Shutdown,
 }
 
+#[cfg(feature = "runtime")]
 impl MotionInput {
-    pub async fn input_loop(tx: Sender<MotionInput>) -> Result<(), InputError> {
-        use tokio::io::{self, AsyncBufReadExt};
-                 
-        let stdin = io::BufReader::new(io::stdin());
-        let mut lines = stdin.lines();
+    /// Reads REPL commands and JSON lines from stdin until `q`, `shutdown` or EOF.
+    pub async fn input_loop(tx: QueueSender) -> Result<LoopExit, InputError> {
+        let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+        Self::read_loop(stdin, tx, true).await
+    }
+
+    /// Reads REPL commands and JSON lines from `reader` until `q`, `shutdown`
+    /// or EOF.
+    /// Non-interactive readers (files, sockets) get no prompt or
+    /// acknowledgements, and usage errors go to stderr.
+    pub async fn read_loop<R>(reader: R, tx: QueueSender, interactive: bool) -> Result<LoopExit, InputError>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let mut lines = reader.lines();
+
+        macro_rules! ack {
+            ($($arg:tt)*) => {
+                if interactive {
+                    println!($($arg)*);
+                }
+            };
+        }
+        macro_rules! say {
+            ($($arg:tt)*) => {
+                if interactive {
+                    println!($($arg)*);
+                } else {
+                    eprintln!($($arg)*);
+                }
+            };
+        }
         
         let mut current_user: Option<String> = None;
+        // Users this reader has already announced. Only saves sending them
+        // again; the core ignores users it already has.
         let mut known_users: HashSet<String> = HashSet::new();
 
         async fn ensure_user(
-            tx: &Sender<MotionInput>,
+            tx: &QueueSender,
             known: &mut HashSet<String>,
             user_id: &str,
         ) -> Result<(), InputError> {
@@ -84,11 +370,17 @@ impl MotionInput {
         }
 
         async fn send_post(
-            tx: &Sender<MotionInput>,
+            tx: &QueueSender,
             user_id: &str,
             text: &str,
         ) -> Result<(), InputError> {
-            let post_id = format!("post-{}", Utc::now().timestamp_millis());
+            // The timestamp keeps ids apart across restarts, the counter
+            // within one millisecond.
+            let post_id = format!(
+                "post-{}-{}",
+                Utc::now().timestamp_millis(),
+                NEXT_POST.fetch_add(1, Ordering::Relaxed)
+            );
             let post = PostInput::new(post_id, user_id, text);
             tx.send(MotionInput::Post(post))
                 .await
@@ -96,17 +388,21 @@ impl MotionInput {
             Ok(())
         }
 
-        println!("Commands: u <id>, s <id>, p <text>, i post <post_id> <user_id> [alpha], i user <src_id> <dst_id> [alpha], q");
+        if interactive {
+            print_help();
+        }
 
         loop {
-            print!("> ");
+            if interactive {
+                print!("> ");
+            }
 
             let Some(line) = lines
                 .next_line()
                 .await
                 .map_err(|_| InputError::InvalidInput)?
             else {
-                break;
+                return Ok(LoopExit::Eof);
             };
 
             let line = line.trim();
@@ -114,7 +410,41 @@ impl MotionInput {
                 continue;
             }
             if line.eq_ignore_ascii_case("q") {
-                break;
+                return Ok(LoopExit::Quit);
+            }
+            if line.eq_ignore_ascii_case("shutdown") {
+                return Ok(LoopExit::Shutdown);
+            }
+
+            if line.starts_with('{') {
+                let input: MotionInput = match serde_json::from_str(line) {
+                    Ok(input) => input,
+                    Err(e) => {
+                        say!("Invalid JSON input: {}", e);
+                        continue;
+                    }
+                };
+                match &input {
+                    MotionInput::User(user) => {
+                        known_users.insert(user.id.clone());
+                    }
+                    MotionInput::Post(post) => {
+                        known_users.insert(post.user_id.clone());
+                    }
+                    MotionInput::DeleteUser(user) => {
+                        known_users.remove(&user.id);
+                    }
+                    MotionInput::Interaction(_)
+                    | MotionInput::DeletePost(_)
+                    | MotionInput::EditPost(_) => {}
+                }
+                let admission = tx.send(input)
+                    .await
+                    .map_err(|_| InputError::ChannelError)?;
+                if admission == Admission::Rejected {
+                    ack!("Input queue full, interaction dropped");
+                }
+                continue;
             }
 
             let mut parts = line.split_whitespace();
@@ -123,109 +453,202 @@ impl MotionInput {
             match cmd {
                 "u" | "s" => {
                     let Some(id) = parts.next() else {
-                        println!("Usage: {} <id>", cmd);
+                        say!("Usage: {} <id>", cmd);
                         continue;
                     };
                     if id.is_empty() {
-                        println!("Cannot accept empty user id");
+                        say!("Cannot accept empty user id");
                         continue;
                     }
                     ensure_user(&tx, &mut known_users, id).await?;
                     current_user = Some(id.to_string());
-                    println!("Current user: {}", id);
+                    ack!("Current user: {}", id);
                 }
                 "p" => {
                     let text = line.strip_prefix("p").unwrap_or("").trim();
                     if text.is_empty() {
-                        println!("Usage: p <text>");
+                        say!("Usage: p <text>");
                         continue;
                     }
                     let Some(user_id) = current_user.as_ref() else {
-                        println!("No current user...");
+                        say!("No current user...");
                         continue;
                     };
                     ensure_user(&tx, &mut known_users, user_id).await?;
                     send_post(&tx, user_id, text).await?;
-                    println!("Posted as user: {}", user_id);
+                    ack!("Posted as user: {}", user_id);
                 }
                 "i" => {
                     let Some(kind) = parts.next() else {
-                        println!("Usage: i post|user ...");
+                        say!("Usage: i <kind> <src_id> <dst_id> [alpha]");
                         continue;
                     };
-                    match kind {
-                        "post" => {
-                            let Some(post_id) = parts.next() else {
-                                println!("Usage: i post <post_id> <user_id> [alpha]");
-                                continue;
-                            };
-                            let Some(user_id) = parts.next() else {
-                                println!("Usage: i post <post_id> <user_id> [alpha]");
+                    let Some(interaction_type) = InteractionType::parse(kind) else {
+                        say!("Unknown interaction kind: {} (expected one of {})", kind, kind_names());
+                        continue;
+                    };
+                    let usage = match interaction_type.target() {
+                        InteractionTarget::Post => format!("Usage: i {} <post_id> <user_id> [alpha]", kind),
+                        InteractionTarget::User => format!("Usage: i {} <src_id> <dst_id> [alpha]", kind),
+                    };
+                    let Some(src_id) = parts.next() else {
+                        say!("{}", usage);
+                        continue;
+                    };
+                    let Some(dst_id) = parts.next() else {
+                        say!("{}", usage);
+                        continue;
+                    };
+                    let mut interaction = Interaction::new(interaction_type, src_id, dst_id);
+                    if let Some(value) = parts.next() {
+                        match value.parse::<f32>() {
+                            Ok(alpha) if alpha.is_finite() => interaction = interaction.with_alpha(alpha),
+                            _ => {
+                                say!("Invalid alpha: {} (expected a finite number)", value);
                                 continue;
-                            };
-                            let alpha = parts.next().and_then(|v| v.parse().ok()).unwrap_or(0.5);
-                            ensure_user(&tx, &mut known_users, user_id).await?;
-                            tx.send(MotionInput::Interaction(Interaction {
-                                interaction_type: InteractionType::PostToUser,
-                                src_id: post_id.to_string(),
-                                dst_id: user_id.to_string(),
-                                alpha,
-                            }))
-                            .await
-                            .map_err(|_| InputError::ChannelError)?;
+                            }
                         }
+                    }
+                    if interaction_type.target() == InteractionTarget::User {
+                        ensure_user(&tx, &mut known_users, src_id).await?;
+                    }
+                    ensure_user(&tx, &mut known_users, dst_id).await?;
+                    let admission = tx.send(MotionInput::Interaction(interaction))
+                        .await
+                        .map_err(|_| InputError::ChannelError)?;
+                    if admission == Admission::Rejected {
+                        ack!("Input queue full, interaction dropped");
+                    }
+                }
+                "d" | "del" => {
+                    let (Some(kind), Some(id)) = (parts.next(), parts.next()) else {
+                        say!("Usage: del user|post <id>");
+                        continue;
+                    };
+                    let input = match kind {
                         "user" => {
-                            let Some(src_id) = parts.next() else {
-                                println!("Usage: i user <src_id> <dst_id> [alpha]");
-                                continue;
-                            };
-                            let Some(dst_id) = parts.next() else {
-                                println!("Usage: i user <src_id> <dst_id> [alpha]");
-                                continue;
-                            };
-                            let alpha = parts.next().and_then(|v| v.parse().ok()).unwrap_or(0.5);
-                            ensure_user(&tx, &mut known_users, src_id).await?;
-                            ensure_user(&tx, &mut known_users, dst_id).await?;
-                            tx.send(MotionInput::Interaction(Interaction {
-                                interaction_type: InteractionType::UserToUser,
-                                src_id: src_id.to_string(),
-                                dst_id: dst_id.to_string(),
-                                alpha,
-                            }))
-                            .await
-                            .map_err(|_| InputError::ChannelError)?;
+                            known_users.remove(id);
+                            if current_user.as_deref() == Some(id) {
+                                current_user = None;
+                            }
+                            MotionInput::DeleteUser(DeleteInput::new(id))
                         }
+                        "post" => MotionInput::DeletePost(DeleteInput::new(id)),
                         _ => {
-                            println!("Usage: i post|user ...");
+                            say!("Usage: del user|post <id>");
+                            continue;
                         }
+                    };
+                    tx.send(input)
+                        .await
+                        .map_err(|_| InputError::ChannelError)?;
+                }
+                "e" | "edit" => {
+                    let Some(post_id) = parts.next() else {
+                        say!("Usage: edit <post_id> <text>");
+                        continue;
+                    };
+                    let text = parts.collect::<Vec<_>>().join(" ");
+                    if text.is_empty() {
+                        say!("Usage: edit <post_id> <text>");
+                        continue;
                     }
+                    tx.send(MotionInput::EditPost(EditPostInput::new(post_id, text)))
+                        .await
+                        .map_err(|_| InputError::ChannelError)?;
                 }
                 "?" | "help" => {
-                    println!("Commands: u <id>, s <id>, p <text>, i post <post_id> <user_id> [alpha], i user <src_id> <dst_id> [alpha], q");
+                    if interactive {
+                        print_help();
+                    }
                 }
                 _ => {
                     if let Some((user_id, text)) = line.split_once(':') {
                         let user_id = user_id.trim();
                         let text = text.trim();
                         if user_id.is_empty() || text.is_empty() {
-                            println!("Usage: <user_id>: <text>");
+                            say!("Usage: <user_id>: <text>");
                             continue;
                         }
                         ensure_user(&tx, &mut known_users, user_id).await?;
                         send_post(&tx, user_id, text).await?;
-                        println!("Posted as user: {}", user_id);
+                        ack!("Posted as user: {}", user_id);
                     } else {
                         let Some(user_id) = current_user.as_ref() else {
-                            println!("No current user...");
+                            say!("No current user...");
                             continue;
                         };
                         ensure_user(&tx, &mut known_users, user_id).await?;
                         send_post(&tx, user_id, line).await?;
-                        println!("Posted as user: {}", user_id);
+                        ack!("Posted as user: {}", user_id);
                     }
                 }
             }
         }
-        Ok(())
+    }
+}
+
+#[cfg(feature = "runtime")]
+fn kind_names() -> String {
+    InteractionType::ALL
+        .iter()
+        .map(|k| k.name())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+#[cfg(feature = "runtime")]
+fn print_help() {
+    println!("Commands: u <id>, s <id>, p <text>, i <kind> <src_id> <dst_id> [alpha], del user|post <id>, edit <post_id> <text>, <json>, q, shutdown");
+    println!("Interaction kinds: {} (post kinds take <post_id> <user_id>)", kind_names());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_kind_name_deserializes() {
+        for kind in InteractionType::ALL {
+            let parsed: InteractionType = serde_json::from_str(&format!("\"{}\"", kind.name())).unwrap();
+            assert_eq!(parsed, kind);
+            let round_trip: InteractionType = serde_json::from_str(&serde_json::to_string(&kind).unwrap()).unwrap();
+            assert_eq!(round_trip, kind);
+            assert_eq!(InteractionType::parse(kind.name()), Some(kind));
+        }
+    }
+
+    #[test]
+    fn short_kind_names_work_in_json_inputs() {
+        let line = r#"{"type":"interaction","interaction_type":"user","src_id":"alice","dst_id":"bob"}"#;
+        let MotionInput::Interaction(interaction) = serde_json::from_str(line).unwrap() else {
+            panic!("not an interaction");
+        };
+        assert_eq!(interaction.interaction_type, InteractionType::UserToUser);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn repl_alpha_must_be_a_finite_number() {
+        use crate::queue::{shed_queue, ShedPolicy};
+
+        let (tx, mut rx) = shed_queue(32, ShedPolicy::Block);
+        let lines = "i like p1 alice 0.5\ni like p1 bob nan\ni like p1 bob inf\ni like p1 bob -inf\n\
+                     i like p1 bob 1e39\ni like p1 bob lots\ni follow alice carol -2\n";
+        let exit = MotionInput::read_loop(lines.as_bytes(), tx, false).await.unwrap();
+        assert_eq!(exit, LoopExit::Eof);
+
+        let mut sent = Vec::new();
+        while let Some(input) = rx.recv().await {
+            sent.push(match input {
+                MotionInput::User(user) => format!("user {}", user.id),
+                MotionInput::Interaction(i) => {
+                    format!("{} {} {} {:?}", i.interaction_type.name(), i.src_id, i.dst_id, i.alpha)
+                }
+                other => panic!("unexpected input {other:?}"),
+            });
+        }
+        // Rejected lines send nothing, not even the users they name.
+        assert_eq!(sent, ["user alice", "like p1 alice Some(0.5)", "user carol", "follow alice carol Some(-2.0)"]);
     }
 }
@@ -0,0 +1,202 @@
+//! Embedding stage between the input loop and the single-writer core.
+//!
+//! Post and edit texts are embedded on tokio's blocking pool while the core
+//! keeps applying earlier inputs. Results are forwarded in arrival order, so
+//! the core sees exactly the sequence the producer sent (and in particular
+//! each user's inputs in order).
+
+use std::sync::Arc;
+
+use thiserror::Error;
+use tokio::sync::mpsc::{self, Sender};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+use crate::embedding::Embedder;
+use crate::math::VecN;
+use crate::motion_core::PreparedInput;
+use crate::motion_input::MotionInput;
+use crate::queue::QueueReceiver;
+
+/// Why the embedding stage stopped.
+#[derive(Debug, Error)]
+pub enum PipelineError {
+    /// An embedding task panicked or was cancelled.
+    #[error("embedding worker failed: {0}")]
+    Worker(String),
+}
+
+enum Pending {
+    Ready(MotionInput),
+    Embedding(MotionInput, JoinHandle<VecN>),
+}
+
+/// Embeds post and edit texts in parallel and forwards inputs in order.
+#[derive(Debug, Clone)]
+pub struct EmbedPool {
+    embedder: Embedder,
+    workers: usize,
+    max_in_flight: usize,
+}
+
+impl EmbedPool {
+    /// `embedder` must match the one of the space the results are applied to.
+    pub fn new(embedder: Embedder) -> Self {
+        let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Self {
+            embedder,
+            workers,
+            max_in_flight: workers * 16,
+        }
+    }
+
+    /// Maximum number of texts embedded at once. Zero is treated as one.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Maximum number of inputs buffered between arrival and hand-off to the
+    /// core, including ones still being embedded.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// Runs until `rx` closes and every buffered input has been forwarded, or
+    /// until `tx` closes.
+    pub async fn run(self, mut rx: QueueReceiver, tx: Sender<PreparedInput>) -> Result<(), PipelineError> {
+        let (order_tx, mut order_rx) = mpsc::channel::<Pending>(self.max_in_flight);
+        let permits = Arc::new(Semaphore::new(self.workers));
+        let output = tx.clone();
+
+        let forward = tokio::spawn(async move {
+            while let Some(pending) = order_rx.recv().await {
+                let prepared = match pending {
+                    Pending::Ready(input) => PreparedInput::from(input),
+                    Pending::Embedding(input, handle) => {
+                        let embedding = handle
+                            .await
+                            .map_err(|e| PipelineError::Worker(e.to_string()))?;
+                        PreparedInput { input, embedding: Some(embedding) }
+                    }
+                };
+                if tx.send(prepared).await.is_err() {
+                    break;
+                }
+            }
+            Ok::<(), PipelineError>(())
+        });
+
+        loop {
+            let input = tokio::select! {
+                input = rx.recv() => input,
+                // Stop waiting for input as soon as nobody takes the results.
+                _ = output.closed() => None,
+            };
+            let Some(input) = input else { break };
+            let pending = match input.text() {
+                Some(text) => {
+                    let permit = permits
+                        .clone()
+                        .acquire_owned()
+                        .await
+                        .expect("embedding semaphore is never closed");
+                    let text = text.to_string();
+                    let embedder = self.embedder.clone();
+                    let handle = tokio::task::spawn_blocking(move || {
+                        let _permit = permit;
+                        embedder.embed(&text)
+                    });
+                    Pending::Embedding(input, handle)
+                }
+                None => Pending::Ready(input),
+            };
+            if order_tx.send(pending).await.is_err() {
+                break;
+            }
+        }
+        drop(order_tx);
+
+        forward
+            .await
+            .map_err(|e| PipelineError::Worker(e.to_string()))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::motion_input::{EditPostInput, Interaction, InteractionType, PostInput, UserInput};
+    use crate::queue::{shed_queue, ShedPolicy};
+
+    /// Posts with long and short texts interleaved, so later embeddings
+    /// often finish before earlier ones, mixed with inputs that need none.
+    fn inputs() -> Vec<MotionInput> {
+        let mut inputs = Vec::new();
+        for i in 0..40 {
+            let text = if i % 3 == 0 { format!("long post {i} ").repeat(2000) } else { format!("short {i}") };
+            inputs.push(MotionInput::Post(PostInput::new(format!("p{i}"), format!("u{}", i % 5), text)));
+            inputs.push(MotionInput::Interaction(Interaction::new(InteractionType::Like, format!("p{i}"), "reader")));
+            if i % 4 == 0 {
+                inputs.push(MotionInput::EditPost(EditPostInput::new(format!("p{i}"), format!("edited {i}"))));
+                inputs.push(MotionInput::User(UserInput::new(format!("lurker-{i}"))));
+            }
+        }
+        inputs
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn parallel_embedding_matches_a_sequential_run() {
+        let embedder = Embedder::default();
+        let inputs = inputs();
+        let (queue, rx) = shed_queue(inputs.len(), ShedPolicy::Block);
+        let (tx, mut out) = mpsc::channel(4);
+        let pool = EmbedPool::new(embedder.clone()).workers(4).max_in_flight(8);
+        let run = tokio::spawn(pool.run(rx, tx));
+        for input in inputs.clone() {
+            queue.send(input).await.unwrap();
+        }
+        drop(queue);
+
+        let mut forwarded = Vec::new();
+        while let Some(prepared) = out.recv().await {
+            forwarded.push(prepared);
+        }
+        run.await.unwrap().unwrap();
+        assert_eq!(forwarded.len(), inputs.len());
+        for (prepared, input) in forwarded.iter().zip(&inputs) {
+            assert_eq!(format!("{:?}", prepared.input), format!("{input:?}"));
+            let expected = input.text().map(|text| embedder.embed(text).data);
+            assert_eq!(prepared.embedding.as_ref().map(|e| &e.data), expected.as_ref(), "{input:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn stops_once_nobody_takes_the_results() {
+        let (queue, rx) = shed_queue(16, ShedPolicy::Block);
+        let (tx, out) = mpsc::channel(4);
+        let run = tokio::spawn(EmbedPool::new(Embedder::default()).workers(2).run(rx, tx));
+        queue.send(MotionInput::User(UserInput::new("alice"))).await.unwrap();
+        drop(out);
+        // The queue stays open, so only the closed output can end the run.
+        let result = timeout(Duration::from_secs(5), run).await.expect("pool kept running");
+        result.unwrap().unwrap();
+        drop(queue);
+    }
+
+    #[tokio::test]
+    async fn stops_once_the_queue_is_drained() {
+        let (queue, rx) = shed_queue(16, ShedPolicy::Block);
+        let (tx, mut out) = mpsc::channel(16);
+        queue.send(MotionInput::Post(PostInput::new("p1", "alice", "hello"))).await.unwrap();
+        drop(queue);
+        EmbedPool::new(Embedder::default()).run(rx, tx).await.unwrap();
+        assert!(out.recv().await.is_some_and(|prepared| prepared.embedding.is_some()));
+        assert!(out.recv().await.is_none());
+    }
+}
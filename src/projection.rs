@@ -0,0 +1,521 @@
+//! Low-dimensional projections of the space for plotting.
+//!
+//! Every placed user and every post is projected to 2 or 3 dimensions,
+//! either linearly with PCA or with exact t-SNE, which keeps local
+//! neighbourhoods but costs `O(n^2)` per iteration and is meant for a few
+//! thousand points at most. [`render_svg`] draws a projection as a
+//! self-contained scatter plot.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::math::{MathError, Matrix, Pca};
+use crate::motion_core::{MotionEntry, MotionSpace};
+
+/// Why a projection could not be made.
+#[derive(Debug, Error)]
+pub enum ProjectionError {
+    /// The requested number of output dimensions is not 2 or 3.
+    #[error("can only project to 2 or 3 dimensions, not {0}")]
+    Dims(usize),
+
+    /// Too few users with a coord and posts to project.
+    #[error("need at least {needed} placed entries to project, found {found}")]
+    TooFewPoints {
+        /// Entries the method needs.
+        needed: usize,
+        /// Placed entries in the space.
+        found: usize,
+    },
+
+    /// The underlying linear algebra failed.
+    #[error(transparent)]
+    Math(#[from] MathError),
+}
+
+/// How the space is projected.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Method {
+    /// Linear projection onto the top principal components.
+    Pca,
+    /// Exact t-SNE.
+    Tsne {
+        /// Effective number of neighbours each point is fitted to.
+        perplexity: f32,
+        /// Gradient descent steps.
+        iterations: usize,
+    },
+}
+
+impl Method {
+    /// Perplexity used when none is given.
+    pub const DEFAULT_PERPLEXITY: f32 = 30.0;
+    /// Iterations used when none are given.
+    pub const DEFAULT_ITERATIONS: usize = 500;
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    /// `pca`, `tsne`, or `tsne:<perplexity>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => (name, Some(param)),
+            None => (s, None),
+        };
+        match (name.to_ascii_lowercase().as_str(), param) {
+            ("pca", None) => Ok(Method::Pca),
+            ("tsne" | "t-sne", param) => {
+                let perplexity = match param {
+                    Some(p) => p
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|p| p.is_finite() && *p > 0.0)
+                        .ok_or_else(|| format!("invalid perplexity '{}'", p))?,
+                    None => Method::DEFAULT_PERPLEXITY,
+                };
+                Ok(Method::Tsne { perplexity, iterations: Method::DEFAULT_ITERATIONS })
+            }
+            _ => Err(format!("unknown projection '{}', expected pca, tsne or tsne:<perplexity>", s)),
+        }
+    }
+}
+
+/// One projected entry. `z` is only set for 3D projections.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectedPoint {
+    /// The user or post id.
+    pub id: String,
+    /// `user` or `post`.
+    pub kind: String,
+    /// First projected coordinate.
+    pub x: f32,
+    /// Second projected coordinate.
+    pub y: f32,
+    /// Third projected coordinate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub z: Option<f32>,
+    /// Users only.
+    pub motion: Option<f32>,
+    /// A user's cluster, or the cluster whose centroid is nearest a post.
+    /// `None` when clustering is off.
+    pub cluster: Option<usize>,
+}
+
+impl MotionSpace {
+    /// Projects every placed user and every post to `dims` dimensions.
+    pub fn project(&self, method: Method, dims: usize) -> Result<Vec<ProjectedPoint>, ProjectionError> {
+        if !(2..=3).contains(&dims) {
+            return Err(ProjectionError::Dims(dims));
+        }
+        let mut points = Vec::new();
+        let mut rows = Vec::new();
+        for entry in &self.entries {
+            let (coord, point) = match entry {
+                MotionEntry::User(u) => {
+                    let Some(coord) = &u.coord else { continue };
+                    let cluster = self.clusters.as_ref().and_then(|c| c.cluster_of(&u.id));
+                    (coord, ProjectedPoint::new(&u.id, "user", Some(u.motion), cluster))
+                }
+                MotionEntry::Post(p) => {
+                    let cluster = self.clusters.as_ref().and_then(|c| c.nearest(&p.coord.data));
+                    (&p.coord, ProjectedPoint::new(&p.id, "post", None, cluster))
+                }
+            };
+            rows.push(coord.data.as_slice());
+            points.push(point);
+        }
+        let needed = dims + 1;
+        if rows.len() < needed {
+            return Err(ProjectionError::TooFewPoints { needed, found: rows.len() });
+        }
+
+        let data = Matrix::from_rows(&rows)?;
+        let projected = match method {
+            Method::Pca => Pca::fit(&data, dims)?.transform(&data)?,
+            Method::Tsne { perplexity, iterations } => tsne(&data, dims, perplexity, iterations)?,
+        };
+        for (point, coords) in points.iter_mut().zip(projected.iter_rows()) {
+            point.x = coords[0];
+            point.y = coords[1];
+            point.z = coords.get(2).copied();
+        }
+        Ok(points)
+    }
+}
+
+impl ProjectedPoint {
+    fn new(id: &str, kind: &str, motion: Option<f32>, cluster: Option<usize>) -> Self {
+        Self {
+            id: id.to_string(),
+            kind: kind.to_string(),
+            x: 0.0,
+            y: 0.0,
+            z: None,
+            motion,
+            cluster,
+        }
+    }
+}
+
+/// Exact t-SNE, started from the PCA layout so the result is deterministic.
+fn tsne(data: &Matrix, dims: usize, perplexity: f32, iterations: usize) -> Result<Matrix, MathError> {
+    const EXAGGERATION: f64 = 12.0;
+    const EXAGGERATION_ITERATIONS: usize = 100;
+    let n = data.rows();
+    // Each point's affinities sum to 1/n, so a fixed rate overshoots and
+    // oscillates on small inputs; scale it with n instead.
+    let learning_rate = n as f64 / EXAGGERATION / 4.0;
+    let perplexity = (perplexity as f64).min((n - 1) as f64 / 3.0).max(1.0);
+
+    let dist: Vec<Vec<f64>> = data
+        .iter_rows()
+        .map(|a| {
+            data.iter_rows()
+                .map(|b| a.iter().zip(b).map(|(x, y)| ((x - y) as f64).powi(2)).sum())
+                .collect()
+        })
+        .collect();
+
+    // Conditional affinities with a per-point bandwidth matching the
+    // perplexity, then symmetrized.
+    let target_entropy = perplexity.ln();
+    let mut p = vec![vec![0.0f64; n]; n];
+    for i in 0..n {
+        let (mut lo, mut hi, mut beta) = (0.0, f64::INFINITY, 1.0);
+        for _ in 0..64 {
+            let mut sum = 0.0;
+            let mut weighted = 0.0;
+            for j in (0..n).filter(|&j| j != i) {
+                let w = (-beta * dist[i][j]).exp();
+                p[i][j] = w;
+                sum += w;
+                weighted += w * dist[i][j];
+            }
+            if sum == 0.0 {
+                hi = beta;
+                beta = (lo + hi) / 2.0;
+                continue;
+            }
+            let entropy = sum.ln() + beta * weighted / sum;
+            p[i].iter_mut().for_each(|w| *w /= sum);
+            if (entropy - target_entropy).abs() < 1e-5 {
+                break;
+            }
+            if entropy > target_entropy {
+                lo = beta;
+                beta = if hi.is_finite() { (lo + hi) / 2.0 } else { beta * 2.0 };
+            } else {
+                hi = beta;
+                beta = (lo + hi) / 2.0;
+            }
+        }
+    }
+    let p: Vec<Vec<f64>> = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| if i == j { 0.0 } else { ((p[i][j] + p[j][i]) / (2.0 * n as f64)).max(1e-12) })
+                .collect()
+        })
+        .collect();
+
+    let init = Pca::fit(data, dims)?.transform(data)?;
+    let spread = init
+        .as_slice()
+        .iter()
+        .map(|x| (*x as f64).powi(2))
+        .sum::<f64>()
+        .sqrt()
+        .max(f64::EPSILON);
+    let mut y: Vec<Vec<f64>> = init
+        .iter_rows()
+        .map(|row| row.iter().map(|&x| x as f64 / spread * 1e-2 * (n as f64).sqrt()).collect())
+        .collect();
+    let mut velocity = vec![vec![0.0f64; dims]; n];
+    let mut q = vec![vec![0.0f64; n]; n];
+
+    for iteration in 0..iterations {
+        let exaggeration = if iteration < EXAGGERATION_ITERATIONS { EXAGGERATION } else { 1.0 };
+        let momentum = if iteration < EXAGGERATION_ITERATIONS { 0.5 } else { 0.8 };
+        let mut q_sum = 0.0;
+        for i in 0..n {
+            for j in i + 1..n {
+                let d: f64 = y[i].iter().zip(&y[j]).map(|(a, b)| (a - b).powi(2)).sum();
+                let w = 1.0 / (1.0 + d);
+                q[i][j] = w;
+                q[j][i] = w;
+                q_sum += 2.0 * w;
+            }
+        }
+        for i in 0..n {
+            let mut grad = vec![0.0f64; dims];
+            for j in (0..n).filter(|&j| j != i) {
+                let force = 4.0 * (exaggeration * p[i][j] - q[i][j] / q_sum) * q[i][j];
+                for (g, (a, b)) in grad.iter_mut().zip(y[i].iter().zip(&y[j])) {
+                    *g += force * (a - b);
+                }
+            }
+            for (v, g) in velocity[i].iter_mut().zip(&grad) {
+                *v = momentum * *v - learning_rate * g;
+            }
+        }
+        for (row, v) in y.iter_mut().zip(&velocity) {
+            for (x, dx) in row.iter_mut().zip(v) {
+                *x += dx;
+            }
+        }
+    }
+
+    Matrix::new(n, dims, y.into_iter().flatten().map(|x| x as f32).collect())
+}
+
+const PALETTE: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+];
+
+/// Smallest width or height [`render_svg`] draws at; anything smaller would
+/// leave no room inside the margins.
+pub const MIN_SVG_SIZE: u32 = 64;
+
+/// Draws the `x`/`y` plane of a projection as a standalone SVG document.
+/// Users are filled circles sized by motion, posts are hollow squares, and
+/// both are coloured by cluster. Hovering a mark shows its id. Sizes below
+/// [`MIN_SVG_SIZE`] are raised to it.
+pub fn render_svg(points: &[ProjectedPoint], width: u32, height: u32) -> String {
+    const MARGIN: f32 = 20.0;
+    let (width, height) = (width.max(MIN_SVG_SIZE), height.max(MIN_SVG_SIZE));
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+    for p in points {
+        min_x = min_x.min(p.x);
+        max_x = max_x.max(p.x);
+        min_y = min_y.min(p.y);
+        max_y = max_y.max(p.y);
+    }
+    let span_x = (max_x - min_x).max(f32::EPSILON);
+    let span_y = (max_y - min_y).max(f32::EPSILON);
+    let (w, h) = (width as f32 - 2.0 * MARGIN, height as f32 - 2.0 * MARGIN);
+    let to_screen = |p: &ProjectedPoint| {
+        (
+            MARGIN + (p.x - min_x) / span_x * w,
+            // SVG y grows downward.
+            MARGIN + (max_y - p.y) / span_y * h,
+        )
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#ffffff"/>"##);
+    // Posts first so users stay visible on top of them.
+    for p in points.iter().filter(|p| p.kind == "post") {
+        let (x, y) = to_screen(p);
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="5" height="5" fill="none" stroke="{}" stroke-opacity="0.7"><title>post {}</title></rect>"#,
+            x - 2.5,
+            y - 2.5,
+            color(p.cluster),
+            escape(&p.id)
+        );
+    }
+    for p in points.iter().filter(|p| p.kind == "user") {
+        let (x, y) = to_screen(p);
+        let radius = 2.5 + p.motion.unwrap_or(0.0).max(0.0).ln_1p();
+        let _ = writeln!(
+            svg,
+            r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="{}" fill-opacity="0.8"><title>user {}</title></circle>"#,
+            x,
+            y,
+            radius,
+            color(p.cluster),
+            escape(&p.id)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn color(cluster: Option<usize>) -> &'static str {
+    cluster.map_or("#444444", |c| PALETTE[c % PALETTE.len()])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::VecN;
+    use crate::motion_core::{MotionPost, MotionUser};
+
+    fn user(space: &mut MotionSpace, id: &str, coord: &[f32], motion: f32) {
+        let mut user = MotionUser::new(id, space.dim);
+        user.coord = Some(VecN::new(coord.to_vec()));
+        user.motion = motion;
+        space.enter(MotionEntry::User(user));
+    }
+
+    fn post(space: &mut MotionSpace, id: &str, coord: &[f32]) {
+        let post = MotionPost::new(id.to_string(), "author".to_string(), VecN::new(coord.to_vec()));
+        space.enter(MotionEntry::Post(post));
+    }
+
+    /// A cross centred on (5, 1, 1): spread 4 along x, 2 along y, none along z.
+    fn cross() -> MotionSpace {
+        let mut space = MotionSpace::new(3);
+        user(&mut space, "west", &[3.0, 1.0, 1.0], 2.0);
+        user(&mut space, "east", &[7.0, 1.0, 1.0], 0.0);
+        post(&mut space, "south", &[5.0, 0.0, 1.0]);
+        post(&mut space, "north", &[5.0, 2.0, 1.0]);
+        // Unplaced users are left out.
+        space.enter(MotionEntry::User(MotionUser::new("nowhere", 3)));
+        space
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn pca_projects_onto_the_widest_axes() {
+        let points = cross().project(Method::Pca, 2).unwrap();
+        let ids: Vec<_> = points.iter().map(|p| (p.id.as_str(), p.kind.as_str())).collect();
+        assert_eq!(ids, [("west", "user"), ("east", "user"), ("south", "post"), ("north", "post")]);
+        assert_eq!(points[0].motion, Some(2.0));
+        assert_eq!(points[2].motion, None);
+        assert!(points.iter().all(|p| p.z.is_none() && p.cluster.is_none()));
+
+        // Components are only defined up to sign.
+        let xs: Vec<f32> = points.iter().map(|p| p.x).collect();
+        let ys: Vec<f32> = points.iter().map(|p| p.y).collect();
+        assert_close(xs[0].abs(), 2.0);
+        assert_close(xs[0] + xs[1], 0.0);
+        assert_close(xs[2], 0.0);
+        assert_close(xs[3], 0.0);
+        assert_close(ys[0], 0.0);
+        assert_close(ys[1], 0.0);
+        assert_close(ys[2].abs(), 1.0);
+        assert_close(ys[2] + ys[3], 0.0);
+
+        let points = cross().project(Method::Pca, 3).unwrap();
+        assert_eq!(points.len(), 4);
+        assert!(points.iter().all(|p| p.z.is_some_and(|z| z.abs() < 1e-4)));
+    }
+
+    #[test]
+    fn bad_dims_and_too_few_points_are_rejected() {
+        let space = cross();
+        for dims in [0, 1, 4] {
+            let err = space.project(Method::Pca, dims).unwrap_err();
+            assert!(matches!(err, ProjectionError::Dims(d) if d == dims), "{err}");
+        }
+        let mut space = MotionSpace::new(3);
+        user(&mut space, "a", &[1.0, 0.0, 0.0], 0.0);
+        post(&mut space, "p", &[0.0, 1.0, 0.0]);
+        let err = space.project(Method::Pca, 2).unwrap_err();
+        assert!(matches!(err, ProjectionError::TooFewPoints { needed: 3, found: 2 }), "{err}");
+        post(&mut space, "q", &[0.0, 0.0, 1.0]);
+        assert!(space.project(Method::Pca, 2).is_ok());
+        let err = space.project(Method::Pca, 3).unwrap_err();
+        assert!(matches!(err, ProjectionError::TooFewPoints { needed: 4, found: 3 }), "{err}");
+    }
+
+    #[test]
+    fn tsne_is_deterministic_and_keeps_groups_apart() {
+        let mut space = MotionSpace::new(4);
+        for i in 0..6 {
+            let d = i as f32 * 0.01;
+            user(&mut space, &format!("a{i}"), &[1.0 + d, 0.0, d, 0.0], 0.0);
+            post(&mut space, &format!("b{i}"), &[0.0, 1.0 - d, 0.0, d]);
+        }
+        let method = Method::Tsne { perplexity: 3.0, iterations: 200 };
+        let first = space.project(method, 2).unwrap();
+        let second = space.project(method, 2).unwrap();
+        let coords = |points: &[ProjectedPoint]| points.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+        assert_eq!(coords(&first), coords(&second));
+        assert!(first.iter().all(|p| p.x.is_finite() && p.y.is_finite()));
+
+        let dist = |a: &ProjectedPoint, b: &ProjectedPoint| ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt();
+        let (a, b): (Vec<_>, Vec<_>) = first.iter().partition(|p| p.id.starts_with('a'));
+        let within = a.iter().flat_map(|p| a.iter().map(move |q| dist(p, q))).fold(0.0, f32::max);
+        let between = a.iter().flat_map(|p| b.iter().map(move |q| dist(p, q))).fold(f32::MAX, f32::min);
+        assert!(within < between, "{within} >= {between}");
+    }
+
+    /// Checks that every tag is closed in order. Enough for the flat
+    /// documents `render_svg` writes.
+    fn assert_well_formed(svg: &str) {
+        let mut open = Vec::new();
+        let mut rest = svg;
+        while let Some(start) = rest.find('<') {
+            let end = start + rest[start..].find('>').expect("unclosed tag");
+            let tag = &rest[start + 1..end];
+            assert!(!tag.contains('<'), "stray '<' in {tag}");
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name), "mismatched </{name}>");
+            } else if !tag.ends_with('/') {
+                open.push(tag.split_whitespace().next().unwrap());
+            }
+            let text = &rest[end + 1..rest[end + 1..].find('<').map_or(rest.len(), |i| end + 1 + i)];
+            assert!(!text.contains('>'), "stray '>' in {text:?}");
+            for (i, _) in text.match_indices('&') {
+                let entity = &text[i..text[i..].find(';').map_or(text.len(), |j| i + j + 1)];
+                assert!(["&amp;", "&lt;", "&gt;"].contains(&entity), "bad entity in {text:?}");
+            }
+            rest = &rest[end + 1..];
+        }
+        assert!(open.is_empty(), "unclosed {open:?}");
+    }
+
+    #[test]
+    fn svg_is_well_formed_and_escapes_ids() {
+        let mut space = cross();
+        user(&mut space, "<b>&co", &[5.0, 1.5, 1.0], 1.0);
+        let points = space.project(Method::Pca, 2).unwrap();
+        let svg = render_svg(&points, 200, 100);
+        assert_well_formed(&svg);
+        assert!(svg.starts_with("<svg ") && svg.contains(r#"width="200" height="100""#));
+        assert!(svg.contains("<title>user &lt;b&gt;&amp;co</title>"));
+        assert_eq!(svg.matches("<circle").count(), 3);
+        assert_eq!(svg.matches("<rect x=").count(), 2);
+    }
+
+    #[test]
+    fn tiny_svgs_keep_every_mark_on_the_canvas() {
+        let points = cross().project(Method::Pca, 2).unwrap();
+        let svg = render_svg(&points, 10, 0);
+        assert_well_formed(&svg);
+        let size = MIN_SVG_SIZE as f32;
+        assert!(svg.contains(&format!(r#"width="{size}" height="{size}""#)));
+        for attr in ["cx=\"", "cy=\"", "x=\"", "y=\""] {
+            for (i, _) in svg.match_indices(&format!(" {attr}")) {
+                let value = &svg[i + attr.len() + 1..];
+                let value: f32 = value[..value.find('"').unwrap()].parse().unwrap();
+                assert!((0.0..=size).contains(&value), "{attr}{value}");
+            }
+        }
+    }
+
+    #[test]
+    fn methods_parse_from_their_names() {
+        assert_eq!("pca".parse::<Method>().unwrap(), Method::Pca);
+        assert_eq!("PCA".parse::<Method>().unwrap(), Method::Pca);
+        let default = Method::Tsne { perplexity: Method::DEFAULT_PERPLEXITY, iterations: Method::DEFAULT_ITERATIONS };
+        assert_eq!("tsne".parse::<Method>().unwrap(), default);
+        assert_eq!("t-SNE".parse::<Method>().unwrap(), default);
+        assert_eq!(
+            "tsne:12.5".parse::<Method>().unwrap(),
+            Method::Tsne { perplexity: 12.5, iterations: Method::DEFAULT_ITERATIONS }
+        );
+        for bad in ["", "umap", "pca:2", "tsne:", "tsne:0", "tsne:-1", "tsne:nan", "tsne:inf"] {
+            assert!(bad.parse::<Method>().is_err(), "{bad:?} parsed");
+        }
+    }
+}
@@ -0,0 +1,473 @@
+//! Bounded input queue with a load-shedding policy.
+//!
+//! Sits between the input loops and the embedding stage. While there is room
+//! every input is queued. Once the queue is full the [`ShedPolicy`] decides
+//! what happens to interactions; users, posts, edits and deletes always wait
+//! for room, since dropping them would make later inputs refer to entries
+//! that never arrived.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Notify;
+
+use crate::motion_input::{Interaction, MotionInput};
+
+/// What a full queue does with a new interaction. Other inputs always wait
+/// for room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShedPolicy {
+    /// Producers wait for room.
+    #[default]
+    Block,
+    /// Drop the oldest queued interaction to make room for a new one.
+    DropOldest,
+    /// Drop the new interaction.
+    RejectNew,
+    /// Fold a new interaction into a queued one with the same kind and ids,
+    /// summing their alphas. Waits for room if there is none to fold into.
+    Coalesce,
+}
+
+impl ShedPolicy {
+    /// Every policy.
+    pub const ALL: [ShedPolicy; 4] = [
+        ShedPolicy::Block,
+        ShedPolicy::DropOldest,
+        ShedPolicy::RejectNew,
+        ShedPolicy::Coalesce,
+    ];
+
+    /// The policy's name on the command line and in metrics.
+    pub fn name(self) -> &'static str {
+        match self {
+            ShedPolicy::Block => "block",
+            ShedPolicy::DropOldest => "drop_oldest",
+            ShedPolicy::RejectNew => "reject_new",
+            ShedPolicy::Coalesce => "coalesce",
+        }
+    }
+}
+
+impl fmt::Display for ShedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ShedPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.replace('-', "_");
+        ShedPolicy::ALL
+            .into_iter()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = ShedPolicy::ALL.iter().map(|p| p.name()).collect();
+                format!("unknown shed policy {s:?} (expected one of {})", names.join(", "))
+            })
+    }
+}
+
+/// The receiving end of the queue is gone.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("input queue closed")]
+pub struct QueueClosed;
+
+/// What happened to an input handed to [`QueueSender::send`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Queued normally.
+    Queued,
+    /// Queued after dropping the oldest queued interaction.
+    QueuedDroppingOldest,
+    /// Folded into an interaction that was already queued.
+    Coalesced,
+    /// Dropped because the queue was full.
+    Rejected,
+}
+
+/// Live counters for one queue, shared by its sender and receiver handles.
+#[derive(Debug, Default)]
+struct QueueStats {
+    depth: AtomicUsize,
+    high_water: AtomicUsize,
+    enqueued: AtomicU64,
+    dropped_oldest: AtomicU64,
+    rejected: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+/// A copy of a queue's counters at one point in time.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct QueueStatsSnapshot {
+    /// Maximum number of queued inputs.
+    pub capacity: usize,
+    /// Inputs queued right now.
+    pub depth: usize,
+    /// Highest depth seen.
+    pub high_water: usize,
+    /// Inputs queued, including ones queued after dropping the oldest.
+    pub enqueued: u64,
+    /// Interactions dropped to make room under [`ShedPolicy::DropOldest`].
+    pub dropped_oldest: u64,
+    /// Interactions dropped under [`ShedPolicy::RejectNew`].
+    pub rejected: u64,
+    /// Interactions folded into a queued one under [`ShedPolicy::Coalesce`].
+    pub coalesced: u64,
+}
+
+impl QueueStatsSnapshot {
+    /// Inputs that never reached the core as sent.
+    pub fn shed(&self) -> u64 {
+        self.dropped_oldest + self.rejected + self.coalesced
+    }
+
+    /// Prometheus text exposition, with every series labelled `queue="<name>"`.
+    pub fn render(&self, name: &str) -> String {
+        let gauges = [
+            ("motion_queue_capacity", "Maximum inputs the queue holds.", self.capacity as u64),
+            ("motion_queue_depth", "Inputs waiting in the queue.", self.depth as u64),
+            ("motion_queue_high_water", "Largest depth seen so far.", self.high_water as u64),
+        ];
+        let counters = [
+            ("motion_queue_enqueued_total", "Inputs queued.", self.enqueued),
+            ("motion_queue_dropped_oldest_total", "Queued interactions dropped for newer ones.", self.dropped_oldest),
+            ("motion_queue_rejected_total", "Interactions rejected because the queue was full.", self.rejected),
+            ("motion_queue_coalesced_total", "Interactions folded into a queued one.", self.coalesced),
+        ];
+        let mut out = String::new();
+        for (kind, metrics) in [("gauge", &gauges[..]), ("counter", &counters[..])] {
+            for (metric, help, value) in metrics {
+                out.push_str(&format!(
+                    "# HELP {metric} {help}\n# TYPE {metric} {kind}\n{metric}{{queue=\"{name}\"}} {value}\n"
+                ));
+            }
+        }
+        out
+    }
+}
+
+struct Shared {
+    capacity: usize,
+    policy: ShedPolicy,
+    queue: Mutex<VecDeque<MotionInput>>,
+    senders: AtomicUsize,
+    receiver_gone: AtomicBool,
+    /// Woken when an input is pushed or the last sender goes away.
+    not_empty: Notify,
+    /// Woken when an input is popped.
+    not_full: Notify,
+    stats: QueueStats,
+}
+
+impl Shared {
+    fn set_depth(&self, depth: usize) {
+        self.stats.depth.store(depth, Ordering::Relaxed);
+        self.stats.high_water.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> QueueStatsSnapshot {
+        let s = &self.stats;
+        QueueStatsSnapshot {
+            capacity: self.capacity,
+            depth: s.depth.load(Ordering::Relaxed),
+            high_water: s.high_water.load(Ordering::Relaxed),
+            enqueued: s.enqueued.load(Ordering::Relaxed),
+            dropped_oldest: s.dropped_oldest.load(Ordering::Relaxed),
+            rejected: s.rejected.load(Ordering::Relaxed),
+            coalesced: s.coalesced.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Creates a queue holding at most `capacity` inputs (zero is treated as one).
+pub fn shed_queue(capacity: usize, policy: ShedPolicy) -> (QueueSender, QueueReceiver) {
+    let shared = Arc::new(Shared {
+        capacity: capacity.max(1),
+        policy,
+        queue: Mutex::new(VecDeque::new()),
+        senders: AtomicUsize::new(1),
+        receiver_gone: AtomicBool::new(false),
+        not_empty: Notify::new(),
+        not_full: Notify::new(),
+        stats: QueueStats::default(),
+    });
+    (
+        QueueSender { shared: shared.clone() },
+        QueueReceiver { shared },
+    )
+}
+
+/// Sending half of an input queue; cloning it adds a producer.
+pub struct QueueSender {
+    shared: Arc<Shared>,
+}
+
+impl Clone for QueueSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.not_empty.notify_one();
+        }
+    }
+}
+
+impl QueueSender {
+    /// Queues `input`, applying the shed policy if the queue is full. Fails
+    /// only once the receiver is gone.
+    pub async fn send(&self, input: MotionInput) -> Result<Admission, QueueClosed> {
+        let shared = &*self.shared;
+        let mut input = Some(input);
+        loop {
+            let notified = shared.not_full.notified();
+            if shared.receiver_gone.load(Ordering::Acquire) {
+                return Err(QueueClosed);
+            }
+            {
+                let mut queue = shared.queue.lock().expect("queue lock poisoned");
+                let admission = if queue.len() < shared.capacity {
+                    Some(Admission::Queued)
+                } else if let Some(MotionInput::Interaction(new)) = input.as_ref() {
+                    shed(&mut queue, shared.policy, new)
+                } else {
+                    None
+                };
+                if let Some(admission) = admission {
+                    let stats = &shared.stats;
+                    match admission {
+                        Admission::Queued | Admission::QueuedDroppingOldest => {
+                            queue.push_back(input.take().expect("input is sent once"));
+                            stats.enqueued.fetch_add(1, Ordering::Relaxed);
+                            if admission == Admission::QueuedDroppingOldest {
+                                stats.dropped_oldest.fetch_add(1, Ordering::Relaxed);
+                            }
+                            shared.not_empty.notify_one();
+                        }
+                        Admission::Coalesced => {
+                            stats.coalesced.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Admission::Rejected => {
+                            stats.rejected.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    shared.set_depth(queue.len());
+                    return Ok(admission);
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// The queue's counters right now.
+    pub fn stats(&self) -> QueueStatsSnapshot {
+        self.shared.snapshot()
+    }
+
+    /// A handle for reading the stats that does not keep the queue open.
+    pub fn monitor(&self) -> QueueMonitor {
+        QueueMonitor { shared: self.shared.clone() }
+    }
+}
+
+/// Read-only access to a queue's counters.
+#[derive(Clone)]
+pub struct QueueMonitor {
+    shared: Arc<Shared>,
+}
+
+impl QueueMonitor {
+    /// The queue's counters right now.
+    pub fn stats(&self) -> QueueStatsSnapshot {
+        self.shared.snapshot()
+    }
+}
+
+/// Applies `policy` to a full queue for the new interaction `new`. `None`
+/// means the producer has to wait.
+fn shed(queue: &mut VecDeque<MotionInput>, policy: ShedPolicy, new: &Interaction) -> Option<Admission> {
+    match policy {
+        ShedPolicy::Block => None,
+        ShedPolicy::RejectNew => Some(Admission::Rejected),
+        ShedPolicy::DropOldest => {
+            let oldest = queue
+                .iter()
+                .position(|queued| matches!(queued, MotionInput::Interaction(_)))?;
+            queue.remove(oldest);
+            Some(Admission::QueuedDroppingOldest)
+        }
+        ShedPolicy::Coalesce => {
+            let queued = queue.iter_mut().rev().find_map(|queued| match queued {
+                MotionInput::Interaction(i)
+                    if i.interaction_type == new.interaction_type
+                        && i.src_id == new.src_id
+                        && i.dst_id == new.dst_id =>
+                {
+                    Some(i)
+                }
+                _ => None,
+            })?;
+            queued.alpha = Some(queued.alpha() + new.alpha());
+            Some(Admission::Coalesced)
+        }
+    }
+}
+
+/// Receiving half of an input queue.
+pub struct QueueReceiver {
+    shared: Arc<Shared>,
+}
+
+impl QueueReceiver {
+    /// Next input in arrival order, or `None` once every sender is gone and
+    /// the queue is drained.
+    pub async fn recv(&mut self) -> Option<MotionInput> {
+        let shared = &*self.shared;
+        loop {
+            let notified = shared.not_empty.notified();
+            {
+                let mut queue = shared.queue.lock().expect("queue lock poisoned");
+                if let Some(input) = queue.pop_front() {
+                    shared.set_depth(queue.len());
+                    shared.not_full.notify_one();
+                    return Some(input);
+                }
+                if shared.senders.load(Ordering::Acquire) == 0 {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// The queue's counters right now.
+    pub fn stats(&self) -> QueueStatsSnapshot {
+        self.shared.snapshot()
+    }
+}
+
+impl Drop for QueueReceiver {
+    fn drop(&mut self) {
+        // Wake every blocked producer so it sees the queue is closed.
+        self.shared.receiver_gone.store(true, Ordering::Release);
+        self.shared.not_full.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::motion_input::{InteractionType, UserInput};
+
+    fn like(post_id: &str, user_id: &str) -> MotionInput {
+        MotionInput::Interaction(Interaction::new(InteractionType::Like, post_id, user_id))
+    }
+
+    fn user(id: &str) -> MotionInput {
+        MotionInput::User(UserInput::new(id))
+    }
+
+    /// Every input left in the queue once the senders are gone, as
+    /// `(src_id, alpha)` for interactions and the id for users.
+    async fn drain(tx: QueueSender, mut rx: QueueReceiver) -> Vec<(String, Option<f32>)> {
+        drop(tx);
+        let mut inputs = Vec::new();
+        while let Some(input) = rx.recv().await {
+            inputs.push(match input {
+                MotionInput::Interaction(i) => (i.src_id, i.alpha),
+                MotionInput::User(u) => (u.id, None),
+                other => panic!("unexpected input {other:?}"),
+            });
+        }
+        inputs
+    }
+
+    fn queued(ids: &[&str]) -> Vec<(String, Option<f32>)> {
+        ids.iter().map(|id| (id.to_string(), None)).collect()
+    }
+
+    /// Whether `send` is still waiting for room after a short while.
+    async fn blocks(tx: &QueueSender, input: MotionInput) -> bool {
+        timeout(Duration::from_millis(20), tx.send(input)).await.is_err()
+    }
+
+    #[test]
+    fn policies_round_trip_through_their_names() {
+        for policy in ShedPolicy::ALL {
+            assert_eq!(policy.name().parse::<ShedPolicy>(), Ok(policy));
+        }
+        assert_eq!("drop-oldest".parse::<ShedPolicy>(), Ok(ShedPolicy::DropOldest));
+        assert!("never".parse::<ShedPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_room() {
+        let (tx, mut rx) = shed_queue(1, ShedPolicy::Block);
+        assert_eq!(tx.send(like("p1", "alice")).await, Ok(Admission::Queued));
+        assert!(blocks(&tx, like("p2", "alice")).await);
+
+        assert!(rx.recv().await.is_some());
+        assert_eq!(tx.send(like("p3", "alice")).await, Ok(Admission::Queued));
+        assert_eq!(tx.stats().shed(), 0);
+        assert_eq!(drain(tx, rx).await, queued(&["p3"]));
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_makes_room_by_dropping_an_interaction() {
+        let (tx, rx) = shed_queue(2, ShedPolicy::DropOldest);
+        tx.send(user("alice")).await.unwrap();
+        tx.send(like("p1", "alice")).await.unwrap();
+        assert_eq!(tx.send(like("p2", "alice")).await, Ok(Admission::QueuedDroppingOldest));
+        assert_eq!(tx.stats().dropped_oldest, 1);
+        assert_eq!(drain(tx, rx).await, queued(&["alice", "p2"]));
+    }
+
+    #[tokio::test]
+    async fn reject_new_drops_the_new_interaction() {
+        let (tx, rx) = shed_queue(1, ShedPolicy::RejectNew);
+        tx.send(like("p1", "alice")).await.unwrap();
+        assert_eq!(tx.send(like("p2", "alice")).await, Ok(Admission::Rejected));
+        assert_eq!(tx.stats().rejected, 1);
+        assert_eq!(drain(tx, rx).await, queued(&["p1"]));
+    }
+
+    #[tokio::test]
+    async fn coalesce_sums_matching_interactions_and_waits_otherwise() {
+        let (tx, rx) = shed_queue(1, ShedPolicy::Coalesce);
+        tx.send(like("p1", "alice")).await.unwrap();
+        assert_eq!(tx.send(like("p1", "alice")).await, Ok(Admission::Coalesced));
+        assert_eq!(tx.stats().coalesced, 1);
+        assert!(blocks(&tx, like("p2", "alice")).await);
+        let alpha = InteractionType::Like.dynamics().alpha;
+        assert_eq!(drain(tx, rx).await, [("p1".to_string(), Some(2.0 * alpha))]);
+    }
+
+    #[tokio::test]
+    async fn other_inputs_always_wait() {
+        for policy in ShedPolicy::ALL {
+            let (tx, rx) = shed_queue(1, policy);
+            tx.send(like("p1", "alice")).await.unwrap();
+            assert!(blocks(&tx, user("bob")).await, "{policy}");
+            assert_eq!(tx.stats().depth, 1);
+            drop(rx);
+            assert_eq!(tx.send(user("bob")).await, Err(QueueClosed));
+        }
+    }
+}
@@ -0,0 +1,977 @@
+//! A motion space partitioned by user id, with one task per shard.
+//!
+//! Users live on `hash(user_id) % shards` and posts live with their author.
+//! [`ShardedSpace`] routes each input to the shard that owns it and forwards
+//! shard-local inputs without waiting, so shards work in parallel.
+//!
+//! An interaction whose two sides live on different shards is applied with a
+//! checkout/commit round trip: the router copies the remote side out of its
+//! shard, the local shard applies the interaction with that copy as a
+//! temporary guest, and the updated copy is written back. The router does not
+//! route anything else until the commit is acknowledged, so every shard still
+//! observes inputs in the order they arrived.
+//!
+//! Interaction graph edges live with the user who made them, which is the
+//! shard that applied the interaction. Deleting a user or post erases the
+//! edges pointing at it on every other shard as well, and a deleted user's
+//! edges are sent to the shards holding the posts they engaged with so that
+//! their engagement counts are taken back.
+//!
+//! With clustering on, one task outside the shards owns the clusters. The
+//! router numbers every input it routes, each shard reports the coords its
+//! input moved under that number, and the cluster task applies the reports
+//! in number order, so clusters evolve exactly as on a single core.
+//!
+//! Outputs from different shards are interleaved in no particular order; the
+//! outputs of any one shard keep their order.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+
+use thiserror::Error;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::trace;
+
+use crate::cluster::Clusters;
+use crate::config::{ConfigError, MotionSpaceBuilder};
+use crate::embedding::{hash_str, TokenIndex};
+use crate::graph::Edge;
+use crate::metrics::Metrics;
+use crate::motion_core::{
+    input_span, CoreError, MotionEntry, MotionOutput, MotionSpace, PreparedInput, ScoredPost, ScoredUser,
+    SpaceStats,
+};
+use crate::motion_input::{InteractionTarget, MotionInput};
+
+/// Capacity of each shard's request channel.
+const SHARD_QUEUE: usize = 256;
+
+/// Why routing or shutting down a sharded space failed.
+#[derive(Debug, Error)]
+pub enum ShardError {
+    /// The task of the given shard ended early.
+    #[error("shard {0} stopped")]
+    ShardClosed(usize),
+
+    /// The cluster task ended early.
+    #[error("cluster task stopped")]
+    ClustersClosed,
+
+    /// The router itself turned the input away.
+    #[error(transparent)]
+    Core(#[from] CoreError),
+}
+
+enum ShardRequest {
+    Apply {
+        seq: u64,
+        prepared: PreparedInput,
+    },
+    Checkout {
+        id: String,
+        target: InteractionTarget,
+        reply: oneshot::Sender<Option<MotionEntry>>,
+    },
+    Commit {
+        entry: MotionEntry,
+        reply: oneshot::Sender<()>,
+    },
+    /// Applies an interaction input with `guest` temporarily entered. Replies
+    /// with the updated guest, or `None` if the interaction was rejected.
+    ApplyWithGuest {
+        seq: u64,
+        guest: MotionEntry,
+        input: MotionInput,
+        reply: oneshot::Sender<Option<MotionEntry>>,
+    },
+    NearestPosts {
+        coord: Vec<f32>,
+        k: usize,
+        exclude_author: Option<String>,
+        reply: oneshot::Sender<Result<Vec<ScoredPost>, CoreError>>,
+    },
+    NearestUsers {
+        coord: Vec<f32>,
+        k: usize,
+        exclude: HashSet<String>,
+        reply: oneshot::Sender<Result<Vec<ScoredUser>, CoreError>>,
+    },
+    /// Replies with a user this shard owns and everyone they have an edge to.
+    Followed {
+        user_id: String,
+        reply: oneshot::Sender<HashSet<String>>,
+    },
+    Stats {
+        reply: oneshot::Sender<SpaceStats>,
+    },
+    /// Erases graph edges into deleted users and posts owned elsewhere.
+    Forget {
+        users: Vec<String>,
+        posts: Vec<String>,
+    },
+    /// Replies with the post edges of a user this shard owns.
+    Engaged {
+        user_id: String,
+        reply: oneshot::Sender<Vec<(String, Edge)>>,
+    },
+    /// Takes a deleted user's engagement back from the posts held here and
+    /// resets them to their origin.
+    Retract {
+        user_id: String,
+        engaged: Vec<(String, Edge)>,
+    },
+}
+
+/// What one routed input did to cluster membership, in the order the core
+/// would have observed it.
+struct ClusterReport {
+    seq: u64,
+    moves: Vec<Membership>,
+}
+
+enum Membership {
+    Moved { user_id: String, coord: Vec<f32> },
+    Removed { user_id: String },
+}
+
+/// Routes inputs to the shard tasks of a partitioned [`MotionSpace`].
+pub struct ShardedSpace {
+    shards: Vec<Sender<ShardRequest>>,
+    handles: Vec<JoinHandle<MotionSpace>>,
+    /// The cluster task, when clustering is on.
+    clusters: Option<JoinHandle<Clusters>>,
+    /// Number of the next routed input.
+    seq: u64,
+    /// Post id -> author id, learned as posts are routed. A post lives on
+    /// its author's shard.
+    post_authors: HashMap<String, String>,
+    out: Sender<MotionOutput>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl ShardedSpace {
+    /// Builds `shards` empty shards from the same configuration.
+    pub fn from_builder(
+        builder: MotionSpaceBuilder,
+        shards: usize,
+        out: Sender<MotionOutput>,
+    ) -> Result<Self, ConfigError> {
+        let space = builder.build()?;
+        Ok(Self::split(space, shards, out))
+    }
+
+    /// Partitions an existing space (e.g. a loaded snapshot) across `shards`
+    /// shards, each keeping the original configuration.
+    pub fn split(space: MotionSpace, shards: usize, out: Sender<MotionOutput>) -> Self {
+        Self::spawn(space, shards, out, None)
+    }
+
+    /// [`split`](Self::split) with every shard recording into `metrics`.
+    pub fn split_observed(
+        space: MotionSpace,
+        shards: usize,
+        out: Sender<MotionOutput>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        metrics.track_space(&space);
+        Self::spawn(space, shards, out, Some(metrics))
+    }
+
+    fn spawn(
+        mut space: MotionSpace,
+        shards: usize,
+        out: Sender<MotionOutput>,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Self {
+        let shards = shards.max(1);
+        let entries = std::mem::take(&mut space.entries);
+        let graph = std::mem::take(&mut space.graph);
+        if let Some(index) = space.user_posts.as_mut() {
+            index.clear();
+        }
+
+        let tokens = space.token_index.as_mut().map(|index| std::mem::replace(index, TokenIndex::new(index.dim())));
+        let (reports, clusters) = match space.clusters.take() {
+            Some(clusters) => {
+                let (tx, rx) = mpsc::channel(SHARD_QUEUE);
+                (Some(tx), Some(tokio::spawn(cluster_loop(clusters, rx, out.clone(), metrics.clone()))))
+            }
+            None => (None, None),
+        };
+        let mut spaces = vec![space; shards];
+        // Edges live with the acting user, whose shard applies their interactions.
+        for (space, graph) in spaces.iter_mut().zip(graph.partition(shards, |user| shard_index(user, shards))) {
+            space.graph = graph;
+        }
+        // Token counts follow their post to its author's shard, which is
+        // where the post is deleted or edited. Whatever is not tracked per
+        // post stays on the first shard, so merging does not double it.
+        if let Some(mut tokens) = tokens {
+            let author_shard: HashMap<&str, usize> = entries
+                .iter()
+                .filter_map(|e| match e {
+                    MotionEntry::Post(p) => Some((p.id.as_str(), shard_index(&p.author_id, shards))),
+                    MotionEntry::User(_) => None,
+                })
+                .collect();
+            for (shard, space) in spaces.iter_mut().enumerate().skip(1) {
+                space.token_index = Some(tokens.take_posts(|post| author_shard.get(post) == Some(&shard)));
+            }
+            spaces[0].token_index = Some(tokens);
+        }
+        let mut post_authors = HashMap::new();
+        for entry in entries {
+            let shard = match &entry {
+                MotionEntry::User(u) => shard_index(&u.id, shards),
+                MotionEntry::Post(p) => {
+                    post_authors.insert(p.id.clone(), p.author_id.clone());
+                    shard_index(&p.author_id, shards)
+                }
+            };
+            spaces[shard].enter(entry);
+        }
+
+        let mut senders = Vec::with_capacity(shards);
+        let mut handles = Vec::with_capacity(shards);
+        for space in spaces {
+            let (tx, rx) = mpsc::channel(SHARD_QUEUE);
+            senders.push(tx);
+            handles.push(tokio::spawn(shard_loop(space, rx, out.clone(), metrics.clone(), reports.clone())));
+        }
+
+        Self {
+            shards: senders,
+            handles,
+            clusters,
+            seq: 0,
+            post_authors,
+            out,
+            metrics,
+        }
+    }
+
+    /// Number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The shard that owns `user_id` and their posts.
+    pub fn shard_of(&self, user_id: &str) -> usize {
+        shard_index(user_id, self.shards.len())
+    }
+
+    /// Routes every input from `rx` until it closes.
+    pub async fn run<T>(&mut self, mut rx: Receiver<T>) -> Result<(), ShardError>
+    where
+        T: Into<PreparedInput>,
+    {
+        while let Some(input) = rx.recv().await {
+            self.apply(input).await?;
+        }
+        Ok(())
+    }
+
+    /// Routes one input. Returns once it is queued on its shard, or once a
+    /// cross-shard interaction has been fully committed.
+    pub async fn apply(&mut self, input: impl Into<PreparedInput>) -> Result<(), ShardError> {
+        let prepared = input.into();
+        let shard = match &prepared.input {
+            MotionInput::User(user) => self.shard_of(&user.id),
+            MotionInput::Post(post) => {
+                // Another author's post with this id may live on another shard.
+                if self.post_authors.contains_key(&post.id) {
+                    let error = CoreError::DuplicatePost { post_id: post.id.clone() };
+                    return self.reject(error, prepared.input).await;
+                }
+                self.post_authors.insert(post.id.clone(), post.user_id.clone());
+                self.shard_of(&post.user_id)
+            }
+            MotionInput::DeleteUser(user) => {
+                let shard = self.shard_of(&user.id);
+                let mut posts = Vec::new();
+                self.post_authors.retain(|post_id, author| {
+                    let authored = *author == user.id;
+                    if authored {
+                        posts.push(post_id.clone());
+                    }
+                    !authored
+                });
+                self.forget(shard, vec![user.id.clone()], posts).await?;
+                self.retract(shard, &user.id).await?;
+                shard
+            }
+            MotionInput::DeletePost(post) => {
+                let shard = self.post_shard(&post.id);
+                self.post_authors.remove(&post.id);
+                self.forget(shard, Vec::new(), vec![post.id.clone()]).await?;
+                shard
+            }
+            MotionInput::EditPost(edit) => self.post_shard(&edit.id),
+            MotionInput::Interaction(interaction) => {
+                let (local, remote) = match interaction.interaction_type.target() {
+                    InteractionTarget::Post => (
+                        self.shard_of(&interaction.dst_id),
+                        self.post_authors.get(&interaction.src_id).map(|author| self.shard_of(author)),
+                    ),
+                    InteractionTarget::User => (
+                        self.shard_of(&interaction.src_id),
+                        Some(self.shard_of(&interaction.dst_id)),
+                    ),
+                };
+                match remote {
+                    Some(remote) if remote != local => {
+                        return self.apply_cross_shard(local, remote, prepared.input).await;
+                    }
+                    _ => local,
+                }
+            }
+        };
+        let seq = self.next_seq();
+        self.send(shard, ShardRequest::Apply { seq, prepared }).await
+    }
+
+    async fn apply_cross_shard(
+        &mut self,
+        local: usize,
+        remote: usize,
+        input: MotionInput,
+    ) -> Result<(), ShardError> {
+        let MotionInput::Interaction(interaction) = &input else {
+            unreachable!("only interactions span shards");
+        };
+        let target = interaction.interaction_type.target();
+        let remote_id = match target {
+            InteractionTarget::Post => interaction.src_id.clone(),
+            InteractionTarget::User => interaction.dst_id.clone(),
+        };
+
+        trace!(local, remote, id = %remote_id, "checking out entry from remote shard");
+        let (reply, rx) = oneshot::channel();
+        self.send(remote, ShardRequest::Checkout { id: remote_id.clone(), target, reply })
+            .await?;
+        let Some(guest) = rx.await.map_err(|_| ShardError::ShardClosed(remote))? else {
+            let error = match target {
+                InteractionTarget::Post => CoreError::PostNotFound { post_id: remote_id },
+                InteractionTarget::User => CoreError::UserNotFound { user_id: remote_id },
+            };
+            return self.reject(error, input).await;
+        };
+
+        let (reply, rx) = oneshot::channel();
+        let seq = self.next_seq();
+        self.send(local, ShardRequest::ApplyWithGuest { seq, guest, input, reply })
+            .await?;
+        if let Some(updated) = rx.await.map_err(|_| ShardError::ShardClosed(local))? {
+            let (reply, rx) = oneshot::channel();
+            self.send(remote, ShardRequest::Commit { entry: updated, reply })
+                .await?;
+            rx.await.map_err(|_| ShardError::ShardClosed(remote))?;
+        }
+        Ok(())
+    }
+
+    /// Nearest posts to a user's coord across all shards, excluding their own.
+    pub async fn recommend_posts(&self, user_id: &str, k: usize) -> Result<Vec<ScoredPost>, ShardError> {
+        let coord = self.user_coord(user_id).await?;
+        let mut pending = Vec::with_capacity(self.shards.len());
+        for shard in 0..self.shards.len() {
+            let (reply, rx) = oneshot::channel();
+            self.send(shard, ShardRequest::NearestPosts {
+                coord: coord.clone(),
+                k,
+                exclude_author: Some(user_id.to_string()),
+                reply,
+            })
+            .await?;
+            pending.push((shard, rx));
+        }
+        let mut merged = Vec::new();
+        for (shard, rx) in pending {
+            merged.extend(rx.await.map_err(|_| ShardError::ShardClosed(shard))??);
+        }
+        merged.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        merged.truncate(k);
+        Ok(merged)
+    }
+
+    /// Who-to-follow across all shards, as
+    /// [`MotionSpace::recommend_users`] ranks it on a single core.
+    pub async fn recommend_users(&self, user_id: &str, k: usize) -> Result<Vec<ScoredUser>, ShardError> {
+        let coord = self.user_coord(user_id).await?;
+        // The user's edges live on their own shard.
+        let owner = self.shard_of(user_id);
+        let (reply, rx) = oneshot::channel();
+        self.send(owner, ShardRequest::Followed { user_id: user_id.to_string(), reply })
+            .await?;
+        let exclude = rx.await.map_err(|_| ShardError::ShardClosed(owner))?;
+
+        let mut pending = Vec::with_capacity(self.shards.len());
+        for shard in 0..self.shards.len() {
+            let (reply, rx) = oneshot::channel();
+            self.send(shard, ShardRequest::NearestUsers {
+                coord: coord.clone(),
+                k,
+                exclude: exclude.clone(),
+                reply,
+            })
+            .await?;
+            pending.push((shard, rx));
+        }
+        let mut merged = Vec::new();
+        for (shard, rx) in pending {
+            merged.extend(rx.await.map_err(|_| ShardError::ShardClosed(shard))??);
+        }
+        merged.sort_by(|a, b| b.score.total_cmp(&a.score));
+        merged.truncate(k);
+        Ok(merged)
+    }
+
+    /// The coord of `user_id`, copied from their shard.
+    async fn user_coord(&self, user_id: &str) -> Result<Vec<f32>, ShardError> {
+        let shard = self.shard_of(user_id);
+        let (reply, rx) = oneshot::channel();
+        self.send(shard, ShardRequest::Checkout {
+            id: user_id.to_string(),
+            target: InteractionTarget::User,
+            reply,
+        })
+        .await?;
+        let Some(MotionEntry::User(user)) = rx.await.map_err(|_| ShardError::ShardClosed(shard))? else {
+            return Err(CoreError::UserNotFound { user_id: user_id.to_string() }.into());
+        };
+        let coord = user.coord.ok_or_else(|| CoreError::CoordNotLoaded {
+            user_id: user_id.to_string(),
+        })?;
+        Ok(coord.data)
+    }
+
+    /// Stats of the whole space, summed over the shards.
+    pub async fn stats(&self) -> Result<SpaceStats, ShardError> {
+        let mut total = SpaceStats::default();
+        let mut motion_sum = 0.0;
+        for shard in 0..self.shards.len() {
+            let (reply, rx) = oneshot::channel();
+            self.send(shard, ShardRequest::Stats { reply }).await?;
+            let stats = rx.await.map_err(|_| ShardError::ShardClosed(shard))?;
+            total.dim = stats.dim;
+            total.users += stats.users;
+            total.unplaced_users += stats.unplaced_users;
+            total.posts += stats.posts;
+            // Posts live with their author, so no author spans two shards.
+            total.authors += stats.authors;
+            total.max_motion = total.max_motion.max(stats.max_motion);
+            motion_sum += stats.mean_motion * stats.users as f32;
+        }
+        if total.users > 0 {
+            total.mean_motion = motion_sum / total.users as f32;
+        }
+        Ok(total)
+    }
+
+    /// Waits for every shard to drain and merges them back into one space.
+    pub async fn shutdown(self) -> Result<MotionSpace, ShardError> {
+        drop(self.shards);
+        let mut merged: Option<MotionSpace> = None;
+        for (shard, handle) in self.handles.into_iter().enumerate() {
+            let mut space = handle.await.map_err(|_| ShardError::ShardClosed(shard))?;
+            match merged.as_mut() {
+                None => merged = Some(space),
+                Some(target) => {
+                    for entry in std::mem::take(&mut space.entries) {
+                        target.enter(entry);
+                    }
+                    target.graph.merge(space.graph);
+                    if let (Some(ours), Some(theirs)) = (target.token_index.as_mut(), space.token_index) {
+                        ours.merge(theirs);
+                    }
+                }
+            }
+        }
+        let mut merged = merged.expect("a sharded space has at least one shard");
+        // Shards held the last senders to the cluster task, so it is done too.
+        if let Some(handle) = self.clusters {
+            merged.clusters = Some(handle.await.map_err(|_| ShardError::ClustersClosed)?);
+        }
+        Ok(merged)
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        self.seq += 1;
+        self.seq - 1
+    }
+
+    /// Reports an input the router turned away without any shard seeing it.
+    async fn reject(&self, error: CoreError, input: MotionInput) -> Result<(), ShardError> {
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_input(&input);
+            metrics.observe_rejected(&error);
+        }
+        self.out
+            .send(MotionOutput::Rejected { error, input })
+            .await
+            .map_err(|_| CoreError::ChannelError.into())
+    }
+
+    fn post_shard(&self, post_id: &str) -> usize {
+        // Unknown posts go anywhere; that shard rejects them as not found.
+        self.post_authors.get(post_id).map_or(0, |author| self.shard_of(author))
+    }
+
+    /// Tells every shard but `owner` to drop its edges into `users` and
+    /// `posts`; `owner` erases them itself when it applies the delete.
+    async fn forget(&self, owner: usize, users: Vec<String>, posts: Vec<String>) -> Result<(), ShardError> {
+        for shard in (0..self.shards.len()).filter(|&shard| shard != owner) {
+            self.send(shard, ShardRequest::Forget { users: users.clone(), posts: posts.clone() })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Takes the engagement `user_id` added to posts on other shards back.
+    /// Their edges live on `owner`, which retracts its own posts itself when
+    /// it applies the delete.
+    async fn retract(&self, owner: usize, user_id: &str) -> Result<(), ShardError> {
+        let (reply, rx) = oneshot::channel();
+        self.send(owner, ShardRequest::Engaged { user_id: user_id.to_string(), reply })
+            .await?;
+        let engaged = rx.await.map_err(|_| ShardError::ShardClosed(owner))?;
+        if engaged.is_empty() {
+            return Ok(());
+        }
+        for shard in (0..self.shards.len()).filter(|&shard| shard != owner) {
+            self.send(shard, ShardRequest::Retract { user_id: user_id.to_string(), engaged: engaged.clone() })
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn send(&self, shard: usize, request: ShardRequest) -> Result<(), ShardError> {
+        self.shards[shard]
+            .send(request)
+            .await
+            .map_err(|_| ShardError::ShardClosed(shard))
+    }
+}
+
+fn shard_index(user_id: &str, shards: usize) -> usize {
+    (hash_str(user_id) % shards as u64) as usize
+}
+
+/// Applies cluster reports in routing order. Reports that arrive early wait
+/// until every input routed before them has been reported.
+async fn cluster_loop(
+    mut clusters: Clusters,
+    mut rx: Receiver<ClusterReport>,
+    out: Sender<MotionOutput>,
+    metrics: Option<Arc<Metrics>>,
+) -> Clusters {
+    let mut next = 0;
+    let mut waiting = BTreeMap::new();
+    while let Some(report) = rx.recv().await {
+        waiting.insert(report.seq, report.moves);
+        while let Some(moves) = waiting.remove(&next) {
+            next += 1;
+            for membership in moves {
+                let change = match membership {
+                    Membership::Moved { user_id, coord } => clusters.observe(&user_id, &coord),
+                    Membership::Removed { user_id } => clusters.remove(&user_id),
+                };
+                let Some(change) = change else { continue };
+                let output = MotionOutput::ClusterChanged(change);
+                if let Some(metrics) = &metrics {
+                    metrics.observe_output(&output);
+                }
+                // Keep clustering for the snapshot even if nobody listens.
+                let _ = out.send(output).await;
+            }
+        }
+    }
+    clusters
+}
+
+/// The cluster updates `outputs` call for: users the interactions moved, at
+/// their new coords, and removed users. `guest` is a user applied here as a
+/// guest, already taken back out of `space`.
+fn memberships(space: &MotionSpace, outputs: &[MotionOutput], guest: Option<&MotionEntry>) -> Vec<Membership> {
+    let coord_of = |id: &str| {
+        let user = match guest {
+            Some(MotionEntry::User(u)) if u.id == id => Some(u),
+            _ => space.user(id),
+        };
+        user?.coord.as_ref().map(|c| c.data.clone())
+    };
+    let mut moves = Vec::new();
+    for output in outputs {
+        match output {
+            MotionOutput::InteractionApplied(result) => {
+                for id in result.moved_users() {
+                    if let Some(coord) = coord_of(id) {
+                        moves.push(Membership::Moved { user_id: id.to_string(), coord });
+                    }
+                }
+            }
+            MotionOutput::Removed(MotionEntry::User(user)) => {
+                moves.push(Membership::Removed { user_id: user.id.clone() });
+            }
+            _ => {}
+        }
+    }
+    moves
+}
+
+fn entry_position(space: &MotionSpace, id: &str, target: InteractionTarget) -> Option<usize> {
+    space.entries.iter().position(|e| match (e, target) {
+        (MotionEntry::User(u), InteractionTarget::User) => u.id == id,
+        (MotionEntry::Post(p), InteractionTarget::Post) => p.id == id,
+        _ => false,
+    })
+}
+
+fn target_of(entry: &MotionEntry) -> InteractionTarget {
+    match entry {
+        MotionEntry::User(_) => InteractionTarget::User,
+        MotionEntry::Post(_) => InteractionTarget::Post,
+    }
+}
+
+async fn shard_loop(
+    mut space: MotionSpace,
+    mut rx: Receiver<ShardRequest>,
+    out: Sender<MotionOutput>,
+    metrics: Option<Arc<Metrics>>,
+    reports: Option<Sender<ClusterReport>>,
+) -> MotionSpace {
+    while let Some(request) = rx.recv().await {
+        let outputs = match request {
+            ShardRequest::Apply { seq, prepared } => {
+                let outputs = space.process_observed(prepared, metrics.as_deref());
+                if let Some(reports) = &reports {
+                    let moves = memberships(&space, &outputs, None);
+                    let _ = reports.send(ClusterReport { seq, moves }).await;
+                }
+                outputs
+            }
+            ShardRequest::Checkout { id, target, reply } => {
+                let entry = entry_position(&space, &id, target).map(|idx| space.entries[idx].clone());
+                let _ = reply.send(entry);
+                continue;
+            }
+            ShardRequest::Commit { entry, reply } => {
+                if let Some(idx) = entry_position(&space, entry.id(), target_of(&entry)) {
+                    space.entries[idx] = entry;
+                }
+                let _ = reply.send(());
+                continue;
+            }
+            ShardRequest::ApplyWithGuest { seq, guest, input, reply } => {
+                let MotionInput::Interaction(interaction) = input.clone() else {
+                    unreachable!("guests are only used for interactions");
+                };
+                let started = Instant::now();
+                let span = input_span(&input);
+                let entered = span.enter();
+                let guest_id = guest.id().to_string();
+                let guest_target = target_of(&guest);
+                // Pushed directly so the guest never enters this shard's indexes.
+                space.entries.push(guest);
+                let result = space.interaction_outputs(interaction);
+                let updated = entry_position(&space, &guest_id, guest_target)
+                    .map(|idx| space.entries.remove(idx));
+                let (outputs, updated) = match result {
+                    Ok(outputs) => (outputs, updated),
+                    Err(error) => (vec![MotionOutput::Rejected { error, input: input.clone() }], None),
+                };
+                if let Some(metrics) = &metrics {
+                    metrics.observe(&input, &outputs, started.elapsed());
+                }
+                drop(entered);
+                // Reported here, where the guest's new coord is known.
+                if let Some(reports) = &reports {
+                    let moves = memberships(&space, &outputs, updated.as_ref());
+                    let _ = reports.send(ClusterReport { seq, moves }).await;
+                }
+                let _ = reply.send(updated);
+                outputs
+            }
+            ShardRequest::NearestPosts { coord, k, exclude_author, reply } => {
+                let _ = reply.send(space.nearest_posts(&coord, k, exclude_author.as_deref()));
+                continue;
+            }
+            ShardRequest::NearestUsers { coord, k, exclude, reply } => {
+                let _ = reply.send(space.nearest_users(&coord, k, &exclude));
+                continue;
+            }
+            ShardRequest::Followed { user_id, reply } => {
+                let _ = reply.send(space.followed(&user_id));
+                continue;
+            }
+            ShardRequest::Stats { reply } => {
+                let _ = reply.send(space.stats());
+                continue;
+            }
+            ShardRequest::Forget { users, posts } => {
+                for user in &users {
+                    space.graph.remove_user(user);
+                }
+                for post in &posts {
+                    space.graph.remove_post(post);
+                }
+                continue;
+            }
+            ShardRequest::Engaged { user_id, reply } => {
+                let _ = reply.send(space.engaged(&user_id));
+                continue;
+            }
+            ShardRequest::Retract { user_id, engaged } => {
+                space.retract_engagement(&user_id, &engaged);
+                continue;
+            }
+        };
+        for output in outputs {
+            if out.send(output).await.is_err() {
+                return space;
+            }
+        }
+    }
+    space
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::cluster::ClusterConfig;
+    use crate::motion_core::Engagement;
+    use crate::motion_input::{DeleteInput, EditPostInput, Interaction, InteractionType, PostInput, UserInput};
+
+    /// Applies `inputs` to `space` split across `shards` shards and merges
+    /// the result.
+    async fn run_sharded(
+        space: MotionSpace,
+        inputs: &[MotionInput],
+        shards: usize,
+    ) -> (MotionSpace, Vec<MotionOutput>) {
+        let (out, mut rx) = mpsc::channel(64);
+        let drain = tokio::spawn(async move {
+            let mut outputs = Vec::new();
+            while let Some(output) = rx.recv().await {
+                outputs.push(output);
+            }
+            outputs
+        });
+        let mut sharded = ShardedSpace::split(space, shards, out);
+        for input in inputs {
+            sharded.apply(input.clone()).await.unwrap();
+        }
+        let space = sharded.shutdown().await.unwrap();
+        (space, drain.await.unwrap())
+    }
+
+    fn engagements(space: &MotionSpace) -> BTreeMap<String, Engagement> {
+        space
+            .entries
+            .iter()
+            .filter_map(|e| match e {
+                MotionEntry::Post(p) => Some((p.id.clone(), p.engagement)),
+                MotionEntry::User(_) => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn deleting_a_user_takes_back_engagement_on_every_shard() {
+        let users: Vec<String> = (0..8).map(|i| format!("user-{i}")).collect();
+        let mut inputs = Vec::new();
+        for (i, user) in users.iter().enumerate() {
+            inputs.push(MotionInput::Post(PostInput::new(format!("p{i}"), user, format!("topic {i} rust"))));
+        }
+        for (i, user) in users.iter().enumerate() {
+            for j in 0..users.len() {
+                let kind = if (i + j) % 3 == 0 { InteractionType::Dislike } else { InteractionType::Like };
+                inputs.push(MotionInput::Interaction(Interaction::new(kind, format!("p{j}"), user)));
+            }
+        }
+        inputs.push(MotionInput::DeleteUser(DeleteInput::new("user-3")));
+
+        let mut single = MotionSpace::new(16);
+        for input in &inputs {
+            let _ = single.process(input.clone());
+        }
+        let expected = engagements(&single);
+        assert!(expected.values().all(|e| e.positive + e.negative == 6), "{expected:?}");
+        for shards in [1, 4] {
+            let (space, _) = run_sharded(MotionSpace::new(16), &inputs, shards).await;
+            assert_eq!(engagements(&space), expected, "{shards} shards");
+            // user-3 engaged with every post, so none keeps any drift.
+            assert!(space.entries.iter().all(|e| match e {
+                MotionEntry::Post(p) => p.coord.data == p.origin.data,
+                MotionEntry::User(_) => true,
+            }));
+        }
+    }
+
+    /// Everything a run leaves behind, in a canonical order and rounded so
+    /// that runs can be compared as strings.
+    fn fingerprint(space: &MotionSpace) -> String {
+        let round = |v: &[f32]| v.iter().map(|x| format!("{x:.4}")).collect::<Vec<_>>().join(",");
+        let mut entries: Vec<String> = space
+            .entries
+            .iter()
+            .map(|e| match e {
+                MotionEntry::User(u) => {
+                    let coord = u.coord.as_ref().map(|c| round(&c.data)).unwrap_or_default();
+                    format!("user {} [{coord}] {:.4}", u.id, u.motion)
+                }
+                MotionEntry::Post(p) => {
+                    format!("post {} by {} [{}] {:?}", p.id, p.author_id, round(&p.coord.data), p.engagement)
+                }
+            })
+            .collect();
+        entries.sort();
+        let mut edges: Vec<String> = space
+            .graph
+            .edges()
+            .iter()
+            .map(|r| {
+                let e = &r.edge;
+                format!("{:?} {} -> {} {} {} {:.4}", r.target, r.src, r.dst, e.count, e.repelled, e.weight)
+            })
+            .collect();
+        edges.sort();
+        let mut out = entries.join("\n") + "\n" + &edges.join("\n");
+        for cluster in space.clusters.as_ref().map(Clusters::summaries).unwrap_or_default() {
+            let centroid = round(&cluster.centroid);
+            let _ = write!(out, "\ncluster {} [{centroid}] {} {:?}", cluster.id, cluster.absorbed, cluster.members);
+        }
+        out
+    }
+
+    fn output_counts(outputs: &[MotionOutput]) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for output in outputs {
+            let kind = match output {
+                MotionOutput::Entered(_) => "entered",
+                MotionOutput::Updated(_) => "updated",
+                MotionOutput::Removed(_) => "removed",
+                MotionOutput::InteractionApplied(_) => "applied",
+                MotionOutput::ClusterChanged(_) => "cluster_changed",
+                MotionOutput::Rejected { error, .. } => error.kind(),
+            };
+            *counts.entry(kind).or_default() += 1;
+        }
+        counts
+    }
+
+    /// A seeded mix of every input kind, including ones the space rejects.
+    fn random_inputs(seed: u64, count: usize) -> Vec<MotionInput> {
+        const WORDS: [&str; 8] = ["rust", "async", "garden", "tomato", "chess", "opening", "jazz", "piano"];
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut inputs = Vec::new();
+        let mut posts = 0;
+        for _ in 0..count {
+            let user = format!("user-{}", rng.random_range(0..16));
+            let post = format!("post-{}", rng.random_range(0..posts.max(1)));
+            let input = match rng.random_range(0..100) {
+                0..20 => {
+                    posts += 1;
+                    let text = (0..4).map(|_| WORDS[rng.random_range(0..WORDS.len())]).collect::<Vec<_>>().join(" ");
+                    MotionInput::Post(PostInput::new(format!("post-{}", posts - 1), user, text))
+                }
+                20..25 => MotionInput::User(UserInput::new(user)),
+                25..70 => {
+                    const KINDS: [InteractionType; 5] = [
+                        InteractionType::Like,
+                        InteractionType::Share,
+                        InteractionType::Reply,
+                        InteractionType::Dislike,
+                        InteractionType::Hide,
+                    ];
+                    let kind = KINDS[rng.random_range(0..KINDS.len())];
+                    MotionInput::Interaction(Interaction::new(kind, post, user))
+                }
+                70..90 => {
+                    let target = format!("user-{}", rng.random_range(0..16));
+                    MotionInput::Interaction(Interaction::new(InteractionType::Follow, user, target))
+                }
+                90..94 => MotionInput::EditPost(EditPostInput::new(post, WORDS[rng.random_range(0..WORDS.len())])),
+                94..97 => MotionInput::DeletePost(DeleteInput::new(post)),
+                _ => MotionInput::DeleteUser(DeleteInput::new(user)),
+            };
+            inputs.push(input);
+        }
+        inputs
+    }
+
+    #[tokio::test]
+    async fn any_shard_count_matches_a_single_core() {
+        let inputs = random_inputs(42, 600);
+        let space = || {
+            MotionSpace::builder()
+                .dim(32)
+                .clusters(ClusterConfig { k: 4, batch_size: 8 })
+                .build()
+                .unwrap()
+        };
+        let mut single = space();
+        for input in &inputs {
+            let _ = single.process(input.clone());
+        }
+        let expected = fingerprint(&single);
+        assert!(single.clusters.as_ref().is_some_and(|c| c.len() == 4));
+
+        let (_, outputs) = run_sharded(space(), &inputs, 1).await;
+        let counts = output_counts(&outputs);
+        assert!(counts["applied"] > 100 && counts["cluster_changed"] > 0, "{counts:?}");
+        for shards in [1, 2, 3, 8] {
+            let (sharded, outputs) = run_sharded(space(), &inputs, shards).await;
+            assert_eq!(fingerprint(&sharded), expected, "{shards} shards");
+            assert_eq!(output_counts(&outputs), counts, "{shards} shards");
+        }
+    }
+
+    #[tokio::test]
+    async fn sharded_user_recommendations_match_a_single_core() {
+        let mut single = MotionSpace::new(32);
+        for input in random_inputs(7, 400) {
+            let _ = single.process(input);
+        }
+        let follows = single.graph.edges().iter().filter(|r| matches!(r.target, InteractionTarget::User)).count();
+        assert!(follows > 20, "{follows}");
+        let (out, _rx) = mpsc::channel(64);
+        let sharded = ShardedSpace::split(single.clone(), 4, out);
+
+        // Exact ties may come back in either order, so the full lists are
+        // compared sorted and the top k by score alone.
+        let scores = |users: &[ScoredUser]| users.iter().map(|u| format!("{:.5}", u.score)).collect::<Vec<_>>();
+        let sorted = |users: &[ScoredUser]| {
+            let mut ids: Vec<_> = users.iter().map(|u| format!("{} {:.5}", u.user_id, u.score)).collect();
+            ids.sort();
+            ids
+        };
+        let mut compared = 0;
+        for entry in &single.entries {
+            let MotionEntry::User(user) = entry else { continue };
+            let Ok(all) = single.recommend_users(&user.id, usize::MAX) else {
+                let err = sharded.recommend_users(&user.id, 3).await.unwrap_err();
+                assert!(matches!(err, ShardError::Core(CoreError::CoordNotLoaded { .. })), "{err}");
+                continue;
+            };
+            let got = sharded.recommend_users(&user.id, usize::MAX).await.unwrap();
+            assert_eq!(sorted(&got), sorted(&all), "{}", user.id);
+            let top = single.recommend_users(&user.id, 3).unwrap();
+            let got = sharded.recommend_users(&user.id, 3).await.unwrap();
+            assert_eq!(scores(&got), scores(&top), "{}", user.id);
+            compared += 1;
+        }
+        assert!(compared > 10, "{compared}");
+        let err = sharded.recommend_users("nobody", 3).await.unwrap_err();
+        assert!(matches!(err, ShardError::Core(CoreError::UserNotFound { .. })), "{err}");
+    }
+}
@@ -0,0 +1,333 @@
+//! Vectorized kernels over `f32` slices.
+//!
+//! [`dot`], [`squared_distance`] and [`zip_map`] pick the fastest
+//! implementation the CPU supports at runtime: AVX with FMA on x86_64,
+//! otherwise a portable version the compiler can vectorize for whatever
+//! target it builds for (the reductions keep eight independent accumulators
+//! for that). The `*_scalar` versions are the plain sequential loops, kept
+//! as the reference.
+//!
+//! The implementations add in different orders, so results agree only up to
+//! float rounding (a few ULPs for 128-dim inputs), not bit for bit.
+//!
+//! Every function here expects slices of equal length; callers check that
+//! and report [`MathError::DimensionMismatch`](crate::math::MathError).
+
+/// Lanes of the portable implementation.
+const LANES: usize = 8;
+
+/// Dot product of `a` and `b`.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+    #[cfg(target_arch = "x86_64")]
+    if has_avx_fma() {
+        // SAFETY: the CPU supports the features `dot_avx` is compiled for.
+        return unsafe { x86::dot_avx(a, b) };
+    }
+    dot_chunked(a, b)
+}
+
+/// Squared Euclidean distance between `a` and `b`.
+pub fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+    #[cfg(target_arch = "x86_64")]
+    if has_avx_fma() {
+        // SAFETY: as in `dot`.
+        return unsafe { x86::squared_distance_avx(a, b) };
+    }
+    squared_distance_chunked(a, b)
+}
+
+/// [`dot`] as a plain loop.
+pub fn dot_scalar(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// [`squared_distance`] as a plain loop.
+pub fn squared_distance_scalar(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| {
+            let d = x - y;
+            d * d
+        })
+        .sum()
+}
+
+/// The portable [`dot`].
+pub fn dot_chunked(a: &[f32], b: &[f32]) -> f32 {
+    reduce_chunked(a, b, |x, y| x * y)
+}
+
+/// The portable [`squared_distance`].
+pub fn squared_distance_chunked(a: &[f32], b: &[f32]) -> f32 {
+    reduce_chunked(a, b, |x, y| {
+        let d = x - y;
+        d * d
+    })
+}
+
+/// `out[i] = f(a[i], b[i])`. `f` is inlined into a copy of the loop
+/// compiled for AVX when the CPU has it, so simple element-wise closures
+/// vectorize at full width.
+pub fn zip_map<F>(a: &[f32], b: &[f32], f: F) -> Vec<f32>
+where
+    F: FnMut(f32, f32) -> f32,
+{
+    debug_assert_eq!(a.len(), b.len());
+    #[cfg(target_arch = "x86_64")]
+    if has_avx_fma() {
+        // SAFETY: as in `dot`.
+        return unsafe { x86::zip_map_avx(a, b, f) };
+    }
+    zip_map_portable(a, b, f)
+}
+
+#[inline(always)]
+fn zip_map_portable<F>(a: &[f32], b: &[f32], mut f: F) -> Vec<f32>
+where
+    F: FnMut(f32, f32) -> f32,
+{
+    // Exact-size zip, so `collect` allocates once and the loop vectorizes.
+    a.iter().zip(b).map(|(&x, &y)| f(x, y)).collect()
+}
+
+#[inline(always)]
+fn reduce_chunked(a: &[f32], b: &[f32], term: impl Fn(f32, f32) -> f32) -> f32 {
+    let mut acc = [0.0f32; LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let tail: f32 = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(&x, &y)| term(x, y))
+        .sum();
+    for (x, y) in a_chunks.zip(b_chunks) {
+        for i in 0..LANES {
+            acc[i] += term(x[i], y[i]);
+        }
+    }
+    // Pairwise, like a horizontal add.
+    let mut width = LANES;
+    while width > 1 {
+        width /= 2;
+        for i in 0..width {
+            acc[i] += acc[i + width];
+        }
+    }
+    acc[0] + tail
+}
+
+#[cfg(target_arch = "x86_64")]
+fn has_avx_fma() -> bool {
+    // The std macros cache the CPUID result after the first call.
+    std::arch::is_x86_feature_detected!("avx") && std::arch::is_x86_feature_detected!("fma")
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    /// Floats per AVX register.
+    const WIDTH: usize = 8;
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX and FMA, and `a` and `b` must be equally long.
+    #[target_feature(enable = "avx,fma")]
+    pub(super) unsafe fn dot_avx(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let split = n - n % (2 * WIDTH);
+        // Two accumulators hide the FMA latency.
+        let (mut acc0, mut acc1) = (_mm256_setzero_ps(), _mm256_setzero_ps());
+        let mut i = 0;
+        while i < split {
+            // SAFETY: `i + 2 * WIDTH <= split <= n`, so both loads are in bounds.
+            unsafe {
+                let (a0, b0) = (_mm256_loadu_ps(a.as_ptr().add(i)), _mm256_loadu_ps(b.as_ptr().add(i)));
+                let (a1, b1) = (
+                    _mm256_loadu_ps(a.as_ptr().add(i + WIDTH)),
+                    _mm256_loadu_ps(b.as_ptr().add(i + WIDTH)),
+                );
+                acc0 = _mm256_fmadd_ps(a0, b0, acc0);
+                acc1 = _mm256_fmadd_ps(a1, b1, acc1);
+            }
+            i += 2 * WIDTH;
+        }
+        let tail: f32 = a[split..n].iter().zip(&b[split..n]).map(|(x, y)| x * y).sum();
+        horizontal_sum(_mm256_add_ps(acc0, acc1)) + tail
+    }
+
+    /// # Safety
+    ///
+    /// As for [`dot_avx`].
+    #[target_feature(enable = "avx,fma")]
+    pub(super) unsafe fn squared_distance_avx(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let split = n - n % (2 * WIDTH);
+        let (mut acc0, mut acc1) = (_mm256_setzero_ps(), _mm256_setzero_ps());
+        let mut i = 0;
+        while i < split {
+            // SAFETY: as in `dot_avx`.
+            unsafe {
+                let d0 = _mm256_sub_ps(_mm256_loadu_ps(a.as_ptr().add(i)), _mm256_loadu_ps(b.as_ptr().add(i)));
+                let d1 = _mm256_sub_ps(
+                    _mm256_loadu_ps(a.as_ptr().add(i + WIDTH)),
+                    _mm256_loadu_ps(b.as_ptr().add(i + WIDTH)),
+                );
+                acc0 = _mm256_fmadd_ps(d0, d0, acc0);
+                acc1 = _mm256_fmadd_ps(d1, d1, acc1);
+            }
+            i += 2 * WIDTH;
+        }
+        let tail: f32 = a[split..n]
+            .iter()
+            .zip(&b[split..n])
+            .map(|(x, y)| {
+                let d = x - y;
+                d * d
+            })
+            .sum();
+        horizontal_sum(_mm256_add_ps(acc0, acc1)) + tail
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX and FMA.
+    #[target_feature(enable = "avx,fma")]
+    pub(super) unsafe fn zip_map_avx<F>(a: &[f32], b: &[f32], f: F) -> Vec<f32>
+    where
+        F: FnMut(f32, f32) -> f32,
+    {
+        super::zip_map_portable(a, b, f)
+    }
+
+    #[target_feature(enable = "avx")]
+    fn horizontal_sum(v: __m256) -> f32 {
+        let sum = _mm_add_ps(_mm256_castps256_ps128(v), _mm256_extractf128_ps::<1>(v));
+        let sum = _mm_hadd_ps(sum, sum);
+        _mm_cvtss_f32(_mm_hadd_ps(sum, sum))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::{apply_kernel2, rbf_kernel};
+
+    /// Allowed drift from the scalar reference, relative to the sum of the
+    /// absolute terms (which bounds the rounding error of any summation
+    /// order).
+    const REL_EPS: f32 = 1e-5;
+    const LENGTHS: [usize; 8] = [0, 1, 7, 8, 15, 16, 17, 129];
+
+    type Reduce = fn(&[f32], &[f32]) -> f32;
+
+    fn vector(len: usize, seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
+    /// Every implementation of `dot` or `squared_distance`, including the
+    /// AVX one when this CPU can run it.
+    fn implementations(squared: bool) -> Vec<(&'static str, Reduce)> {
+        let mut fns: Vec<(&'static str, Reduce)> = if squared {
+            vec![("dispatched", squared_distance), ("chunked", squared_distance_chunked)]
+        } else {
+            vec![("dispatched", dot), ("chunked", dot_chunked)]
+        };
+        #[cfg(target_arch = "x86_64")]
+        if has_avx_fma() {
+            // SAFETY: only called after the feature check above.
+            fns.push(if squared {
+                ("avx", |a, b| unsafe { x86::squared_distance_avx(a, b) })
+            } else {
+                ("avx", |a, b| unsafe { x86::dot_avx(a, b) })
+            });
+        }
+        fns
+    }
+
+    fn assert_matches_scalar(name: &str, fast: f32, scalar: f32, magnitude: f32) {
+        if scalar.is_nan() {
+            assert!(fast.is_nan(), "{name}: {fast} for NaN");
+        } else if scalar.is_infinite() {
+            assert_eq!(fast, scalar, "{name}");
+        } else {
+            let tolerance = REL_EPS * magnitude.max(1.0);
+            assert!((fast - scalar).abs() <= tolerance, "{name}: {fast} vs {scalar} (tolerance {tolerance})");
+        }
+    }
+
+    #[test]
+    fn dot_matches_scalar() {
+        for len in LENGTHS {
+            let (a, b) = (vector(len, 1), vector(len, 2));
+            let magnitude = a.iter().zip(&b).map(|(x, y)| (x * y).abs()).sum();
+            for (name, f) in implementations(false) {
+                assert_matches_scalar(&format!("{name} dot, len {len}"), f(&a, &b), dot_scalar(&a, &b), magnitude);
+            }
+        }
+    }
+
+    #[test]
+    fn squared_distance_matches_scalar() {
+        for len in LENGTHS {
+            let (a, b) = (vector(len, 3), vector(len, 4));
+            // Every term is non-negative, so the result is its own magnitude.
+            let scalar = squared_distance_scalar(&a, &b);
+            for (name, f) in implementations(true) {
+                assert_matches_scalar(&format!("{name} sq distance, len {len}"), f(&a, &b), scalar, scalar);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_slices_reduce_to_zero() {
+        for (name, f) in implementations(false).into_iter().chain(implementations(true)) {
+            assert_eq!(f(&[], &[]), 0.0, "{name}");
+        }
+    }
+
+    #[test]
+    fn non_finite_inputs_propagate() {
+        // In the vectorized body and in the tail of a 17-long slice.
+        for at in [3, 16] {
+            let a = vector(17, 5);
+            for (bad, expect_nan) in [(f32::NAN, true), (f32::INFINITY, false), (f32::NEG_INFINITY, false)] {
+                let mut b = vector(17, 6);
+                b[at] = bad;
+                for squared in [false, true] {
+                    let scalar = if squared { squared_distance_scalar(&a, &b) } else { dot_scalar(&a, &b) };
+                    assert_eq!(scalar.is_nan(), expect_nan);
+                    for (name, f) in implementations(squared) {
+                        assert_matches_scalar(&format!("{name} with {bad} at {at}"), f(&a, &b), scalar, 0.0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_match_scalar() {
+        for len in LENGTHS {
+            let (a, b) = (vector(len, 7), vector(len, 8));
+            let gamma = 1.0 / len.max(1) as f32;
+            let scalar = (-gamma * squared_distance_scalar(&a, &b)).exp();
+            assert_matches_scalar(&format!("rbf, len {len}"), rbf_kernel(&a, &b, gamma).unwrap(), scalar, 1.0);
+
+            let step = |f: f32, t: f32| f * 0.9 + t * 0.1;
+            let blended = apply_kernel2(&a, &b, step).unwrap();
+            let expected: Vec<f32> = a.iter().zip(&b).map(|(&f, &t)| step(f, t)).collect();
+            // Element-wise, so every path does the same arithmetic.
+            assert_eq!(blended, expected, "zip_map, len {len}");
+            assert_eq!(zip_map_portable(&a, &b, step), expected);
+        }
+    }
+}
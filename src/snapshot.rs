@@ -0,0 +1,211 @@
+//! Saving and restoring a [`MotionSpace`] as a JSON snapshot.
+
+use std::fs;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::motion_core::MotionSpace;
+
+/// Why a snapshot could not be saved or loaded.
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    /// Reading or writing a file failed.
+    #[error("snapshot io error at {path}: {reason}")]
+    Io {
+        /// The file or directory involved.
+        path: String,
+        /// The underlying IO error.
+        reason: String,
+    },
+
+    /// The snapshot could not be serialized, or the file is not a valid snapshot.
+    #[error("snapshot format error: {0}")]
+    Format(String),
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> SnapshotError {
+    SnapshotError::Io {
+        path: path.display().to_string(),
+        reason: e.to_string(),
+    }
+}
+
+impl MotionSpace {
+    /// Writes the space to `path`. The snapshot goes to a sibling temp file,
+    /// `path` with `.tmp` appended, that is synced to disk and then renamed
+    /// into place, so a crash never leaves a torn file and a failed save
+    /// leaves the previous snapshot as it was. On unix the directory is
+    /// synced too, so the rename itself survives a power loss.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let saved = self
+            .write_synced(&tmp)
+            .and_then(|()| fs::rename(&tmp, path).map_err(|e| io_error(path, e)));
+        if saved.is_err() {
+            // Whatever was written is incomplete or was never moved into place.
+            let _ = fs::remove_file(&tmp);
+        }
+        saved?;
+        #[cfg(unix)]
+        {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            fs::File::open(dir)
+                .and_then(|dir| dir.sync_all())
+                .map_err(|e| io_error(dir, e))?;
+        }
+        Ok(())
+    }
+
+    fn write_synced(&self, path: &Path) -> Result<(), SnapshotError> {
+        let file = fs::File::create(path).map_err(|e| io_error(path, e))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self).map_err(|e| SnapshotError::Format(e.to_string()))?;
+        writer.flush().map_err(|e| io_error(path, e))?;
+        let file = writer.into_inner().map_err(|e| io_error(path, e.into_error()))?;
+        file.sync_all().map_err(|e| io_error(path, e))
+    }
+
+    /// Reads a space written by [`MotionSpace::save_snapshot`].
+    pub fn load_snapshot(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|e| io_error(path, e))?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| SnapshotError::Format(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::cluster::{ClusterConfig, Clusters};
+    use crate::motion_input::{Interaction, InteractionType, MotionInput, PostInput};
+
+    /// A directory in the temp dir that is removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("motion-snapshot-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn space() -> MotionSpace {
+        let mut space = MotionSpace::builder()
+            .dim(16)
+            .clusters(ClusterConfig { k: 2, batch_size: 2 })
+            .build()
+            .unwrap();
+        let posts = [("p1", "alice", "rust async"), ("p2", "bob", "garden tomato"), ("p3", "carol", "rust garden")];
+        for (id, user, text) in posts {
+            space.process(MotionInput::Post(PostInput::new(id, user, text))).unwrap();
+        }
+        for (kind, src, dst) in [
+            (InteractionType::Like, "p2", "alice"),
+            (InteractionType::Dislike, "p1", "bob"),
+            (InteractionType::Follow, "carol", "alice"),
+        ] {
+            space.process(MotionInput::Interaction(Interaction::new(kind, src, dst))).unwrap();
+        }
+        space
+    }
+
+    /// The space as JSON, with the graph's edges and every array of strings
+    /// sorted: both come out of hash maps in no particular order.
+    fn canonical(space: &MotionSpace) -> Value {
+        fn sort(value: &mut Value) {
+            match value {
+                Value::Array(items) => {
+                    items.iter_mut().for_each(sort);
+                    if items.iter().all(Value::is_string) {
+                        items.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+                    }
+                }
+                Value::Object(map) => map.values_mut().for_each(sort),
+                _ => {}
+            }
+        }
+        let mut value = serde_json::to_value(space).unwrap();
+        sort(&mut value);
+        if let Value::Array(edges) = &mut value["graph"] {
+            edges.sort_by_key(Value::to_string);
+        }
+        value
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        let dir = TempDir::new("round-trip");
+        let path = dir.0.join("space.json");
+        let space = space();
+        space.save_snapshot(&path).unwrap();
+        assert!(!dir.0.join("space.json.tmp").exists());
+
+        let loaded = MotionSpace::load_snapshot(&path).unwrap();
+        assert_eq!(canonical(&loaded), canonical(&space));
+        assert_eq!(loaded.graph.edges().len(), 3);
+        assert!(loaded.graph.has_user_edge("carol", "alice"));
+        let bucket = loaded.embedder.token_bucket("rust");
+        let rust = loaded.token_index.as_ref().unwrap().tokens(bucket).find(|(t, _)| *t == "rust");
+        assert_eq!(rust.map(|(_, count)| count), Some(2));
+        let summaries = |space: &MotionSpace| space.clusters.as_ref().map(Clusters::summaries).unwrap();
+        assert_eq!(summaries(&loaded).len(), 2);
+        assert_eq!(format!("{:?}", summaries(&loaded)), format!("{:?}", summaries(&space)));
+    }
+
+    #[test]
+    fn the_temp_file_keeps_the_whole_name() {
+        let dir = TempDir::new("temp-name");
+        // `with_extension` would have put both of these at `space.tmp`.
+        fs::create_dir(dir.0.join("space.tmp")).unwrap();
+        space().save_snapshot(dir.0.join("space.json")).unwrap();
+        space().save_snapshot(dir.0.join("space.bak")).unwrap();
+        assert!(MotionSpace::load_snapshot(dir.0.join("space.json")).is_ok());
+        assert!(MotionSpace::load_snapshot(dir.0.join("space.bak")).is_ok());
+    }
+
+    #[test]
+    fn a_failed_save_leaves_the_previous_snapshot() {
+        let dir = TempDir::new("failed-save");
+        let path = dir.0.join("space.json");
+        MotionSpace::new(16).save_snapshot(&path).unwrap();
+        let before = fs::read(&path).unwrap();
+
+        // A directory in the temp file's place makes the write fail.
+        fs::create_dir(dir.0.join("space.json.tmp")).unwrap();
+        let err = space().save_snapshot(&path).unwrap_err();
+        assert!(matches!(err, SnapshotError::Io { .. }), "{err}");
+        assert_eq!(fs::read(&path).unwrap(), before);
+    }
+
+    #[test]
+    fn a_failed_rename_removes_the_temp_file() {
+        let dir = TempDir::new("failed-rename");
+        // A non-empty directory in the snapshot's place makes the rename fail.
+        let path = dir.0.join("space.json");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), "").unwrap();
+
+        let err = space().save_snapshot(&path).unwrap_err();
+        assert!(matches!(err, SnapshotError::Io { .. }), "{err}");
+        assert!(!dir.0.join("space.json.tmp").exists());
+        assert!(path.join("keep").exists());
+    }
+}
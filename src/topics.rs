@@ -0,0 +1,337 @@
+//! Offline topic discovery: density-based clustering of post coords.
+//!
+//! Posts are grouped with DBSCAN, using the space's kernel as the
+//! similarity: two posts are neighbours when `K(a, b) >= min_similarity`, a
+//! post with at least `min_posts` neighbours (itself included) is a core
+//! post, and a topic is everything reachable from a core post through other
+//! core posts. Posts reachable from no core post are reported as noise.
+//!
+//! Each topic is labelled with the tokens that best explain the mean text
+//! embedding of its posts, looked up in the space's [`TokenIndex`].
+//!
+//! [`TokenIndex`]: crate::embedding::TokenIndex
+
+use std::collections::{HashSet, VecDeque};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::motion_core::{CoreError, MotionEntry, MotionPost, MotionSpace};
+
+/// Parameters of [`MotionSpace::discover_topics`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct TopicConfig {
+    /// Kernel similarity at which two posts count as neighbours.
+    pub min_similarity: f32,
+    /// Neighbours, the post itself included, that make a post a core post.
+    pub min_posts: usize,
+    /// Label tokens per topic.
+    pub labels: usize,
+}
+
+impl Default for TopicConfig {
+    fn default() -> Self {
+        Self { min_similarity: 0.5, min_posts: 3, labels: 5 }
+    }
+}
+
+/// A token that describes a topic.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopicLabel {
+    /// The token, lowercased.
+    pub token: String,
+    /// The token's share of the topic's mean text embedding.
+    pub weight: f32,
+}
+
+/// A group of posts that sit close together.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Topic {
+    /// Topics are numbered largest first.
+    pub id: usize,
+    /// Post ids.
+    pub posts: Vec<String>,
+    /// Distinct authors of the posts.
+    pub authors: usize,
+    /// Mean kernel similarity of the posts to the topic's mean coord.
+    pub cohesion: f32,
+    /// Empty when the space keeps no token index.
+    pub labels: Vec<TopicLabel>,
+}
+
+/// Every topic found in one pass over the space.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopicReport {
+    /// Unix millis.
+    pub generated_at: i64,
+    /// The parameters the report was made with.
+    pub config: TopicConfig,
+    /// Posts considered.
+    pub posts: usize,
+    /// Largest first.
+    pub topics: Vec<Topic>,
+    /// Posts in no topic.
+    pub noise: Vec<String>,
+}
+
+impl MotionSpace {
+    /// Clusters every post by its current coord. Quadratic in the number of
+    /// posts; meant for offline reports.
+    pub fn discover_topics(&self, config: &TopicConfig) -> Result<TopicReport, CoreError> {
+        let posts: Vec<&MotionPost> = self
+            .entries
+            .iter()
+            .filter_map(|e| match e {
+                MotionEntry::Post(p) => Some(p),
+                MotionEntry::User(_) => None,
+            })
+            .collect();
+
+        let mut neighbours = vec![Vec::new(); posts.len()];
+        for i in 0..posts.len() {
+            neighbours[i].push(i);
+            for j in i + 1..posts.len() {
+                if self.kernel.apply(&posts[i].coord.data, &posts[j].coord.data)? >= config.min_similarity {
+                    neighbours[i].push(j);
+                    neighbours[j].push(i);
+                }
+            }
+        }
+        let is_core = |i: usize| neighbours[i].len() >= config.min_posts.max(1);
+
+        let mut topic_of: Vec<Option<usize>> = vec![None; posts.len()];
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for seed in 0..posts.len() {
+            if topic_of[seed].is_some() || !is_core(seed) {
+                continue;
+            }
+            let topic = groups.len();
+            let mut members = Vec::new();
+            let mut queue = VecDeque::from([seed]);
+            topic_of[seed] = Some(topic);
+            while let Some(i) = queue.pop_front() {
+                members.push(i);
+                if !is_core(i) {
+                    continue;
+                }
+                for &j in &neighbours[i] {
+                    if topic_of[j].is_none() {
+                        topic_of[j] = Some(topic);
+                        queue.push_back(j);
+                    }
+                }
+            }
+            groups.push(members);
+        }
+        groups.sort_by_key(|members| std::cmp::Reverse(members.len()));
+
+        let mut topics = Vec::with_capacity(groups.len());
+        for (id, members) in groups.into_iter().enumerate() {
+            topics.push(self.describe_topic(id, &members, &posts, config)?);
+        }
+        let noise = posts
+            .iter()
+            .zip(&topic_of)
+            .filter(|(_, topic)| topic.is_none())
+            .map(|(p, _)| p.id.clone())
+            .collect();
+
+        Ok(TopicReport {
+            generated_at: Utc::now().timestamp_millis(),
+            config: *config,
+            posts: posts.len(),
+            topics,
+            noise,
+        })
+    }
+
+    fn describe_topic(
+        &self,
+        id: usize,
+        members: &[usize],
+        posts: &[&MotionPost],
+        config: &TopicConfig,
+    ) -> Result<Topic, CoreError> {
+        let centroid = mean(members.iter().map(|&i| posts[i].coord.data.as_slice()), self.dim);
+        let mut cohesion = 0.0;
+        for &i in members {
+            cohesion += self.kernel.apply(&posts[i].coord.data, &centroid)?;
+        }
+        cohesion /= members.len() as f32;
+
+        // Coords drift toward the audience; the text a topic is about is in
+        // the embeddings the posts entered with.
+        let labels = match &self.token_index {
+            Some(index) => {
+                let text_centroid = mean(members.iter().map(|&i| posts[i].origin.data.as_slice()), self.dim);
+                index
+                    .top_tokens(&text_centroid, config.labels)
+                    .into_iter()
+                    .map(|(token, weight)| TopicLabel { token, weight })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        let mut ids: Vec<String> = members.iter().map(|&i| posts[i].id.clone()).collect();
+        ids.sort();
+        let authors: HashSet<&str> = members.iter().map(|&i| posts[i].author_id.as_str()).collect();
+        Ok(Topic {
+            id,
+            posts: ids,
+            authors: authors.len(),
+            cohesion,
+            labels,
+        })
+    }
+}
+
+fn mean<'a>(vectors: impl Iterator<Item = &'a [f32]>, dim: usize) -> Vec<f32> {
+    let mut sum = vec![0.0; dim];
+    let mut n = 0;
+    for v in vectors {
+        for (s, x) in sum.iter_mut().zip(v) {
+            *s += x;
+        }
+        n += 1;
+    }
+    if n > 0 {
+        sum.iter_mut().for_each(|s| *s /= n as f32);
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::VecN;
+
+    const DIM: usize = 16;
+
+    fn axis(bucket: usize, scale: f32) -> VecN {
+        let mut data = vec![0.0; DIM];
+        data[bucket] = scale;
+        VecN::new(data)
+    }
+
+    /// Enters a post whose text embedding is `origin` but which has since
+    /// drifted to `coord`.
+    fn add_post(space: &mut MotionSpace, id: &str, author: &str, text: &str, origin: VecN, coord: VecN) {
+        let mut post = MotionPost::new(id.to_string(), author.to_string(), origin);
+        post.coord = coord;
+        space.enter(MotionEntry::Post(post));
+        if let Some(index) = space.token_index.as_mut() {
+            index.observe(id, text);
+        }
+    }
+
+    /// Under the default gamma of 2 and `min_similarity` 0.5, posts are
+    /// neighbours up to a distance of about 0.589.
+    ///
+    /// - garden: five posts about gardening, drifted onto the chess axis.
+    /// - chess: three core posts drifted onto the garden axis, plus a border
+    ///   post within reach of only one of them.
+    /// - noise: a lone post, and a pair too small to form a core.
+    fn space() -> MotionSpace {
+        let mut space = MotionSpace::new(DIM);
+        let garden = space.embedder.token_bucket("garden");
+        let chess = space.embedder.token_bucket("chess");
+        let other = (0..DIM).find(|&b| b != garden && b != chess).unwrap();
+        assert_ne!(garden, chess);
+        // Smaller topic first, so ids have to be assigned by size.
+        for (i, at) in [1.0, 1.1, 1.2, 1.7].into_iter().enumerate() {
+            add_post(&mut space, &format!("c{i}"), "carol", "chess", axis(chess, 1.0), axis(garden, at));
+        }
+        for (i, at) in [1.0, 1.1, 1.2, 1.3, 1.4].into_iter().enumerate() {
+            let author = if i % 2 == 0 { "alice" } else { "bob" };
+            add_post(&mut space, &format!("g{i}"), author, "garden", axis(garden, 1.0), axis(chess, at));
+        }
+        add_post(&mut space, "lone", "dave", "noise", axis(other, 1.0), axis(other, 3.0));
+        add_post(&mut space, "pair0", "dave", "noise", axis(other, 1.0), axis(other, 1.0));
+        add_post(&mut space, "pair1", "dave", "noise", axis(other, 1.0), axis(other, 1.1));
+        space
+    }
+
+    fn config() -> TopicConfig {
+        TopicConfig { min_similarity: 0.5, min_posts: 3, labels: 1 }
+    }
+
+    #[test]
+    fn core_border_and_noise_posts_are_told_apart() {
+        let report = space().discover_topics(&config()).unwrap();
+        assert_eq!(report.posts, 12);
+        assert_eq!(report.topics.len(), 2);
+        assert_eq!(report.topics[0].id, 0);
+        assert_eq!(report.topics[0].posts, ["g0", "g1", "g2", "g3", "g4"]);
+        assert_eq!(report.topics[0].authors, 2);
+        // c3 is a border post: reachable from c2 but not a core post itself.
+        assert_eq!(report.topics[1].id, 1);
+        assert_eq!(report.topics[1].posts, ["c0", "c1", "c2", "c3"]);
+        assert_eq!(report.topics[1].authors, 1);
+        let mut noise = report.noise.clone();
+        noise.sort();
+        assert_eq!(noise, ["lone", "pair0", "pair1"]);
+
+        // Two neighbours are enough to make the pair a topic of its own.
+        let report = space().discover_topics(&TopicConfig { min_posts: 2, ..config() }).unwrap();
+        assert_eq!(report.topics.len(), 3);
+        assert_eq!(report.topics[2].posts, ["pair0", "pair1"]);
+        assert_eq!(report.noise, ["lone"]);
+    }
+
+    #[test]
+    fn topics_are_labelled_by_origin_not_coord() {
+        let report = space().discover_topics(&config()).unwrap();
+        assert_eq!(report.topics[0].labels.len(), 1);
+        assert_eq!(report.topics[0].labels[0].token, "garden");
+        assert!((report.topics[0].labels[0].weight - 1.0).abs() < 1e-6);
+        assert_eq!(report.topics[1].labels[0].token, "chess");
+
+        let mut space = space();
+        space.token_index = None;
+        let report = space.discover_topics(&config()).unwrap();
+        assert!(report.topics.iter().all(|t| t.labels.is_empty()));
+    }
+
+    #[test]
+    fn cohesion_is_mean_similarity_to_the_centroid() {
+        let space = space();
+        let posts: Vec<&MotionPost> = space
+            .entries
+            .iter()
+            .filter_map(|e| match e {
+                MotionEntry::Post(p) => Some(p),
+                MotionEntry::User(_) => None,
+            })
+            .collect();
+        // c0 and c2 sit 0.1 either side of their centroid at c1.
+        let topic = space.describe_topic(7, &[2, 0, 1], &posts, &config()).unwrap();
+        assert_eq!(topic.id, 7);
+        assert_eq!(topic.posts, ["c0", "c1", "c2"]);
+        let expected = (1.0 + 2.0 * (-2.0f32 * 0.01).exp()) / 3.0;
+        assert!((topic.cohesion - expected).abs() < 1e-5, "{} != {expected}", topic.cohesion);
+    }
+
+    #[test]
+    fn reports_serialize_to_json() {
+        let report = space().discover_topics(&config()).unwrap();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["posts"], 12);
+        assert_eq!(json["config"]["min_posts"], 3);
+        assert_eq!(json["config"]["labels"], 1);
+        assert_eq!(json["topics"][0]["id"], 0);
+        assert_eq!(json["topics"][0]["posts"][0], "g0");
+        assert_eq!(json["topics"][0]["authors"], 2);
+        assert_eq!(json["topics"][0]["labels"][0]["token"], "garden");
+        assert!(json["topics"][1]["cohesion"].as_f64().unwrap() > 0.5);
+        assert_eq!(json["noise"].as_array().unwrap().len(), 3);
+        assert!(json["generated_at"].as_i64().unwrap() > 0);
+
+        let back: TopicReport = serde_json::from_value(json).unwrap();
+        assert_eq!(back.topics[1].posts, report.topics[1].posts);
+        // Missing parameters fall back to their defaults.
+        let config: TopicConfig = serde_json::from_str(r#"{"min_posts": 4}"#).unwrap();
+        assert_eq!((config.min_posts, config.min_similarity, config.labels), (4, 0.5, 5));
+    }
+}